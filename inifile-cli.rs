@@ -0,0 +1,387 @@
+//! Query and edit INI files from the command line, keeping their comments and layout.
+//!
+//! inifile get config.ini server port
+//! inifile set config.ini server port 8080
+//! inifile validate config.ini --schema schema.ini
+//!
+//! Exit codes: 0 success; 1 missing section or option, invalid file, or differences found;
+//! 2 usage error; 3 I/O error; 4 syntax error in the file.
+//!
+//! Syntax errors and schema violations are printed as rustc diagnostics, colored on a terminal unless
+//! NO_COLOR is set.
+//!
+//! @license MIT license <http://www.opensource.org/licenses/mit-license.php>
+
+extern crate getopts;
+extern crate inifile;
+use getopts::Options;
+use inifile::{Diagnostic, IniError, IniFile, IniResult, Schema};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+
+const EXIT_OK: i32 = 0;
+const EXIT_NOT_FOUND: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO: i32 = 3;
+const EXIT_SYNTAX: i32 = 4;
+
+const COMMANDS: &str = "
+Commands:
+    get FILE SECTION OPTION        print the value of an option
+    set FILE SECTION OPTION VALUE  set an option, adding the section if needed
+    unset FILE SECTION OPTION      remove an option
+    sections FILE                  list the sections
+    options FILE SECTION           list the options of a section
+    validate FILE                  check the syntax, and the schema given by --schema
+    fmt FILE                       print the file in a canonical layout, or rewrite it with -i
+    diff FILE OTHER                list the changes from FILE to OTHER
+
+Exit codes: 0 success; 1 missing section or option, invalid file, or differences found;
+2 usage error; 3 I/O error; 4 syntax error.";
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} COMMAND FILE [ARGS] [options]", program);
+    println!("{}{}", opts.usage(&brief), COMMANDS);
+}
+
+/**
+ * Exit code telling why an operation failed; an included file fails as its cause.
+ */
+fn exit_code(e: &IniError) -> i32 {
+    match *e {
+        IniError::Io(_) => EXIT_IO,
+        IniError::Syntax(_) | IniError::IncludeCycle(_) => EXIT_SYNTAX,
+        IniError::Include { ref cause, .. } => exit_code(cause),
+        _ => EXIT_NOT_FOUND,
+    }
+}
+
+/**
+ * Exit code telling why an operation failed, and its diagnostics on stderr.
+ */
+fn fail(e: &IniError) -> i32 {
+    let color = colored(&io::stderr());
+    for diagnostic in e.diagnostics() {
+        if diagnostic.location.is_some() {
+            eprint!("{}", render(&diagnostic, color));
+        } else {
+            eprintln!("inifile: {}", diagnostic.message);
+        }
+    }
+    exit_code(e)
+}
+
+/**
+ * Whether the diagnostics written to a stream are colored: it is a terminal, and NO_COLOR is not set.
+ */
+fn colored<T: IsTerminal>(stream: &T) -> bool {
+    stream.is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/**
+ * A diagnostic, colored or as plain text.
+ */
+fn render(diagnostic: &Diagnostic, color: bool) -> String {
+    if color {
+        diagnostic.render_ansi()
+    } else {
+        diagnostic.render()
+    }
+}
+
+fn load(path: &str) -> IniResult<IniFile> {
+    IniFile::load(path)
+}
+
+/**
+ * Run a command; `args` are the arguments after the command name, `out` receives the output.
+ */
+fn run_command(
+    command: &str,
+    args: &[String],
+    matches: &getopts::Matches,
+    out: &mut dyn Write,
+) -> IniResult<i32> {
+    let expected = match command {
+        "sections" | "validate" | "fmt" => 1,
+        "options" | "diff" => 2,
+        "get" | "unset" => 3,
+        "set" => 4,
+        _ => {
+            eprintln!("inifile: unknown command {:?}", command);
+            return Ok(EXIT_USAGE);
+        }
+    };
+    if args.len() != expected {
+        eprintln!(
+            "inifile: {} expects {} arguments, found {}",
+            command,
+            expected,
+            args.len()
+        );
+        return Ok(EXIT_USAGE);
+    }
+    let mut ini = load(&args[0])?;
+    match command {
+        "get" => {
+            let value = ini.try_get(&args[1], &args[2])?;
+            writeln!(out, "{}", value)?;
+        }
+        "set" => {
+            if args[1] != ini.default_section() && !ini.has_section(&args[1]) {
+                ini.try_add_section(&args[1])?;
+            }
+            ini.try_set(args[1].clone(), args[2].clone(), args[3].clone())?;
+            ini.try_save()?;
+        }
+        "unset" => {
+            if !ini.try_remove_option(args[1].clone(), args[2].clone())? {
+                return Err(IniError::MissingOption {
+                    section: args[1].clone(),
+                    option: args[2].clone(),
+                });
+            }
+            ini.try_save()?;
+        }
+        "sections" => {
+            for section in ini.sections() {
+                writeln!(out, "{}", section)?;
+            }
+        }
+        "options" => {
+            if args[1] != ini.default_section() && !ini.has_section(&args[1]) {
+                return Err(IniError::MissingSection(args[1].clone()));
+            }
+            for option in ini.options(args[1].clone()) {
+                writeln!(out, "{}", option)?;
+            }
+        }
+        "validate" => {
+            if let Some(path) = matches.opt_str("schema") {
+                let schema = Schema::from_ini(&load(&path)?)?;
+                let report = schema.validate(&ini);
+                if !report.violations.is_empty() {
+                    let color = colored(&io::stdout());
+                    for diagnostic in report.diagnostics() {
+                        writeln!(out, "{}", render(&diagnostic, color))?;
+                    }
+                    writeln!(
+                        out,
+                        "{} error(s), {} warning(s)",
+                        report.errors().len(),
+                        report.warnings().len()
+                    )?;
+                }
+                if !report.is_valid() {
+                    return Ok(EXIT_NOT_FOUND);
+                }
+            }
+        }
+        "fmt" => {
+            let before = ini.to_string();
+            ini.normalize();
+            let after = ini.to_string();
+            if matches.opt_present("check") {
+                if before != after {
+                    eprintln!("inifile: {} is not formatted", args[0]);
+                    return Ok(EXIT_NOT_FOUND);
+                }
+            } else if matches.opt_present("i") {
+                if before != after {
+                    ini.try_save()?;
+                }
+            } else {
+                write!(out, "{}", after)?;
+            }
+        }
+        "diff" => {
+            let changes = ini.diff(&load(&args[1])?);
+            for change in changes.iter() {
+                writeln!(out, "{}", change)?;
+            }
+            if !changes.is_empty() {
+                return Ok(EXIT_NOT_FOUND);
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(EXIT_OK)
+}
+
+/**
+ * Parse the arguments, the program name included, run the command and return the exit code.
+ */
+fn run(args: &[String], out: &mut dyn Write) -> i32 {
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optflag(
+        "i",
+        "in-place",
+        "fmt: rewrite the file instead of printing it",
+    );
+    opts.optflag("", "check", "fmt: only tell whether the file is formatted");
+    opts.optopt(
+        "",
+        "schema",
+        "validate: schema declared in an INI file",
+        "SCHEMA",
+    );
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("inifile: {}", f);
+            return EXIT_USAGE;
+        }
+    };
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return EXIT_OK;
+    }
+    if matches.free.is_empty() {
+        print_usage(&program, &opts);
+        return EXIT_USAGE;
+    }
+    match run_command(&matches.free[0], &matches.free[1..], &matches, out) {
+        Ok(code) => code,
+        Err(e) => fail(&e),
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let code = run(&args, &mut std::io::stdout());
+    std::process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, EXIT_IO, EXIT_NOT_FOUND, EXIT_OK, EXIT_SYNTAX, EXIT_USAGE};
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    /// File of the inifile crate, read by the tests.
+    const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/inifile/src/data/config.ini");
+
+    fn inifile(args: &[&str]) -> (i32, String) {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.insert(0, "inifile".to_string());
+        let mut out = Vec::new();
+        let code = run(&args, &mut out);
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    /**
+     * Empty temporary directory of the test `name`, holding the files it writes.
+     */
+    fn directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("inifile-cli-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /**
+     * Path of the file `name` in the directory, as an argument.
+     */
+    fn file(directory: &Path, name: &str) -> String {
+        directory.join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn query() {
+        let path = CONFIG;
+        assert_eq!(
+            (EXIT_OK, "2\n".to_string()),
+            inifile(&["get", path, "Integers", "integer2"])
+        );
+        assert_eq!(
+            EXIT_NOT_FOUND,
+            inifile(&["get", path, "Integers", "missing"]).0
+        );
+        assert_eq!(
+            EXIT_NOT_FOUND,
+            inifile(&["get", path, "Missing", "integer2"]).0
+        );
+        let (code, out) = inifile(&["sections", path]);
+        assert_eq!(EXIT_OK, code);
+        assert!(out.contains("Integers\n"), "{}", out);
+        let (code, out) = inifile(&["options", path, "Integers"]);
+        assert_eq!(EXIT_OK, code);
+        assert!(out.starts_with("integer0\n"), "{}", out);
+        assert_eq!(EXIT_NOT_FOUND, inifile(&["options", path, "Missing"]).0);
+        assert_eq!(EXIT_OK, inifile(&["validate", path]).0);
+        let directory = directory("query");
+        let missing = file(&directory, "missing.ini");
+        assert_eq!(EXIT_IO, inifile(&["get", &missing, "a", "b"]).0);
+        fs::remove_dir_all(directory).unwrap();
+        assert_eq!(EXIT_USAGE, inifile(&["get", path]).0);
+        assert_eq!(EXIT_USAGE, inifile(&["frobnicate", path]).0);
+        assert_eq!(EXIT_USAGE, inifile(&[]).0);
+        assert_eq!(EXIT_USAGE, inifile(&["get", "--unknown"]).0);
+    }
+    #[test]
+    fn edit_in_place() {
+        let directory = directory("edit_in_place");
+        let path = &file(&directory, "edit.ini");
+        fs::write(path, "# comment\n[server]\nport=80 \n\n# end\n").unwrap();
+        assert_eq!(EXIT_OK, inifile(&["set", path, "server", "port", "8080"]).0);
+        assert_eq!(EXIT_OK, inifile(&["set", path, "new", "key", "value"]).0);
+        assert_eq!(EXIT_OK, inifile(&["unset", path, "server", "port"]).0);
+        assert_eq!(
+            EXIT_NOT_FOUND,
+            inifile(&["unset", path, "server", "port"]).0
+        );
+        assert_eq!(EXIT_OK, inifile(&["set", path, "server", "port", "1"]).0);
+        let found = fs::read_to_string(path).unwrap();
+        assert_eq!(
+            "# comment\n[server]\nport=1\n\n# end\n[new]\nkey=value\n",
+            found
+        );
+        fs::write(path, "[a]\nkey=1\n[b]\n").unwrap();
+        assert_eq!(EXIT_NOT_FOUND, inifile(&["fmt", "--check", path]).0);
+        assert_eq!(
+            (EXIT_OK, "[a]\nkey = 1\n\n[b]\n".to_string()),
+            inifile(&["fmt", path])
+        );
+        assert_eq!(EXIT_OK, inifile(&["fmt", "-i", path]).0);
+        assert_eq!(EXIT_OK, inifile(&["fmt", "--check", path]).0);
+        fs::write(path, "[a\n").unwrap();
+        assert_eq!(EXIT_SYNTAX, inifile(&["validate", path]).0);
+        fs::write(path, "!include missing.ini\n").unwrap();
+        assert_eq!(EXIT_IO, inifile(&["validate", path]).0);
+        fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn validate_and_diff() {
+        let directory = directory("validate_and_diff");
+        let path = &file(&directory, "validate.ini");
+        let schema = &file(&directory, "schema.ini");
+        let other = &file(&directory, "other.ini");
+        fs::write(path, "[server]\nport = 0\n").unwrap();
+        fs::write(schema, "[server]\nport = int min=1\n").unwrap();
+        fs::write(other, "[server]\nport = 1\n").unwrap();
+        let (code, out) = inifile(&["validate", path, "--schema", schema]);
+        assert_eq!(EXIT_NOT_FOUND, code);
+        assert!(out.starts_with("error: [server] port: "), "{}", out);
+        assert!(
+            out.contains("validate.ini:2:8\n  |\n2 | port = 0\n  |        ^\n"),
+            "{}",
+            out
+        );
+        assert!(out.ends_with("\n1 error(s), 0 warning(s)\n"), "{}", out);
+        assert_eq!(EXIT_OK, inifile(&["validate", other, "--schema", schema]).0);
+        let (code, out) = inifile(&["diff", path, other]);
+        assert_eq!(
+            (EXIT_NOT_FOUND, "~ [server] port = 0 -> 1\n".to_string()),
+            (code, out)
+        );
+        assert_eq!(EXIT_OK, inifile(&["diff", path, path]).0);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
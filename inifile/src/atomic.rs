@@ -0,0 +1,220 @@
+//! Crash-safe writing of a file, and advisory locking of a file between processes.
+//!
+//! The content is written to a temporary file in the same directory, synced to disk, then renamed over
+//! the file: a crash leaves either the old or the new file, never a truncated one.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of the next temporary file of the process, so that threads saving the same file do not clash.
+static TEMPORARY: AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * Path of the file a path designates: the target of a symbolic link, so that the link is kept.
+ */
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/**
+ * Path of a file next to `path`, its name being the name of `path` followed by `suffix`.
+ */
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}{}{}", prefix, name, suffix))
+}
+
+/**
+ * Path of the backup number `index` of a file: `config.ini.bak`, then `config.ini.bak.1`, ...
+ */
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        sibling(path, "", ".bak")
+    } else {
+        sibling(path, "", &format!(".bak.{}", index))
+    }
+}
+
+/**
+ * Shift the backups of a file, dropping the oldest one, then copy the file as the newest backup.
+ */
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    for index in (1..backups).rev() {
+        let older = backup_path(path, index - 1);
+        if older.exists() {
+            fs::rename(&older, backup_path(path, index))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 0))?;
+    Ok(())
+}
+
+/**
+ * Sync a directory, so that a rename in it is durable.
+ */
+#[cfg(unix)]
+fn sync_directory(directory: &Path) -> io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_directory: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/**
+ * Replace the content of a file atomically, keeping its permissions, and keep its former content in
+ * `backups` rotated backups.
+ */
+pub fn write(path: &Path, content: &[u8], backups: usize) -> io::Result<()> {
+    let path = resolve(path)?;
+    let permissions = fs::metadata(&path).ok().map(|m| m.permissions());
+    let number = TEMPORARY.fetch_add(1, Ordering::Relaxed);
+    let temporary = sibling(&path, ".", &format!(".tmp{}.{}", process::id(), number));
+    let result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            if let Some(permissions) = permissions.clone() {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    if backups > 0 && permissions.is_some() {
+        if let Err(e) = rotate_backups(&path, backups) {
+            let _ = fs::remove_file(&temporary);
+            return Err(e);
+        }
+    }
+    if let Err(e) = fs::rename(&temporary, &path) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+    match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => sync_directory(directory),
+        _ => sync_directory(Path::new(".")),
+    }
+}
+
+/**
+ * Advisory lock on a file, released when dropped; see `IniFile::try_lock()`.
+ * The lock is held on a `.lock` file next to it, which the atomic save does not replace.
+ */
+#[derive(Debug)]
+pub struct Lock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Lock {
+    /**
+     * Wait until the lock of the file is acquired.
+     */
+    pub(crate) fn acquire(path: &Path) -> io::Result<Lock> {
+        let path = sibling(&resolve(path)?, "", ".lock");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        file.lock()?;
+        Ok(Lock { file, path })
+    }
+    /**
+     * Path of the lock file.
+     */
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_path, write, Lock};
+    use std::fs;
+    use test_directory;
+
+    #[test]
+    fn write_with_backups() {
+        let directory = test_directory("atomic-backups");
+        let path = &directory.join("atomic_test.ini");
+        for content in ["1", "2", "3", "4"].iter() {
+            write(path, content.as_bytes(), 2).unwrap();
+        }
+        assert_eq!("4", fs::read_to_string(path).unwrap());
+        assert_eq!("3", fs::read_to_string(backup_path(path, 0)).unwrap());
+        assert_eq!("2", fs::read_to_string(backup_path(path, 1)).unwrap());
+        assert!(!backup_path(path, 2).exists());
+        assert!(write(&directory.join("missing/atomic_test.ini"), b"", 0).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let directory = test_directory("atomic-mode");
+        let path = &directory.join("atomic_mode_test.ini");
+        fs::write(path, "1").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        write(path, b"2", 0).unwrap();
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(0o600, mode & 0o777);
+    }
+    #[test]
+    fn lock() {
+        let directory = test_directory("lock");
+        let lock = Lock::acquire(&directory.join("lock_test.ini")).unwrap();
+        assert_eq!(directory.join("lock_test.ini.lock"), lock.path());
+        let file = fs::File::open(lock.path()).unwrap();
+        assert!(file.try_lock().is_err());
+        drop(lock);
+        assert!(file.try_lock().is_ok());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn write_from_threads() {
+        let directory = test_directory("atomic-threads");
+        let path = directory.join("atomic_threads_test.ini");
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let path = path.clone();
+                ::std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        write(&path, format!("[t]\nn = {}\n", thread).as_bytes(), 0).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(fs::read_to_string(&path).unwrap().starts_with("[t]\nn = "));
+        let left = fs::read_dir(&directory).unwrap().count();
+        fs::remove_dir_all(&directory).unwrap();
+        // No temporary file is left.
+        assert_eq!(1, left);
+    }
+}
@@ -1,14 +1,98 @@
-#![feature(test)]
-#[cfg(test)]
-extern crate test;
-
-#[cfg(test)]
-use test::Bencher;
-
-#[bench]
-fn bench_inifile(b: &mut Bencher) {
-	b.iter(|| {
-		let mut ini = IniFile::new();
-		ini.read("data/config.ini");
-	});
+//! Compare the time taken to read a large generated INI file: into an `IniFile`, with the pull parser,
+//! and with the line reader. Fail if reading into an `IniFile` an eighth of the file is not about
+//! eight times faster.
+//!
+//! cargo bench -- 100
+//!
+//! The argument is the size of the file in MB, 20 by default.
+
+extern crate inifile;
+
+use inifile::{Dialect, Event, IniFile, Parser, Reader};
+use std::env;
+use std::time::Instant;
+
+/**
+ * INI text of about `size` bytes: sections of 50 options, with comments and blank lines, then a section
+ * with a value of a line per 10 kB.
+ */
+fn generate(size: usize) -> String {
+    let mut text = String::with_capacity(size + 1024);
+    let mut section = 0;
+    while text.len() < size * 9 / 10 {
+        text.push_str(&format!("\n; section {}\n[section{}]\n", section, section));
+        for option in 0..50 {
+            text.push_str(&format!(
+                "option{} = value of option {} in section {}\n",
+                option, option, section
+            ));
+        }
+        section += 1;
+    }
+    text.push_str("\n[notes]\ntext = first line\n");
+    for line in 0..size / 10_000 {
+        text.push_str(&format!("    line {} of the notes\n", line));
+    }
+    text
+}
+
+/**
+ * Run `f`, print its throughput and return its time in seconds.
+ */
+fn measure<F: FnOnce() -> usize>(name: &str, size: usize, f: F) -> f64 {
+    let start = Instant::now();
+    let count = f();
+    let elapsed = start.elapsed();
+    let seconds = elapsed.as_secs_f64();
+    println!(
+        "{:<10} {:>8.3} s {:>10.1} MB/s ({})",
+        name,
+        seconds,
+        size as f64 / 1e6 / seconds,
+        count
+    );
+    seconds
+}
+
+fn main() {
+    // cargo bench passes --bench
+    let megabytes = env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .next()
+        .unwrap_or(20);
+    let text = generate(megabytes * 1_000_000);
+    println!("{} bytes, {} lines", text.len(), text.lines().count());
+
+    let eighth = generate(megabytes * 125_000);
+    let small = measure("IniFile/8", eighth.len(), || {
+        let ini: IniFile = eighth.parse().unwrap();
+        ini.sections().len()
+    });
+    let large = measure("IniFile", text.len(), || {
+        let ini: IniFile = text.parse().unwrap();
+        ini.sections().len()
+    });
+    // About 8 when linear, 64 when quadratic.
+    assert!(
+        large < small * 24.0,
+        "reading into an IniFile is not linear: {:.3} s for the file, {:.3} s for an eighth",
+        large,
+        small
+    );
+    measure("Parser", text.len(), || {
+        Parser::new(&text, Dialect::Python)
+            .filter(|event| matches!(*event, Ok(Event::KeyValue { .. })))
+            .count()
+    });
+    measure("Reader", text.len(), || {
+        let mut reader = Reader::new(text.as_bytes(), Dialect::Python);
+        let mut count = 0;
+        while let Some(event) = reader.next_event() {
+            if let Event::KeyValue { .. } = event.unwrap() {
+                count += 1;
+            }
+        }
+        count
+    });
 }
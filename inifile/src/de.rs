@@ -0,0 +1,477 @@
+//! Deserialize an `IniFile` into a typed structure, with serde.
+//!
+//! Sections are deserialized into nested structures and options into fields.
+//! Options of the default section can be read as top-level fields.
+//!
+//! Values are read with `IniFile::get()`, so they are interpolated. A missing or empty option gives `None` for an
+//! `Option<T>` field, a list is split as `IniFile::get_list()` does, and a unit enum
+//! variant from its name.
+//!
+//! @see https://serde.rs/impl-deserializer.html
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+
+use error::{IniError, IniResult};
+use value;
+use IniFile;
+
+/**
+ * Deserialize a structure from the sections and options of an INI file.
+ */
+pub fn from_ini<T: DeserializeOwned>(ini: &IniFile) -> IniResult<T> {
+    T::deserialize(Deserializer::new(ini))
+}
+
+/**
+ * Deserialize a structure from the text of an INI file.
+ */
+pub fn from_str<T: DeserializeOwned>(text: &str) -> IniResult<T> {
+    from_ini(&text.parse::<IniFile>()?)
+}
+
+impl de::Error for IniError {
+    fn custom<T: ::std::fmt::Display>(msg: T) -> IniError {
+        IniError::Serde(msg.to_string())
+    }
+}
+
+/**
+ * Deserializer of a whole INI file: a map of the default options and of the sections.
+ */
+pub struct Deserializer<'a> {
+    ini: &'a IniFile,
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(ini: &'a IniFile) -> Deserializer<'a> {
+        Deserializer { ini }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = IniError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        let default_section = self.ini.default_section();
+        let mut entries: Vec<(String, Option<String>)> = self
+            .ini
+            .options(default_section.clone())
+            .into_iter()
+            .map(|option| (option, Some(default_section.clone())))
+            .collect();
+        for section in self.ini.sections() {
+            entries.push((section, None));
+        }
+        visitor.visit_map(FileAccess {
+            ini: self.ini,
+            entries: entries.into_iter(),
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+fn key_deserializer(key: &str) -> de::value::StringDeserializer<IniError> {
+    key.to_string().into_deserializer()
+}
+
+/**
+ * Entries of the file: `(option, Some(default section))` or `(section, None)`.
+ */
+struct FileAccess<'a> {
+    ini: &'a IniFile,
+    entries: ::std::vec::IntoIter<(String, Option<String>)>,
+    value: Option<(String, Option<String>)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for FileAccess<'a> {
+    type Error = IniError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> IniResult<Option<K::Value>> {
+        match self.entries.next() {
+            Some((name, default_section)) => {
+                let key = seed.deserialize(key_deserializer(&name))?;
+                self.value = Some((name, default_section));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> IniResult<V::Value> {
+        match self.value.take() {
+            Some((option, Some(section))) => {
+                seed.deserialize(ValueDeserializer::new(self.ini, &section, &option)?)
+            }
+            Some((section, None)) => seed.deserialize(SectionDeserializer {
+                ini: self.ini,
+                section,
+            }),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+/**
+ * Deserializer of a section: a map of its options.
+ */
+struct SectionDeserializer<'a> {
+    ini: &'a IniFile,
+    section: String,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for SectionDeserializer<'a> {
+    type Error = IniError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        let options = self.ini.options(self.section.clone());
+        visitor.visit_map(SectionAccess {
+            ini: self.ini,
+            section: self.section,
+            options: options.into_iter(),
+            option: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct SectionAccess<'a> {
+    ini: &'a IniFile,
+    section: String,
+    options: ::std::vec::IntoIter<String>,
+    option: Option<String>,
+}
+
+impl<'de, 'a> MapAccess<'de> for SectionAccess<'a> {
+    type Error = IniError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> IniResult<Option<K::Value>> {
+        match self.options.next() {
+            Some(option) => {
+                let key = seed.deserialize(key_deserializer(&option))?;
+                self.option = Some(option);
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> IniResult<V::Value> {
+        match self.option.take() {
+            Some(option) => {
+                seed.deserialize(ValueDeserializer::new(self.ini, &self.section, &option)?)
+            }
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+/**
+ * Deserializer of an option value.
+ */
+struct ValueDeserializer<'a> {
+    ini: &'a IniFile,
+    section: String,
+    option: String,
+    value: String,
+}
+
+impl<'a> ValueDeserializer<'a> {
+    fn new(ini: &'a IniFile, section: &str, option: &str) -> IniResult<ValueDeserializer<'a>> {
+        Ok(ValueDeserializer {
+            ini,
+            section: section.to_string(),
+            option: option.to_string(),
+            value: ini.try_get(section, option)?,
+        })
+    }
+
+    fn mismatch(self, expected: &'static str) -> IniError {
+        IniError::TypeMismatch {
+            section: self.section,
+            option: self.option,
+            value: self.value,
+            expected,
+        }
+    }
+
+    /**
+     * Items of a list, split on the list delimiter and on new lines, see `IniFile::get_list()`.
+     */
+    fn items(&self) -> Vec<ValueDeserializer<'a>> {
+        self.ini
+            .split_list(&self.value)
+            .into_iter()
+            .map(|item| ValueDeserializer {
+                ini: self.ini,
+                section: self.section.clone(),
+                option: self.option.clone(),
+                value: item.to_string(),
+            })
+            .collect()
+    }
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident => $visit:ident;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+                match value::parse_int(&self.value) {
+                    Some(parsed) => visitor.$visit(parsed),
+                    None => Err(self.mismatch("an integer")),
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $expected:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+                match self.value.trim().parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Err(self.mismatch($expected)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = IniError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        visitor.visit_string(self.value)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        match self.ini.parse_bool(&self.value) {
+            Some(b) => visitor.visit_bool(b),
+            None => Err(self.mismatch("a boolean")),
+        }
+    }
+
+    deserialize_int! {
+        deserialize_i8 => visit_i8;
+        deserialize_i16 => visit_i16;
+        deserialize_i32 => visit_i32;
+        deserialize_i64 => visit_i64;
+        deserialize_i128 => visit_i128;
+        deserialize_u8 => visit_u8;
+        deserialize_u16 => visit_u16;
+        deserialize_u32 => visit_u32;
+        deserialize_u64 => visit_u64;
+        deserialize_u128 => visit_u128;
+    }
+
+    deserialize_parsed! {
+        deserialize_f32 => visit_f32, "a float";
+        deserialize_f64 => visit_f64, "a float";
+        deserialize_char => visit_char, "a character";
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        if self.value.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> IniResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> IniResult<V::Value> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(self.items().into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> IniResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> IniResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> IniResult<V::Value> {
+        let value: de::value::StringDeserializer<IniError> =
+            self.value.trim().to_string().into_deserializer();
+        visitor.visit_enum(value)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> IniResult<V::Value> {
+        Err(self.mismatch("a section, options can not contain structures"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> IniResult<V::Value> {
+        Err(self.mismatch("a section, options can not contain structures"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, IniError> for ValueDeserializer<'a> {
+    type Deserializer = ValueDeserializer<'a>;
+
+    fn into_deserializer(self) -> ValueDeserializer<'a> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_ini, from_str};
+    use IniError;
+    use IniFile;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+        secure: bool,
+        timeout: Option<f64>,
+        aliases: Vec<String>,
+        ports: Vec<u16>,
+        level: Level,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        server: Server,
+        backup: Option<Server>,
+    }
+
+    static TEXT: &str = "name = app
+level = info
+[server]
+host = localhost
+port = 8080
+secure = yes
+timeout =
+aliases = a, b
+ports =
+    80
+    443
+";
+
+    #[test]
+    fn deserialize() {
+        let config: Config = from_str(TEXT).unwrap();
+        let expected = Config {
+            name: "app".to_string(),
+            server: Server {
+                host: "localhost".to_string(),
+                port: 8080,
+                secure: true,
+                timeout: None,
+                aliases: vec!["a".to_string(), "b".to_string()],
+                ports: vec![80, 443],
+                level: Level::Info,
+            },
+            backup: None,
+        };
+        assert_eq!(expected, config);
+    }
+    #[test]
+    fn deserialize_type_mismatch() {
+        let mut ini = IniFile::new();
+        ini.read_string(TEXT.lines().map(|line| line.to_string()).collect());
+        ini.set("server".to_string(), "port".to_string(), "http".to_string());
+        match from_ini::<Config>(&ini) {
+            Err(IniError::TypeMismatch {
+                section,
+                option,
+                expected,
+                ..
+            }) => {
+                assert_eq!("server", section);
+                assert_eq!("port", option);
+                assert_eq!("an integer", expected);
+            }
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+        ini.set(
+            "server".to_string(),
+            "level".to_string(),
+            "verbose".to_string(),
+        );
+        ini.set("server".to_string(), "port".to_string(), "80".to_string());
+        match from_ini::<Config>(&ini) {
+            Err(IniError::Serde(_)) => (),
+            other => panic!("IniError::Serde expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn deserialize_missing_option() {
+        match from_str::<Config>("name = app\n[server]\nhost = localhost\n") {
+            Err(IniError::Serde(message)) => assert!(message.contains("port"), "{}", message),
+            other => panic!("IniError::Serde expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn deserialize_as_getters() {
+        let mut ini: IniFile = TEXT
+            .replace("aliases = a, b", "aliases = a; b,c")
+            .parse()
+            .unwrap();
+        ini.set(
+            "server".to_string(),
+            "port".to_string(),
+            "0x1F90".to_string(),
+        );
+        ini.set_list_delimiter(';');
+        let config: Config = from_ini(&ini).unwrap();
+        assert_eq!(8080, config.server.port);
+        assert_eq!(vec!["a", "b,c"], config.server.aliases);
+        assert_eq!(vec![80, 443], config.server.ports);
+    }
+}
@@ -0,0 +1,297 @@
+//! Problems found in INI files, located and rendered as rustc does, see `IniError::diagnostics()`.
+//!
+//! ```text
+//! error: expected "key=value", found "port 80"
+//!  --> config.ini:3:1
+//!   |
+//! 3 | port 80
+//!   | ^^^^^^^
+//!   |
+//!   = help: write the option as `key=value`, or start a comment with `;` or `#`
+//! ```
+//!
+//! `render()` gives plain text, `render_ansi()` the same text colored for a terminal.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use schema::Severity;
+
+/**
+ * Position of a problem in a file.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// File path, empty for a text which was not read from a file.
+    pub path: PathBuf,
+    /// Starts at 1.
+    pub line: usize,
+    /// Starts at 1, in characters.
+    pub column: usize,
+    /// Byte offsets of the problem in the text of the file; it may go past the line.
+    pub span: Range<usize>,
+    /// Text of the line, without its line ending.
+    pub source_line: String,
+}
+
+impl Location {
+    /**
+     * Location of `span`, byte offsets in `text`, the text of a whole file.
+     */
+    pub(crate) fn in_text(text: &str, span: Range<usize>) -> Location {
+        let start = text[..span.start].rfind('\n').map_or(0, |index| index + 1);
+        let end = text[start..]
+            .find('\n')
+            .map_or(text.len(), |index| start + index);
+        let line = text[..start].matches('\n').count() + 1;
+        let source_line = &text[start..end];
+        let source_line = source_line.strip_suffix('\r').unwrap_or(source_line);
+        Location::in_line(line, source_line, start, span)
+    }
+    /**
+     * Location of `span`, byte offsets in a file, on the line numbered `line` whose text `source_line`
+     * starts at offset `start`.
+     */
+    pub(crate) fn in_line(
+        line: usize,
+        source_line: &str,
+        start: usize,
+        span: Range<usize>,
+    ) -> Location {
+        let offset = (span.start - start).min(source_line.len());
+        Location {
+            path: PathBuf::new(),
+            line,
+            column: source_line[..offset].chars().count() + 1,
+            span,
+            source_line: source_line.to_string(),
+        }
+    }
+}
+
+/**
+ * A problem found in a file: a syntax error, or a violation of its schema.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// None when the problem is not on a line of the file, as a missing section.
+    pub location: Option<Location>,
+    /// How the problem may be fixed.
+    pub help: Option<String>,
+}
+
+/**
+ * Escape sequences of the parts of a rendered diagnostic; empty for plain text.
+ */
+struct Palette {
+    /// Severity and carets
+    severity: &'static str,
+    /// Message and help title
+    bold: &'static str,
+    /// Arrow, line numbers and gutter
+    gutter: &'static str,
+    reset: &'static str,
+}
+
+const PLAIN: Palette = Palette {
+    severity: "",
+    bold: "",
+    gutter: "",
+    reset: "",
+};
+
+impl Diagnostic {
+    /**
+     * Error at the given location.
+     */
+    pub(crate) fn error(message: String, location: Option<Location>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            location,
+            help: None,
+        }
+    }
+    /**
+     * Number of the line of the problem, starting at 1, if it is located.
+     */
+    pub fn line(&self) -> Option<usize> {
+        self.location.as_ref().map(|location| location.line)
+    }
+    /**
+     * The diagnostic as plain text, over several lines ending with `\n`.
+     */
+    pub fn render(&self) -> String {
+        self.render_with(&PLAIN)
+    }
+    /**
+     * The diagnostic colored with ANSI escape sequences, for a terminal.
+     */
+    pub fn render_ansi(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+        };
+        self.render_with(&Palette {
+            severity,
+            bold: "\x1b[1m",
+            gutter: "\x1b[1;34m",
+            reset: "\x1b[0m",
+        })
+    }
+    /**
+     * The same diagnostic, with a hint on how to fix the problem.
+     */
+    pub(crate) fn with_help(mut self, help: String) -> Diagnostic {
+        self.help = Some(help);
+        self
+    }
+    /**
+     * The diagnostic, with the escape sequences of the palette.
+     */
+    fn render_with(&self, palette: &Palette) -> String {
+        let (severity, bold, gutter, reset) = (
+            palette.severity,
+            palette.bold,
+            palette.gutter,
+            palette.reset,
+        );
+        let title = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut text = format!(
+            "{}{}{}{}: {}{}\n",
+            severity, title, reset, bold, self.message, reset
+        );
+        let digits = self
+            .location
+            .as_ref()
+            .map_or(0, |location| location.line.to_string().len());
+        let margin = " ".repeat(digits);
+        if let Some(ref location) = self.location {
+            let path = if location.path.as_os_str().is_empty() {
+                "<string>".to_string()
+            } else {
+                location.path.display().to_string()
+            };
+            text.push_str(&format!(
+                "{}{}-->{} {}:{}:{}\n",
+                margin, gutter, reset, path, location.line, location.column
+            ));
+            text.push_str(&format!("{} {}|{}\n", margin, gutter, reset));
+            // Tabs are expanded, so that the carets are under the characters they point at.
+            let source_line = location.source_line.replace('\t', "    ");
+            text.push_str(&format!(
+                "{}{} |{} {}\n",
+                gutter, location.line, reset, source_line
+            ));
+            let line = &location.source_line;
+            let start = line
+                .char_indices()
+                .nth(location.column - 1)
+                .map_or(line.len(), |(index, _)| index);
+            let end = (start + location.span.len()).min(line.len());
+            let indent = width(&line[..start]);
+            let carets = width(&line[start..end]).max(1);
+            text.push_str(&format!(
+                "{} {}|{} {}{}{}{}\n",
+                margin,
+                gutter,
+                reset,
+                " ".repeat(indent),
+                severity,
+                "^".repeat(carets),
+                reset
+            ));
+        }
+        if let Some(ref help) = self.help {
+            if self.location.is_some() {
+                text.push_str(&format!("{} {}|{}\n", margin, gutter, reset));
+            }
+            text.push_str(&format!(
+                "{} {}={} {}help{}: {}\n",
+                margin, gutter, reset, bold, reset, help
+            ));
+        }
+        text
+    }
+}
+
+/**
+ * Columns taken by a text once its tabs are expanded.
+ */
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+/**
+ * The plain rendering.
+ */
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Location};
+    use schema::Severity;
+    use std::path::PathBuf;
+
+    #[test]
+    fn locations() {
+        let text = "[a]\r\nk\u{e9}y = \tvalue\r\nlast";
+        let location = Location::in_text(text, 13..18);
+        assert_eq!((2, 8), (location.line, location.column));
+        assert_eq!("k\u{e9}y = \tvalue", location.source_line);
+        let location = Location::in_text(text, 20..24);
+        assert_eq!(
+            (3, 1, "last"),
+            (location.line, location.column, &*location.source_line)
+        );
+    }
+    #[test]
+    fn render() {
+        let mut location = Location::in_text("[server]\n\tport = eighty\n", 17..23);
+        location.path = PathBuf::from("config.ini");
+        let diagnostic = Diagnostic::error("invalid port".to_string(), Some(location))
+            .with_help("write a number".to_string());
+        assert_eq!(
+            "error: invalid port\n \
+             --> config.ini:2:9\n  \
+             |\n\
+             2 |     port = eighty\n  \
+             |            ^^^^^^\n  \
+             |\n  \
+             = help: write a number\n",
+            diagnostic.render()
+        );
+        assert_eq!(diagnostic.render(), diagnostic.to_string());
+        let ansi = diagnostic.render_ansi();
+        assert!(ansi.starts_with("\x1b[1;31merror\x1b[0m"), "{:?}", ansi);
+        assert!(ansi.contains("\x1b[1;31m^^^^^^\x1b[0m"), "{:?}", ansi);
+
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            message: "[extra]: unknown section".to_string(),
+            location: Some(Location::in_text("[extra]", 0..0)),
+            help: None,
+        };
+        assert_eq!(
+            "warning: [extra]: unknown section\n \
+             --> <string>:1:1\n  \
+             |\n\
+             1 | [extra]\n  \
+             | ^\n",
+            diagnostic.render()
+        );
+        let diagnostic = Diagnostic::error("no file".to_string(), None);
+        assert_eq!("error: no file\n", diagnostic.render());
+        assert_eq!(None, diagnostic.line());
+    }
+}
@@ -0,0 +1,397 @@
+//! Syntax variants of INI files, see `IniFile::set_dialect()`.
+//!
+//! @see https://docs.python.org/3/library/configparser.html#supported-ini-file-structure
+//!
+//! @see https://git-scm.com/docs/git-config#_configuration_file
+//!
+//! @see https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-getprivateprofilestring
+
+use std::borrow::Cow;
+
+/**
+ * Syntax of the files read and written by an `IniFile`.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dialect {
+    #[default]
+    /// Python's configparser: comments at the start of a line, values continued by indented lines,
+    /// options of the default section visible in every section, the last duplicate option wins.
+    Python,
+    /// git-config: sections `[section "subsection"]` named `section.subsection`, quoted values with
+    /// backslash escapes, inline comments, lines continued by a trailing backslash, keys without value
+    /// meaning `true`, multi-valued keys; no default section.
+    Git,
+    /// Windows INI files, as GetPrivateProfileString() reads them: `;` comments, indented lines,
+    /// values in double quotes unquoted, the first duplicate option wins; no default section.
+    Windows,
+}
+
+impl Dialect {
+    /**
+     * Marker of the comments written in a file without comments: `;` in Windows INI files, `#` otherwise.
+     */
+    pub(crate) fn comment_marker(self) -> char {
+        if self == Dialect::Windows {
+            ';'
+        } else {
+            '#'
+        }
+    }
+    /**
+     * Whether the value of an option continues on the next line: in git-config, when it ends with an
+     * unescaped backslash.
+     */
+    pub(crate) fn continues(self, raw: &str) -> bool {
+        if self != Dialect::Git {
+            return false;
+        }
+        let backslashes = raw[..self.value_length(raw)]
+            .chars()
+            .rev()
+            .take_while(|&c| c == '\\')
+            .count();
+        backslashes % 2 == 1
+    }
+    /**
+     * Whether the options of the default section are visible in the other sections.
+     */
+    pub(crate) fn default_fallback(self) -> bool {
+        self == Dialect::Python
+    }
+    /**
+     * Text of a value as written in the file, from its raw text.
+     */
+    pub(crate) fn decode(self, raw: &str) -> Result<String, String> {
+        match self {
+            Dialect::Python => Ok(raw.to_string()),
+            Dialect::Git => git_decode(raw),
+            Dialect::Windows => Ok(unquote(raw).to_string()),
+        }
+    }
+    /**
+     * Raw text of a value, to write it in the file.
+     */
+    pub(crate) fn encode(self, value: &str) -> String {
+        match self {
+            Dialect::Python => value.to_string(),
+            Dialect::Git => git_encode(value),
+            Dialect::Windows => {
+                if value.trim() != value || unquote(value) != value {
+                    format!("\"{}\"", value)
+                } else {
+                    value.to_string()
+                }
+            }
+        }
+    }
+    /**
+     * Whether the first definition of a duplicate option wins, instead of the last one.
+     */
+    pub(crate) fn first_wins(self) -> bool {
+        self == Dialect::Windows
+    }
+    /**
+     * Section header of a section.
+     */
+    pub(crate) fn header(self, section: &str) -> String {
+        match (self, section.find('.')) {
+            (Dialect::Git, Some(index)) => format!(
+                "[{} \"{}\"]",
+                &section[..index],
+                section[index + 1..]
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+            ),
+            _ => format!("[{}]", section),
+        }
+    }
+    /**
+     * Value of a key without delimiter: `true` in git-config, empty otherwise.
+     */
+    pub(crate) fn implicit_value(self) -> &'static str {
+        if self == Dialect::Git {
+            "true"
+        } else {
+            ""
+        }
+    }
+    /**
+     * Whether the lines which are neither comments, section headers nor options are ignored, instead of
+     * being syntax errors.
+     */
+    pub(crate) fn ignores_invalid_lines(self) -> bool {
+        self == Dialect::Windows
+    }
+    /**
+     * Whether comments may follow a value, as in `key = value ; comment`.
+     */
+    pub(crate) fn inline_comments(self) -> bool {
+        self == Dialect::Git
+    }
+    /**
+     * Whether lines may be indented; otherwise, indented lines continue the value of the previous option.
+     */
+    pub(crate) fn indented_lines(self) -> bool {
+        self != Dialect::Python
+    }
+    /**
+     * Whether a key without delimiter is an option, as `key` for `key = true`.
+     */
+    pub(crate) fn keys_without_value(self) -> bool {
+        self == Dialect::Git
+    }
+    /**
+     * Name of the section declared by a header line, or why it is not a valid header.
+     */
+    pub(crate) fn section_name(self, line: &str) -> Result<Cow<'_, str>, String> {
+        match self {
+            Dialect::Python => {
+                // A comment may follow the header: Python ignores what follows the last `]`.
+                let end = match line.rfind(']') {
+                    Some(end) if end > 0 => end,
+                    _ => return Err("section header must end with ']'".to_string()),
+                };
+                let rest = line[end + 1..].trim_start();
+                if !rest.is_empty() && !rest.starts_with('#') && !rest.starts_with(';') {
+                    return Err("section header must end with ']'".to_string());
+                }
+                if end == 1 {
+                    return Err("section name must not be empty".to_string());
+                }
+                Ok(Cow::Borrowed(&line[1..end]))
+            }
+            Dialect::Windows => match line.find(']') {
+                Some(end) => Ok(Cow::Borrowed(line[1..end].trim())),
+                None => Err("section header must end with ']'".to_string()),
+            },
+            Dialect::Git => git_section_name(line),
+        }
+    }
+    /**
+     * Length of the value of an option, without its inline comment and the spaces before it.
+     */
+    pub(crate) fn value_length(self, raw: &str) -> usize {
+        if !self.inline_comments() {
+            return raw.len();
+        }
+        let mut quoted = false;
+        let mut escaped = false;
+        for (index, c) in raw.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = !quoted;
+            } else if !quoted && (c == '#' || c == ';') {
+                return raw[..index].trim_end().len();
+            }
+        }
+        raw.len()
+    }
+}
+
+/**
+ * Value of a git-config option: quotes removed, escapes replaced, spaces outside quotes kept but at
+ * both ends, inline comment removed.
+ */
+fn git_decode(raw: &str) -> Result<String, String> {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut spaces = 0;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if !quoted && c.is_whitespace() && c != '\n' {
+            if !value.is_empty() {
+                spaces += 1;
+            }
+            continue;
+        }
+        if !quoted && (c == '#' || c == ';') {
+            break;
+        }
+        for _ in 0..spaces {
+            value.push(' ');
+        }
+        spaces = 0;
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('\\') => value.push('\\'),
+                Some('"') => value.push('"'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{8}'),
+                // Line continuation
+                Some('\n') => (),
+                Some(other) => return Err(format!("bad escape sequence \\{}", other)),
+                None => return Err("unexpected end of value after '\\'".to_string()),
+            },
+            // Continuation lines are joined by the parser with '\n', after the backslash.
+            '\n' => (),
+            _ => value.push(c),
+        }
+    }
+    if quoted {
+        return Err("missing closing quote".to_string());
+    }
+    Ok(value)
+}
+
+/**
+ * Raw text of a git-config value: quoted if it has spaces at its ends or comment characters.
+ */
+fn git_encode(value: &str) -> String {
+    let mut raw = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => raw.push_str("\\\\"),
+            '"' => raw.push_str("\\\""),
+            '\n' => raw.push_str("\\n"),
+            '\t' => raw.push_str("\\t"),
+            _ => raw.push(c),
+        }
+    }
+    if value.trim() != value || value.contains('#') || value.contains(';') {
+        format!("\"{}\"", raw)
+    } else {
+        raw
+    }
+}
+
+/**
+ * Name of a git-config section: `[section]`, `[section "subsection"]` or the deprecated
+ * `[section.subsection]`. Section names are case-insensitive, and lowercased; subsection names are not.
+ */
+fn git_section_name(line: &str) -> Result<Cow<'_, str>, String> {
+    let inner = &line[1..];
+    let (name, rest) = match inner.find('"') {
+        Some(quote) => {
+            let section = inner[..quote].trim();
+            let mut subsection = String::new();
+            let mut chars = inner[quote + 1..].char_indices();
+            let mut end = None;
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => match chars.next() {
+                        Some((_, escaped)) => subsection.push(escaped),
+                        None => break,
+                    },
+                    '"' => {
+                        end = Some(quote + 1 + index + 1);
+                        break;
+                    }
+                    _ => subsection.push(c),
+                }
+            }
+            let end = match end {
+                Some(end) => end,
+                None => return Err("missing closing quote in section header".to_string()),
+            };
+            let rest = inner[end..].trim_start();
+            if !rest.starts_with(']') {
+                return Err("section header must end with ']'".to_string());
+            }
+            (
+                Cow::Owned(format!("{}.{}", section.to_lowercase(), subsection)),
+                &rest[1..],
+            )
+        }
+        None => match inner.find(']') {
+            Some(end) => {
+                let name = inner[..end].trim();
+                if name.chars().any(char::is_uppercase) {
+                    (Cow::Owned(name.to_lowercase()), &inner[end + 1..])
+                } else {
+                    (Cow::Borrowed(name), &inner[end + 1..])
+                }
+            }
+            None => return Err("section header must end with ']'".to_string()),
+        },
+    };
+    let rest = rest.trim();
+    if !(rest.is_empty() || rest.starts_with('#') || rest.starts_with(';')) {
+        return Err(format!("unexpected {:?} after section header", rest));
+    }
+    Ok(name)
+}
+
+/**
+ * Value without the double quotes surrounding it.
+ */
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dialect;
+
+    #[test]
+    fn git_values() {
+        let git = Dialect::Git;
+        assert_eq!(Ok("a  b".to_string()), git.decode("a  b"));
+        assert_eq!(
+            Ok(" a ; b ".to_string()),
+            git.decode("\" a ; b \" ; comment")
+        );
+        assert_eq!(Ok("a\"b\\c\nd".to_string()), git.decode("a\\\"b\\\\c\\nd"));
+        assert_eq!(Ok("ab".to_string()), git.decode("a\\\nb"));
+        assert!(git.decode("\"a").is_err());
+        assert!(git.decode("a\\x").is_err());
+        for value in [" a ", "a;b", "a\"b\\c\nd", "plain value"].iter() {
+            assert_eq!(Ok(value.to_string()), git.decode(&git.encode(value)));
+        }
+        assert_eq!("plain value", git.encode("plain value"));
+        assert_eq!(5, git.value_length("value  # comment"));
+        assert_eq!(7, git.value_length("\"a # b\""));
+        assert!(git.continues("a \\"));
+        assert!(!git.continues("a \\\\"));
+        assert!(!git.continues("a # comment \\"));
+        assert!(!Dialect::Python.continues("a \\"));
+    }
+    #[test]
+    fn git_sections() {
+        let git = Dialect::Git;
+        assert_eq!(Ok("core".into()), git.section_name("[Core]"));
+        assert_eq!(
+            Ok("remote.Origin".into()),
+            git.section_name("[remote \"Origin\"] # comment")
+        );
+        assert_eq!(Ok("a.b\"c".into()), git.section_name("[a \"b\\\"c\"]"));
+        assert_eq!(
+            Ok("remote.origin".into()),
+            git.section_name("[remote.origin]")
+        );
+        assert!(git.section_name("[remote \"origin]").is_err());
+        assert!(git.section_name("[core] x").is_err());
+        assert_eq!("[remote \"origin\"]", git.header("remote.origin"));
+        assert_eq!("[core]", git.header("core"));
+    }
+    #[test]
+    fn python_sections() {
+        let python = Dialect::Python;
+        assert_eq!(Ok("a".into()), python.section_name("[a]"));
+        assert_eq!(Ok("a".into()), python.section_name("[a] ; comment"));
+        assert_eq!(Ok("a".into()), python.section_name("[a]# comment"));
+        assert_eq!(Ok("a] [b".into()), python.section_name("[a] [b]"));
+        assert!(python.section_name("[a] x").is_err());
+        assert!(python.section_name("[a").is_err());
+        assert_eq!(
+            Err("section name must not be empty".to_string()),
+            python.section_name("[]")
+        );
+    }
+    #[test]
+    fn windows_values() {
+        let windows = Dialect::Windows;
+        assert_eq!(Ok(" a ".to_string()), windows.decode("\" a \""));
+        assert_eq!("\" a \"", windows.encode(" a "));
+        assert_eq!("a", windows.encode("a"));
+        assert_eq!(Ok("Name".into()), windows.section_name("[ Name ] junk"));
+    }
+}
@@ -0,0 +1,424 @@
+//! Differences between two configurations, and three-way merge of configurations.
+
+use std::fmt;
+
+use IniFile;
+
+/**
+ * Difference of a section or of an option between an old and a new configuration.
+ * Option changes include the default section; values are raw, as read or set, the overrides being ignored.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    SectionAdded {
+        section: String,
+    },
+    SectionRemoved {
+        section: String,
+    },
+    Added {
+        section: String,
+        option: String,
+        value: String,
+    },
+    Removed {
+        section: String,
+        option: String,
+        value: String,
+    },
+    Changed {
+        section: String,
+        option: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Change {
+    /**
+     * Section of the change.
+     */
+    pub fn section(&self) -> &str {
+        match *self {
+            Change::SectionAdded { ref section }
+            | Change::SectionRemoved { ref section }
+            | Change::Added { ref section, .. }
+            | Change::Removed { ref section, .. }
+            | Change::Changed { ref section, .. } => section,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::SectionAdded { ref section } => write!(f, "+ [{}]", section),
+            Change::SectionRemoved { ref section } => write!(f, "- [{}]", section),
+            Change::Added {
+                ref section,
+                ref option,
+                ref value,
+            } => write!(f, "+ [{}] {} = {}", section, option, value),
+            Change::Removed {
+                ref section,
+                ref option,
+                ref value,
+            } => write!(f, "- [{}] {} = {}", section, option, value),
+            Change::Changed {
+                ref section,
+                ref option,
+                ref old,
+                ref new,
+            } => write!(f, "~ [{}] {} = {} -> {}", section, option, old, new),
+        }
+    }
+}
+
+/**
+ * Changes turning `old` into `new`, in the order of the sections and of the options:
+ * the default section first, then the sections of `old`, then those only in `new`.
+ * A section change comes before the changes of its options.
+ */
+pub fn changes(old: &IniFile, new: &IniFile) -> Vec<Change> {
+    let old_sections = old.file_sections();
+    let new_sections = new.file_sections();
+    let mut sections = vec![old.default_section()];
+    for section in old_sections.iter().chain(new_sections.iter()) {
+        if !sections.contains(section) {
+            sections.push(section.clone());
+        }
+    }
+    let mut changes = Vec::new();
+    for section in sections {
+        let old_options = old.file_options(&section);
+        let new_options = new.file_options(&section);
+        if section != old.default_section() {
+            match (
+                old_sections.contains(&section),
+                new_sections.contains(&section),
+            ) {
+                (true, false) => changes.push(Change::SectionRemoved {
+                    section: section.clone(),
+                }),
+                (false, true) => changes.push(Change::SectionAdded {
+                    section: section.clone(),
+                }),
+                _ => (),
+            }
+        }
+        for option in old_options.iter() {
+            let old_value = old.stored_value(&section, option).unwrap();
+            match new.stored_value(&section, option) {
+                Some(new_value) if new_value == old_value => (),
+                Some(new_value) => changes.push(Change::Changed {
+                    section: section.clone(),
+                    option: option.clone(),
+                    old: old_value.clone(),
+                    new: new_value.clone(),
+                }),
+                None => changes.push(Change::Removed {
+                    section: section.clone(),
+                    option: option.clone(),
+                    value: old_value.clone(),
+                }),
+            }
+        }
+        for option in new_options.iter() {
+            if !old_options.contains(option) {
+                changes.push(Change::Added {
+                    section: section.clone(),
+                    option: option.clone(),
+                    value: new.stored_value(&section, option).unwrap().clone(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+/**
+ * Option modified differently on both sides of a merge; `None` when the option is missing.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub section: String,
+    pub option: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match (&self.base, &self.ours, &self.theirs) {
+            (&None, _, _) => "add/add",
+            (_, &None, _) => "delete/modify",
+            (_, _, &None) => "modify/delete",
+            _ => "content",
+        };
+        write!(f, "CONFLICT ({}): [{}] {}", kind, self.section, self.option)
+    }
+}
+
+/**
+ * Result of a three-way merge. `merged` keeps our value of the conflicting options;
+ * its text, given by `Display`, holds git-like conflict markers instead.
+ */
+#[derive(Clone)]
+pub struct Merge {
+    pub merged: IniFile,
+    pub conflicts: Vec<Conflict>,
+    /// Merged text, with conflict markers
+    text: String,
+}
+
+impl Merge {
+    /**
+     * Indicates whether the merge has no conflict.
+     */
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl fmt::Display for Merge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/**
+ * Apply to `ours` the changes turning `base` into `theirs`, keeping the layout of `ours`.
+ * The values of the files are merged: overrides are neither compared nor written.
+ * An option changed on both sides, differently, is a conflict, as well as an option changed on one
+ * side and removed on the other. A section removed by `theirs` is removed if it has no option left.
+ */
+pub fn merge(base: &IniFile, ours: &IniFile, theirs: &IniFile) -> Merge {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+    let mut removed_sections = Vec::new();
+    // New sections are added last, after the removed sections are gone.
+    let (later, first): (Vec<Change>, Vec<Change>) = {
+        let changes = changes(base, theirs);
+        let added: Vec<String> = changes
+            .iter()
+            .filter_map(|change| match *change {
+                Change::SectionAdded { ref section } if !ours.has_section(section) => {
+                    Some(section.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        changes
+            .into_iter()
+            .partition(|change| added.iter().any(|section| section == change.section()))
+    };
+    for change in first {
+        apply(
+            &mut merged,
+            ours,
+            change,
+            &mut conflicts,
+            &mut removed_sections,
+        );
+    }
+    for section in removed_sections.drain(..) {
+        let left = merged.file_options(&section).len();
+        if left == 0 && !conflicts.iter().any(|c| c.section == section) {
+            merged.remove_section(section);
+        }
+    }
+    for change in later {
+        apply(
+            &mut merged,
+            ours,
+            change,
+            &mut conflicts,
+            &mut removed_sections,
+        );
+    }
+    let mut document = merged.document.clone();
+    for conflict in conflicts.iter() {
+        document.mark_conflict(
+            &conflict.section,
+            &conflict.option,
+            conflict.theirs.as_deref(),
+        );
+    }
+    Merge {
+        merged,
+        conflicts,
+        text: document.to_string(),
+    }
+}
+
+/**
+ * Apply one of their changes to the merged configuration, or record a conflict with ours.
+ */
+fn apply(
+    merged: &mut IniFile,
+    ours: &IniFile,
+    change: Change,
+    conflicts: &mut Vec<Conflict>,
+    removed_sections: &mut Vec<String>,
+) {
+    let (section, option, base_value, their_value) = match change {
+        Change::SectionAdded { section } => {
+            if !merged.has_section(&section) {
+                merged.add_section(&section);
+            }
+            return;
+        }
+        Change::SectionRemoved { section } => {
+            removed_sections.push(section);
+            return;
+        }
+        Change::Added {
+            section,
+            option,
+            value,
+        } => (section, option, None, Some(value)),
+        Change::Removed {
+            section,
+            option,
+            value,
+        } => (section, option, Some(value), None),
+        Change::Changed {
+            section,
+            option,
+            old,
+            new,
+        } => (section, option, Some(old), Some(new)),
+    };
+    let our_value = ours.stored_value(&section, &option).cloned();
+    if our_value == their_value {
+        return;
+    }
+    if our_value != base_value {
+        conflicts.push(Conflict {
+            section,
+            option,
+            base: base_value,
+            ours: our_value,
+            theirs: their_value,
+        });
+        return;
+    }
+    match their_value {
+        Some(value) => {
+            if section != merged.default_section() && !merged.has_section(&section) {
+                merged.add_section(&section);
+            }
+            merged.set(section, option, value);
+        }
+        None => {
+            merged.remove_option(section, option);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changes, merge, Change};
+    use IniFile;
+
+    #[test]
+    fn option_changes() {
+        let old: IniFile = "a = 1\n[s]\nkept = 1\nchanged = 1\nremoved = 1\n[gone]\nx = 1\n"
+            .parse()
+            .unwrap();
+        let new: IniFile = "a = 2\n[s]\nadded = 1\nchanged = 2\nkept = 1\n[new]\ny = 1\n"
+            .parse()
+            .unwrap();
+        let found: Vec<String> = changes(&old, &new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            vec![
+                "~ [DEFAULT] a = 1 -> 2",
+                "~ [s] changed = 1 -> 2",
+                "- [s] removed = 1",
+                "+ [s] added = 1",
+                "- [gone]",
+                "- [gone] x = 1",
+                "+ [new]",
+                "+ [new] y = 1",
+            ],
+            found
+        );
+        assert!(changes(&old, &old).is_empty());
+        assert_eq!(
+            Change::Added {
+                section: "new".to_string(),
+                option: "y".to_string(),
+                value: "1".to_string(),
+            },
+            changes(&old, &new)[7]
+        );
+    }
+    #[test]
+    fn diff_method() {
+        let old: IniFile = "[a]\nx = 1\n".parse().unwrap();
+        let new: IniFile = "[a]\nx = 2\n".parse().unwrap();
+        assert_eq!(changes(&old, &new), old.diff(&new));
+    }
+    #[test]
+    fn merge_clean() {
+        let base: IniFile = "[server]\nport = 80\nhost = a\nold = 1\n\n[legacy]\nx = 1\n"
+            .parse()
+            .unwrap();
+        let ours: IniFile =
+            "# local\n[server]\nport = 8080\nhost = a\nold = 1\n\n[legacy]\nx = 1\n"
+                .parse()
+                .unwrap();
+        let theirs: IniFile = "[server]\nport = 80\nhost = b\ntimeout = 30\n\n[cache]\nsize = 1\n"
+            .parse()
+            .unwrap();
+        let merge = merge(&base, &ours, &theirs);
+        assert!(merge.is_clean(), "{:?}", merge.conflicts);
+        assert_eq!(
+            "# local\n[server]\nport = 8080\nhost = b\ntimeout = 30\n\n[cache]\nsize = 1\n",
+            merge.to_string()
+        );
+        assert_eq!(merge.to_string(), merge.merged.to_string());
+    }
+    #[test]
+    fn merge_conflicts() {
+        let base: IniFile = "[s]\nport = 80\nhost = a\nkept = 1\n".parse().unwrap();
+        let ours: IniFile = "[s]\nport = 8080\nkept = 1\nnew = ours\n".parse().unwrap();
+        let theirs: IniFile = "[s]\nport = 9090\nhost = b\nkept = 1\nnew = theirs\n"
+            .parse()
+            .unwrap();
+        let merge = merge(&base, &ours, &theirs);
+        let found: Vec<String> = merge.conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            vec![
+                "CONFLICT (content): [s] port",
+                "CONFLICT (delete/modify): [s] host",
+                "CONFLICT (add/add): [s] new",
+            ],
+            found
+        );
+        assert_eq!("8080", merge.merged.get("s", "port"));
+        assert_eq!(
+            "[s]\n<<<<<<< ours\nport = 8080\n=======\nport = 9090\n>>>>>>> theirs\nkept = 1\n<<<<<<< ours\nnew = ours\n=======\nnew = theirs\n>>>>>>> theirs\n<<<<<<< ours\n=======\nhost = b\n>>>>>>> theirs\n",
+            merge.to_string()
+        );
+    }
+    #[test]
+    fn overrides_ignored() {
+        let mut old: IniFile = "[s]\nport = 80\n".parse().unwrap();
+        let new = old.clone();
+        old.set_override("s", "port", "8080", "env APP__S__PORT");
+        old.set_override("other", "x", "1", "--set other.x=1");
+        assert!(changes(&old, &new).is_empty());
+        assert!(changes(&new, &old).is_empty());
+
+        let base: IniFile = "[s]\nport = 80\nhost = a\n".parse().unwrap();
+        let mut ours = base.clone();
+        ours.set_override("s", "port", "8080", "env APP__S__PORT");
+        let theirs: IniFile = "[s]\nport = 90\nhost = b\n".parse().unwrap();
+        let merge = merge(&base, &ours, &theirs);
+        assert!(merge.is_clean(), "{:?}", merge.conflicts);
+        assert_eq!("[s]\nport = 90\nhost = b\n", merge.to_string());
+        assert_eq!("8080", merge.merged.get("s", "port"));
+    }
+}
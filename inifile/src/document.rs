@@ -0,0 +1,1367 @@
+//! Layout of an INI file: every physical line is kept, so that the file is written back as it was read
+//! and an edit only changes the line holding the edited option.
+//!
+//! Raw lines keep the `\r` of their CRLF line ending, so that a file mixing line endings is written back
+//! byte for byte; added lines end as the document does, see `Document::set_line_ending()`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use encoding::LineEnding;
+
+/**
+ * A physical line of an INI file.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+    /// Blank line or comment, written as is; merge conflicts hold several lines.
+    Text(String),
+    /// Section header `[name]`.
+    Section { name: String, raw: String },
+    /// `key = value` line, written as `prefix`, the first line of `value`, `suffix`, then `continuation`.
+    /// `prefix` holds the key, the delimiter and the spaces around it, `suffix` the trailing spaces,
+    /// the inline comment and the `\r` of a CRLF line ending. A key without delimiter (`delimited` false) has an empty value.
+    /// `continuation` holds the raw lines indented deeper than the key, which continue a multi-line value.
+    Option {
+        key: String,
+        delimited: bool,
+        prefix: String,
+        value: String,
+        suffix: String,
+        continuation: Vec<String>,
+    },
+}
+
+impl Line {
+    /**
+     * Whether the line is a blank line, spaces aside.
+     */
+    pub fn is_blank(&self) -> bool {
+        match *self {
+            Line::Text(ref raw) => raw.trim().is_empty(),
+            _ => false,
+        }
+    }
+    /**
+     * Whether the line is a comment, starting with `#` or `;`.
+     */
+    pub fn is_comment(&self) -> bool {
+        match *self {
+            Line::Text(ref raw) => raw.trim_start().starts_with(['#', ';']),
+            _ => false,
+        }
+    }
+    /**
+     * Option line of the given key, the value being at `value` in the raw line: the prefix is the text
+     * before it, the suffix the text after it. For a key without delimiter, the value is empty, after the key.
+     */
+    pub fn option(raw: &str, key: &str, value: Range<usize>, delimited: bool) -> Line {
+        Line::Option {
+            key: key.to_string(),
+            delimited,
+            prefix: raw[..value.start].to_string(),
+            value: raw[value.clone()].to_string(),
+            suffix: raw[value.end..].to_string(),
+            continuation: Vec::new(),
+        }
+    }
+    /**
+     * Text of the line, without the line ending.
+     * For a multi-line value, the lines are separated by `\n`.
+     */
+    pub fn raw(&self) -> String {
+        match *self {
+            Line::Text(ref raw) | Line::Section { ref raw, .. } => raw.clone(),
+            Line::Option {
+                ref prefix,
+                ref value,
+                ref suffix,
+                ref continuation,
+                ..
+            } => {
+                let first = value.split('\n').next().unwrap_or("");
+                let mut raw = format!("{}{}{}", prefix, first, suffix);
+                for line in continuation.iter() {
+                    raw.push('\n');
+                    raw.push_str(line);
+                }
+                raw
+            }
+        }
+    }
+    /**
+     * Change the value of an option line; a key without delimiter gets `delimiter`.
+     * The lines after the first one are written with the indentation of the former continuation lines,
+     * or indented by four spaces more than the key, and the line ending of the first one.
+     */
+    fn set_value(&mut self, new_value: &str, delimiter: char) {
+        if let Line::Option {
+            ref mut delimited,
+            ref mut prefix,
+            ref mut value,
+            ref suffix,
+            ref mut continuation,
+            ..
+        } = *self
+        {
+            if !*delimited {
+                prefix.push(' ');
+                prefix.push(delimiter);
+                if !new_value.is_empty() {
+                    prefix.push(' ');
+                }
+                *delimited = true;
+            } else if value == new_value {
+                return;
+            }
+            let cr = if suffix.ends_with('\r') { "\r" } else { "" };
+            // Blank and comment lines do not tell the indentation of the value.
+            let indent = match continuation
+                .iter()
+                .find(|line| !line.trim_start().starts_with(['#', ';']) && !line.trim().is_empty())
+            {
+                Some(line) => line[..line.len() - line.trim_start().len()].to_string(),
+                None => format!(
+                    "{}    ",
+                    &prefix[..prefix.len() - prefix.trim_start().len()]
+                ),
+            };
+            *continuation = new_value
+                .split('\n')
+                .skip(1)
+                .map(|line| {
+                    if line.is_empty() {
+                        cr.to_string()
+                    } else {
+                        format!("{}{}{}", indent, line, cr)
+                    }
+                })
+                .collect();
+            *value = new_value.to_string();
+        }
+    }
+    /**
+     * Replace the line endings of the physical lines of the line: `cr` is `\r` for CRLF, empty for LF.
+     */
+    fn set_ending(&mut self, cr: &str) {
+        let convert = |raw: &mut String| {
+            let lines: Vec<String> = raw
+                .split('\n')
+                .map(|line| format!("{}{}", line.strip_suffix('\r').unwrap_or(line), cr))
+                .collect();
+            *raw = lines.join("\n");
+        };
+        match *self {
+            Line::Text(ref mut raw) | Line::Section { ref mut raw, .. } => convert(raw),
+            Line::Option {
+                ref mut suffix,
+                ref mut continuation,
+                ..
+            } => {
+                convert(suffix);
+                continuation.iter_mut().for_each(convert);
+            }
+        }
+    }
+    /**
+     * Key as written in the file, which may differ from the normalized key.
+     */
+    fn spelling(&self) -> Option<&str> {
+        match *self {
+            Line::Option { ref prefix, .. } => {
+                let separator = self.separator().unwrap_or("");
+                Some(prefix[..prefix.len() - separator.len()].trim())
+            }
+            _ => None,
+        }
+    }
+    /**
+     * Text between the key and the value, such as `=` or ` : `; empty for a key without delimiter.
+     * The key may be normalized, so the separator is found from the delimiter ending the prefix.
+     */
+    fn separator(&self) -> Option<&str> {
+        match *self {
+            Line::Option {
+                delimited: false, ..
+            } => Some(""),
+            Line::Option { ref prefix, .. } => {
+                let delimited = prefix.trim_end();
+                let key = match delimited.char_indices().next_back() {
+                    Some((delimiter, _)) => delimited[..delimiter].trim_end(),
+                    None => "",
+                };
+                Some(&prefix[key.len()..])
+            }
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Ordered lines of an INI file.
+ * Lines before the first section header belong to the default section.
+ */
+#[derive(Clone)]
+pub struct Document {
+    /// Name of the default section
+    default_section: String,
+    /// Delimiter of the options added without a neighbour to copy
+    delimiter: char,
+    /// Whether the last line ends with a line ending
+    final_newline: bool,
+    /// Positions of the section headers and option lines
+    index: Index,
+    /// Line ending of the lines added
+    line_ending: LineEnding,
+    /// Physical lines, in file order
+    lines: Vec<Line>,
+}
+
+impl Document {
+    /**
+     * Append a section header at the end of the document, written as `header`.
+     */
+    pub fn add_section(&mut self, section: &str, header: &str) {
+        let cr = self.cr();
+        let mut lines = match self.lines.last() {
+            None | Some(&Line::Text(_)) => Vec::new(),
+            Some(_) => vec![Line::Text(cr.to_string())],
+        };
+        lines.push(Line::Section {
+            name: section.to_string(),
+            raw: format!("{}{}", header, cr),
+        });
+        let end = self.lines.len();
+        self.insert_lines(end, lines);
+    }
+    /**
+     * Add a line defining the option in the section after the last one, as it is written but for its value
+     * and its inline comment; if the option is missing, add it as set_value() does.
+     */
+    pub fn add_value(&mut self, section: &str, option: &str, spelling: &str, new_value: &str) {
+        let index = match self.find_option(section, option) {
+            Some(index) => index,
+            None => return self.set_value(section, option, spelling, new_value),
+        };
+        let mut line = self.lines[index].clone();
+        if let Line::Option {
+            ref mut value,
+            ref mut suffix,
+            ..
+        } = line
+        {
+            // The inline comment is dropped, the line ending kept.
+            suffix.drain(..suffix.len() - usize::from(suffix.ends_with('\r')));
+            // Forces set_value() to rewrite the continuation lines.
+            value.push('\n');
+        }
+        line.set_value(new_value, self.delimiter);
+        self.insert_lines(index + 1, vec![line]);
+    }
+    /**
+     * Comment of the last line defining the option in the section, or of the first header of the
+     * section if `option` is `None`: the comment lines right above it, without their markers.
+     */
+    pub fn comment(&self, section: &str, option: Option<&str>) -> Option<String> {
+        let index = self.find_item(section, option)?;
+        self.comment_text(self.comment_above(index))
+    }
+    /**
+     * Comment at the end of the file, after the last section or option.
+     */
+    pub fn footer_comment(&self) -> Option<String> {
+        self.comment_text(self.footer_block())
+    }
+    /**
+     * Comment at the top of the file, separated from what follows by a blank line.
+     */
+    pub fn header_comment(&self) -> Option<String> {
+        self.comment_text(self.header_block())
+    }
+    /**
+     * Inline comment of the last line defining the option in the section, with its prefix, as `; comment`.
+     */
+    pub fn inline_comment(&self, section: &str, option: &str) -> Option<&str> {
+        match self.lines[self.find_option(section, option)?] {
+            Line::Option { ref suffix, .. } => Some(suffix.trim()).filter(|raw| !raw.is_empty()),
+            _ => None,
+        }
+    }
+    /**
+     * Value of the last line, if it is an option.
+     */
+    pub fn last_value(&self) -> Option<&str> {
+        match self.lines.last() {
+            Some(Line::Option { value, .. }) => Some(value),
+            _ => None,
+        }
+    }
+    /**
+     * Replace the last line defining the option in the section by git-like conflict markers, showing
+     * this line and the line with `theirs` value. If the option is missing, the markers are added
+     * where set_value() would add it.
+     */
+    pub fn mark_conflict(&mut self, section: &str, option: &str, theirs: Option<&str>) {
+        let index = match self.find_option(section, option) {
+            Some(index) => index,
+            None => {
+                let value = theirs.unwrap_or("");
+                self.set_value(section, option, option, value);
+                let index = self.find_option(section, option).unwrap();
+                let their_raw = self.lines[index].raw();
+                let cr = self.cr();
+                let markers = Line::Text(format!(
+                    "<<<<<<< ours{}\n======={}\n{}\n>>>>>>> theirs{}",
+                    cr, cr, their_raw, cr
+                ));
+                return self.splice_lines(index..index + 1, vec![markers]);
+            }
+        };
+        let our_raw = self.lines[index].raw();
+        let their_raw = match theirs {
+            Some(value) => {
+                let mut line = self.lines[index].clone();
+                line.set_value(value, self.delimiter);
+                format!("{}\n", line.raw())
+            }
+            None => String::new(),
+        };
+        let cr = self.cr();
+        let markers = Line::Text(format!(
+            "<<<<<<< ours{}\n{}\n======={}\n{}>>>>>>> theirs{}",
+            cr, our_raw, cr, their_raw, cr
+        ));
+        self.splice_lines(index..index + 1, vec![markers]);
+    }
+    /**
+     * Rewrite the lines in a canonical form: `key = value` options, with the delimiter of the document, continuation lines indented by
+     * four spaces, comments and keys not indented, no trailing spaces, a single blank line before each
+     * section header and its comments, no other consecutive blank lines.
+     */
+    pub fn normalize(&mut self) {
+        let cr = self.cr();
+        let blank = Line::Text(cr.to_string());
+        let mut lines: Vec<Line> = Vec::new();
+        for line in self.lines.drain(..) {
+            match line {
+                Line::Text(raw) => {
+                    let raw = raw.trim();
+                    if raw.is_empty() && lines.last().is_none_or(|last| *last == blank) {
+                        continue;
+                    }
+                    lines.push(Line::Text(format!("{}{}", raw, cr)));
+                }
+                Line::Section { name, raw } => {
+                    // The comments right before the header stay with it.
+                    let mut start = lines.len();
+                    while start > 0 && lines[start - 1] != blank {
+                        match lines[start - 1] {
+                            Line::Text(_) => start -= 1,
+                            _ => break,
+                        }
+                    }
+                    if start > 0 && lines[start - 1] != blank {
+                        lines.insert(start, blank.clone());
+                    }
+                    lines.push(Line::Section {
+                        raw: format!("{}{}", raw.trim(), cr),
+                        name,
+                    });
+                }
+                Line::Option {
+                    ref key, ref value, ..
+                } => {
+                    let spelling = line.spelling().unwrap_or(key);
+                    let separator = if line.separator() == Some("") {
+                        String::new()
+                    } else if value.split('\n').next().unwrap_or("").is_empty() {
+                        format!(" {}", self.delimiter)
+                    } else {
+                        format!(" {} ", self.delimiter)
+                    };
+                    let mut normalized = Line::Option {
+                        key: key.clone(),
+                        delimited: !separator.is_empty(),
+                        prefix: format!("{}{}", spelling, separator),
+                        value: String::new(),
+                        suffix: cr.to_string(),
+                        continuation: Vec::new(),
+                    };
+                    if !separator.is_empty() {
+                        normalized.set_value(value, self.delimiter);
+                    }
+                    lines.push(normalized);
+                }
+            }
+        }
+        while lines.last() == Some(&blank) {
+            lines.pop();
+        }
+        self.lines = lines;
+        self.reindex();
+    }
+    pub fn new(default_section: &str) -> Document {
+        Document {
+            default_section: default_section.to_string(),
+            delimiter: '=',
+            final_newline: true,
+            index: Index::default(),
+            line_ending: LineEnding::Lf,
+            lines: Vec::new(),
+        }
+    }
+    /**
+     * Append a continuation line to the last line, which must be an option: `raw` is written,
+     * `text` is appended to the value. `skipped_lines` are the blank and comment lines between the previous
+     * line of the value and this one: the blank lines add empty lines to the value, the comments nothing.
+     */
+    pub fn continue_option(&mut self, skipped_lines: Vec<String>, raw: &str, text: &str) {
+        match self.lines.last_mut() {
+            Some(&mut Line::Option {
+                ref mut value,
+                ref mut continuation,
+                ..
+            }) => {
+                for skipped_line in skipped_lines {
+                    if skipped_line.trim().is_empty() {
+                        value.push('\n');
+                    }
+                    continuation.push(skipped_line);
+                }
+                value.push('\n');
+                value.push_str(text);
+                continuation.push(raw.to_string());
+            }
+            _ => panic!("a continuation line must follow an option"),
+        }
+    }
+    /**
+     * Append a parsed line.
+     */
+    pub fn push(&mut self, line: Line) {
+        let end = self.lines.len();
+        self.insert_lines(end, vec![line]);
+    }
+    /**
+     * Raw values of the lines defining the option in the section, in file order;
+     * `None` for a key without delimiter.
+     */
+    pub fn values(&self, section: &str, option: &str) -> Vec<Option<String>> {
+        self.index
+            .option_lines(section, option)
+            .iter()
+            .map(|&index| match self.lines[index] {
+                ref line @ Line::Option { ref value, .. } if line.separator() != Some("") => {
+                    Some(value.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /**
+     * Byte offsets, in the text of the document, of the key of the last line defining the option in the
+     * section, or of its value if `value` and the value is not empty.
+     */
+    pub fn option_span(&self, section: &str, option: &str, value: bool) -> Option<Range<usize>> {
+        let index = self.find_option(section, option)?;
+        let start = self.offsets()[index];
+        match self.lines[index] {
+            Line::Option {
+                ref prefix,
+                value: ref text,
+                ..
+            } => {
+                let first = text.split('\n').next().unwrap_or("");
+                if value && !first.is_empty() {
+                    return Some(start + prefix.len()..start + prefix.len() + first.len());
+                }
+                let key_start = start + prefix.len() - prefix.trim_start().len();
+                let spelling = self.lines[index].spelling().unwrap_or("");
+                Some(key_start..key_start + spelling.len())
+            }
+            _ => None,
+        }
+    }
+    /**
+     * Number of the last line defining the option in the section, starting at 1.
+     */
+    pub fn option_line(&self, section: &str, option: &str) -> Option<usize> {
+        self.find_option(section, option)
+            .map(|index| self.line_numbers()[index])
+    }
+    /**
+     * Remove every line defining the option in the section.
+     */
+    pub fn remove_option(&mut self, section: &str, option: &str) {
+        while let Some(index) = self.find_option(section, option) {
+            self.remove_lines(index..index + 1);
+        }
+    }
+    /**
+     * Remove the headers and the options of the section.
+     * Blank lines and comments following its last option are kept, as they introduce the next section.
+     */
+    pub fn remove_section(&mut self, section: &str) {
+        while let Some(&header) = self
+            .index
+            .sections
+            .get(section)
+            .and_then(|positions| positions.headers.last())
+        {
+            let headers = &self.index.headers;
+            let next = headers
+                .get(headers.partition_point(|&index| index <= header))
+                .map_or(self.lines.len(), |&index| index);
+            let options = &self.index.sections[section].options;
+            let last = options[..options.partition_point(|&index| index < next)]
+                .last()
+                .map_or(header, |&index| index.max(header));
+            self.remove_lines(header..last + 1);
+        }
+    }
+    /**
+     * Number of the first header of the section, starting at 1.
+     */
+    pub fn section_line(&self, section: &str) -> Option<usize> {
+        self.find_item(section, None)
+            .map(|index| self.line_numbers()[index])
+    }
+    /**
+     * Byte offsets, in the text of the document, of the header of the section.
+     */
+    pub fn section_span(&self, section: &str) -> Option<Range<usize>> {
+        let index = self.find_item(section, None)?;
+        let raw = self.lines[index].raw();
+        let start = self.offsets()[index] + raw.len() - raw.trim_start().len();
+        Some(start..start + raw.trim().len())
+    }
+    /**
+     * Redefine the delimiter of the options added or normalized, `=` by default.
+     */
+    pub fn set_delimiter(&mut self, delimiter: char) {
+        self.delimiter = delimiter;
+    }
+    /**
+     * Define whether the last line ends with a line ending, as in the file read.
+     */
+    pub fn set_final_newline(&mut self, final_newline: bool) {
+        self.final_newline = final_newline;
+    }
+    /**
+     * Redefine the line ending of the lines added, and of the lines already there.
+     */
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+        let cr = self.cr();
+        for line in self.lines.iter_mut() {
+            line.set_ending(cr);
+        }
+    }
+    /**
+     * Redefine the name of the default section.
+     */
+    pub fn set_default_section(&mut self, section: &str) {
+        self.default_section = section.to_string();
+        self.reindex();
+    }
+    /**
+     * Replace the comment of the option or section (see `comment()`) by `comment`, one comment line per
+     * line, indented as the commented line; an empty comment removes it. The lines keep the marker of
+     * the former comment, or use the first marker of the file, `marker` in a file without comments.
+     * Nothing happens if the option or section is missing.
+     */
+    pub fn set_comment(
+        &mut self,
+        section: &str,
+        option: Option<&str>,
+        comment: &str,
+        marker: char,
+    ) {
+        let index = match self.find_item(section, option) {
+            Some(index) => index,
+            None => return,
+        };
+        let block = self.comment_above(index);
+        let raw = self.lines[index].raw();
+        let indent = &raw[..raw.len() - raw.trim_start().len()];
+        let lines = self.comment_lines(comment, &block, indent, marker);
+        self.splice_lines(block, lines);
+    }
+    /**
+     * Replace the comment at the end of the file, separated by a blank line from the last option;
+     * an empty comment removes it.
+     */
+    pub fn set_footer_comment(&mut self, comment: &str, marker: char) {
+        let block = self.footer_block();
+        let mut lines = self.comment_lines(comment, &block, "", marker);
+        let mut start = block.start;
+        if lines.is_empty() {
+            while start > 0 && self.lines[start - 1].is_blank() {
+                start -= 1;
+            }
+        } else if block.is_empty() && start > 0 && !self.lines[start - 1].is_blank() {
+            lines.insert(0, Line::Text(self.cr().to_string()));
+        }
+        self.splice_lines(start..block.end, lines);
+    }
+    /**
+     * Replace the comment at the top of the file, followed by a blank line; an empty comment removes it.
+     */
+    pub fn set_header_comment(&mut self, comment: &str, marker: char) {
+        let block = self.header_block();
+        let mut lines = self.comment_lines(comment, &block, "", marker);
+        let mut end = block.end;
+        let followed = self.lines.get(end).is_none_or(Line::is_blank);
+        if lines.is_empty() {
+            if !block.is_empty() && end < self.lines.len() {
+                end += 1;
+            }
+        } else if !followed {
+            lines.push(Line::Text(self.cr().to_string()));
+        }
+        self.splice_lines(block.start..end, lines);
+    }
+    /**
+     * Replace the inline comment of the last line defining the option in the section by `raw`, prefix
+     * included; an empty `raw` removes it. Nothing happens if the option is missing.
+     */
+    pub fn set_inline_comment(&mut self, section: &str, option: &str, raw: &str) {
+        let index = match self.find_option(section, option) {
+            Some(index) => index,
+            None => return,
+        };
+        if let Line::Option { ref mut suffix, .. } = self.lines[index] {
+            let cr = if suffix.ends_with('\r') { "\r" } else { "" };
+            *suffix = if raw.is_empty() {
+                cr.to_string()
+            } else {
+                let spaces = &suffix[..suffix.len() - suffix.trim_start().len()];
+                format!(
+                    "{}{}{}",
+                    if spaces.is_empty() { " " } else { spaces },
+                    raw,
+                    cr
+                )
+            };
+        }
+    }
+    /**
+     * Change the value of the last line defining the option in the section, or add a line after the last
+     * option of the section. The new line spells the key as `spelling` and uses the same separator as its
+     * neighbour.
+     */
+    pub fn set_value(&mut self, section: &str, option: &str, spelling: &str, new_value: &str) {
+        if let Some(index) = self.find_option(section, option) {
+            self.lines[index].set_value(new_value, self.delimiter);
+            return;
+        }
+        let positions = self.index.sections.get(section);
+        let last_option = positions.and_then(|positions| positions.options.last().cloned());
+        let last_header = positions.and_then(|positions| positions.headers.last().cloned());
+        let insert_at = last_option.max(last_header).map(|index| index + 1);
+        // The first option of the file is the neighbour of the first option of a section.
+        let neighbour = last_option.or_else(|| {
+            self.index
+                .sections
+                .values()
+                .filter_map(|positions| positions.options.first().cloned())
+                .min()
+        });
+        let separator = match neighbour.and_then(|index| self.lines[index].separator()) {
+            // A key without delimiter
+            Some("") => format!(" {} ", self.delimiter),
+            Some(separator) => separator.to_string(),
+            None => self.delimiter.to_string(),
+        };
+        let cr = self.cr();
+        let mut line = Line::Option {
+            key: option.to_string(),
+            delimited: true,
+            prefix: format!("{}{}", spelling, separator),
+            value: String::new(),
+            suffix: cr.to_string(),
+            continuation: Vec::new(),
+        };
+        line.set_value(new_value, self.delimiter);
+        match insert_at {
+            Some(index) => self.insert_lines(index, vec![line]),
+            None => {
+                // The default section is missing: it is written first.
+                let header = Line::Section {
+                    name: section.to_string(),
+                    raw: format!("[{}]{}", section, cr),
+                };
+                self.insert_lines(0, vec![header, line]);
+            }
+        }
+    }
+    /**
+     * End of the lines added before their `\n`: `\r` for CRLF, empty for LF.
+     */
+    fn cr(&self) -> &'static str {
+        match self.line_ending {
+            LineEnding::CrLf => "\r",
+            LineEnding::Lf => "",
+        }
+    }
+    /**
+     * Indexes of the comment lines right above the line at `index`.
+     */
+    fn comment_above(&self, index: usize) -> Range<usize> {
+        let mut start = index;
+        while start > 0 && self.lines[start - 1].is_comment() {
+            start -= 1;
+        }
+        start..index
+    }
+    /**
+     * Lines of a comment replacing the comment lines `block`: each line of `comment` is indented and
+     * starts with the marker of the block, of the first comment of the file, or `marker`. An empty
+     * comment has no lines.
+     */
+    fn comment_lines(
+        &self,
+        comment: &str,
+        block: &Range<usize>,
+        indent: &str,
+        marker: char,
+    ) -> Vec<Line> {
+        // A comment ending with a line ending has no empty last line.
+        let comment = comment.strip_suffix('\n').unwrap_or(comment);
+        if comment.is_empty() {
+            return Vec::new();
+        }
+        let first = if block.is_empty() {
+            self.lines.iter().find(|line| line.is_comment())
+        } else {
+            Some(&self.lines[block.start])
+        };
+        let marker = first
+            .and_then(|line| line.raw().trim_start().chars().next())
+            .unwrap_or(marker);
+        let cr = self.cr();
+        comment
+            .split('\n')
+            .map(|line| match line.trim_end() {
+                "" => Line::Text(format!("{}{}{}", indent, marker, cr)),
+                line => Line::Text(format!("{}{} {}{}", indent, marker, line, cr)),
+            })
+            .collect()
+    }
+    /**
+     * Text of the comment lines `block`, without their markers and the space after them.
+     */
+    fn comment_text(&self, block: Range<usize>) -> Option<String> {
+        if block.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = self.lines[block]
+            .iter()
+            .map(|line| {
+                let raw = line.raw();
+                let text = &raw.trim()[1..];
+                text.strip_prefix(' ').unwrap_or(text).to_string()
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+    /**
+     * Index of the last line defining the option in the section, or of the first header of the section
+     * if `option` is `None`.
+     */
+    fn find_item(&self, section: &str, option: Option<&str>) -> Option<usize> {
+        match option {
+            Some(option) => self.find_option(section, option),
+            None => self
+                .index
+                .sections
+                .get(section)
+                .and_then(|positions| positions.headers.first().cloned()),
+        }
+    }
+    /**
+     * Index of the last line defining the option in the section.
+     */
+    fn find_option(&self, section: &str, option: &str) -> Option<usize> {
+        self.index.option_lines(section, option).last().cloned()
+    }
+    /**
+     * Indexes of the comment lines ending the file, blank lines aside; the block is empty, at the end of
+     * the last line which is not blank, if there is no such comment or it is the header comment.
+     */
+    fn footer_block(&self) -> Range<usize> {
+        let mut end = self.lines.len();
+        while end > 0 && self.lines[end - 1].is_blank() {
+            end -= 1;
+        }
+        let mut start = end;
+        while start > 0 && self.lines[start - 1].is_comment() {
+            start -= 1;
+        }
+        let header = self.header_block();
+        if !header.is_empty() && start < header.end {
+            return end..end;
+        }
+        start..end
+    }
+    /**
+     * Indexes of the comment lines starting the file, blank lines aside, if a blank line or the end of
+     * the file follows them; otherwise, the empty block where they would be.
+     */
+    fn header_block(&self) -> Range<usize> {
+        let start = self
+            .lines
+            .iter()
+            .position(|line| !line.is_blank())
+            .unwrap_or(self.lines.len());
+        let mut end = start;
+        while end < self.lines.len() && self.lines[end].is_comment() {
+            end += 1;
+        }
+        if self.lines.get(end).is_none_or(Line::is_blank) {
+            start..end
+        } else {
+            start..start
+        }
+    }
+    /**
+     * Insert `lines` before the line at `at`, and index them.
+     */
+    fn insert_lines(&mut self, at: usize, lines: Vec<Line>) {
+        let count = lines.len();
+        let header = lines
+            .iter()
+            .any(|line| matches!(*line, Line::Section { .. }));
+        self.lines.splice(at..at, lines);
+        if header && at + count < self.lines.len() {
+            // The lines following a new header change section.
+            return self.reindex();
+        }
+        if at + count < self.lines.len() {
+            // Appended lines move no position.
+            self.index.shift(|index| {
+                if *index >= at {
+                    *index += count
+                }
+            });
+        }
+        for index in at..at + count {
+            match self.lines[index] {
+                Line::Section { ref name, .. } => self.index.add_header(name, index),
+                Line::Option { ref key, .. } => {
+                    let section = self.owner(index).to_string();
+                    self.index.add_option(&section, key, index);
+                }
+                Line::Text(_) => (),
+            }
+        }
+    }
+    /**
+     * Number of the first physical line of each line, starting at 1.
+     */
+    fn line_numbers(&self) -> Vec<usize> {
+        let mut number = 1;
+        self.lines
+            .iter()
+            .map(|line| {
+                let first = number;
+                number += match *line {
+                    Line::Option {
+                        ref continuation, ..
+                    } => 1 + continuation.len(),
+                    _ => 1,
+                };
+                first
+            })
+            .collect()
+    }
+    /**
+     * Byte offset of each line in the text of the document.
+     */
+    fn offsets(&self) -> Vec<usize> {
+        let mut offset = 0;
+        self.lines
+            .iter()
+            .map(|line| {
+                let start = offset;
+                offset += line.raw().len() + 1;
+                start
+            })
+            .collect()
+    }
+    /**
+     * Name of the section owning the line at `index`: the section of the last header up to it.
+     */
+    fn owner(&self, index: usize) -> &str {
+        let headers = &self.index.headers;
+        match headers.partition_point(|&header| header <= index) {
+            0 => &self.default_section,
+            count => match self.lines[headers[count - 1]] {
+                Line::Section { ref name, .. } => name,
+                _ => &self.default_section,
+            },
+        }
+    }
+    /**
+     * Index every line again, after the sections owning the lines changed.
+     */
+    fn reindex(&mut self) {
+        let mut index = Index::default();
+        let mut section = &self.default_section;
+        for (position, line) in self.lines.iter().enumerate() {
+            match *line {
+                Line::Section { ref name, .. } => {
+                    index.add_header(name, position);
+                    section = name;
+                }
+                Line::Option { ref key, .. } => index.add_option(section, key, position),
+                Line::Text(_) => (),
+            }
+        }
+        self.index = index;
+    }
+    /**
+     * Remove the lines `range` and their index.
+     */
+    fn remove_lines(&mut self, range: Range<usize>) {
+        let header = self.lines[range.clone()]
+            .iter()
+            .any(|line| matches!(*line, Line::Section { .. }));
+        if header {
+            // The lines following a removed header change section.
+            self.lines.drain(range);
+            return self.reindex();
+        }
+        for index in range.clone() {
+            if let Line::Option { ref key, .. } = self.lines[index] {
+                let section = self.owner(index).to_string();
+                self.index.remove_option(&section, key, index);
+            }
+        }
+        self.lines.drain(range.clone());
+        let count = range.len();
+        self.index.shift(|index| {
+            if *index >= range.end {
+                *index -= count
+            }
+        });
+    }
+    /**
+     * Replace the lines `range` by `lines`.
+     */
+    fn splice_lines(&mut self, range: Range<usize>, lines: Vec<Line>) {
+        let start = range.start;
+        self.remove_lines(range);
+        self.insert_lines(start, lines);
+    }
+}
+
+/**
+ * Positions, in the lines of a document, of the section headers and of the option lines of each section,
+ * kept up to date as lines are inserted and removed; every list is in file order.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Index {
+    /// Positions of the headers of every section
+    headers: Vec<usize>,
+    /// Positions of the lines of each section
+    sections: HashMap<String, Positions>,
+}
+
+/**
+ * Positions of the lines of a section.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Positions {
+    /// Positions of the headers
+    headers: Vec<usize>,
+    /// Positions of the option lines, by key
+    keys: HashMap<String, Vec<usize>>,
+    /// Positions of the option lines
+    options: Vec<usize>,
+}
+
+impl Index {
+    fn add_header(&mut self, section: &str, index: usize) {
+        insert_position(&mut self.headers, index);
+        let positions = self.sections.entry(section.to_string()).or_default();
+        insert_position(&mut positions.headers, index);
+    }
+    fn add_option(&mut self, section: &str, key: &str, index: usize) {
+        let positions = self.sections.entry(section.to_string()).or_default();
+        insert_position(&mut positions.options, index);
+        insert_position(positions.keys.entry(key.to_string()).or_default(), index);
+    }
+    /**
+     * Positions of the lines defining the option in the section.
+     */
+    fn option_lines(&self, section: &str, option: &str) -> &[usize] {
+        self.sections
+            .get(section)
+            .and_then(|positions| positions.keys.get(option))
+            .map_or(&[], |lines| lines)
+    }
+    fn remove_option(&mut self, section: &str, key: &str, index: usize) {
+        let positions = match self.sections.get_mut(section) {
+            Some(positions) => positions,
+            None => return,
+        };
+        remove_position(&mut positions.options, index);
+        if let Some(lines) = positions.keys.get_mut(key) {
+            remove_position(lines, index);
+            if lines.is_empty() {
+                positions.keys.remove(key);
+            }
+        }
+        if positions.headers.is_empty() && positions.options.is_empty() {
+            self.sections.remove(section);
+        }
+    }
+    /**
+     * Apply `update` to every position.
+     */
+    fn shift<F: FnMut(&mut usize)>(&mut self, mut update: F) {
+        self.headers.iter_mut().for_each(&mut update);
+        for positions in self.sections.values_mut() {
+            positions.headers.iter_mut().for_each(&mut update);
+            positions.options.iter_mut().for_each(&mut update);
+            for lines in positions.keys.values_mut() {
+                lines.iter_mut().for_each(&mut update);
+            }
+        }
+    }
+}
+
+fn insert_position(positions: &mut Vec<usize>, index: usize) {
+    if let Err(at) = positions.binary_search(&index) {
+        positions.insert(at, index);
+    }
+}
+
+fn remove_position(positions: &mut Vec<usize>, index: usize) {
+    if let Ok(at) = positions.binary_search(&index) {
+        positions.remove(at);
+    }
+}
+
+/**
+ * Text of the file: each line followed by `\n`, its `\r` being part of it, but for a last line read without
+ * line ending.
+ */
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, line) in self.lines.iter().enumerate() {
+            if self.final_newline || index + 1 < self.lines.len() {
+                writeln!(f, "{}", line.raw())?;
+            } else {
+                write!(f, "{}", line.raw())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, Line};
+    use model::Random;
+
+    fn document(text: &str) -> Document {
+        let mut document = Document::new("DEFAULT");
+        for raw in text.lines() {
+            let line = if raw.starts_with('[') {
+                Line::Section {
+                    name: raw[1..raw.len() - 1].to_string(),
+                    raw: raw.to_string(),
+                }
+            } else {
+                match raw.find('=') {
+                    Some(index) => {
+                        let value = raw[index + 1..].trim();
+                        let start = raw.len() - raw[index + 1..].trim_start().len();
+                        Line::option(raw, raw[..index].trim(), start..start + value.len(), true)
+                    }
+                    None => Line::Text(raw.to_string()),
+                }
+            };
+            document.push(line);
+        }
+        document
+    }
+
+    #[test]
+    fn option() {
+        let line = Line::option("  key =  value ; comment  ", "key", 9..24, true);
+        assert_eq!(
+            Line::Option {
+                key: "key".to_string(),
+                delimited: true,
+                prefix: "  key =  ".to_string(),
+                value: "value ; comment".to_string(),
+                suffix: "  ".to_string(),
+                continuation: Vec::new(),
+            },
+            line
+        );
+        assert_eq!("  key =  value ; comment  ", line.raw());
+        let line = Line::option("key = value ; comment", "key", 6..11, true);
+        assert_eq!("key = value ; comment", line.raw());
+        assert_eq!(Some(" = "), line.separator());
+        let mut line = Line::option("\tkey # comment", "key", 4..4, false);
+        assert_eq!(Some(""), line.separator());
+        assert_eq!(Some("key"), line.spelling());
+        line.set_value("1", '=');
+        assert_eq!("\tkey = 1 # comment", line.raw());
+        let line = Line::option("key : value", "key", 6..11, true);
+        assert_eq!(Some(" : "), line.separator());
+        assert_eq!(Some("key"), line.spelling());
+    }
+    #[test]
+    fn set_value() {
+        let mut doc = document("[a]\nkey = 1  \n\n# b\n[b]\nkey=2\n");
+        doc.set_value("a", "key", "key", "10");
+        doc.set_value("a", "new", "new", "3");
+        doc.set_value("b", "new", "new", "4");
+        assert_eq!(
+            "[a]\nkey = 10  \nnew = 3\n\n# b\n[b]\nkey=2\nnew=4\n",
+            doc.to_string()
+        );
+    }
+    #[test]
+    fn set_value_empty_section() {
+        let mut doc = document("# header\n[a]\n\n[b]\nkey = 1\n");
+        doc.set_value("a", "key", "key", "0");
+        doc.set_value("DEFAULT", "key", "key", "default");
+        assert_eq!(
+            "[DEFAULT]\nkey = default\n# header\n[a]\nkey = 0\n\n[b]\nkey = 1\n",
+            doc.to_string()
+        );
+    }
+    #[test]
+    fn remove_option() {
+        let mut doc = document("[a]\nkey=1\nother=2\nkey=3\n[b]\nkey=4\n");
+        doc.remove_option("a", "key");
+        assert_eq!("[a]\nother=2\n[b]\nkey=4\n", doc.to_string());
+    }
+    #[test]
+    fn remove_section() {
+        let mut doc = document("[a]\nkey=1\n\n# b\n[b]\nkey=2\n\n# footer\n");
+        doc.remove_section("a");
+        assert_eq!("\n# b\n[b]\nkey=2\n\n# footer\n", doc.to_string());
+        doc.remove_section("b");
+        assert_eq!("\n# b\n\n# footer\n", doc.to_string());
+    }
+    #[test]
+    fn add_section() {
+        let mut doc = document("[a]\nkey=1");
+        doc.add_section("b", "[b]");
+        doc.set_value("b", "key", "key", "2");
+        assert_eq!("[a]\nkey=1\n\n[b]\nkey=2\n", doc.to_string());
+    }
+    #[test]
+    fn values() {
+        let mut doc = document("[a]\n\tkey = 1\nother = 2\n\tkey = 3 \n[b]\nkey = 4\n");
+        doc.push(Line::option("\tkey", "key", 4..4, false));
+        doc.add_value("a", "key", "key", "5");
+        doc.add_value("a", "new", "New", "6");
+        assert_eq!(
+            "[a]\n\tkey = 1\nother = 2\n\tkey = 3 \n\tkey = 5\nNew = 6\n[b]\nkey = 4\n\tkey\n",
+            doc.to_string()
+        );
+        let values = doc.values("a", "key");
+        assert_eq!(
+            vec![
+                Some("1".to_string()),
+                Some("3".to_string()),
+                Some("5".to_string())
+            ],
+            values
+        );
+        assert_eq!(vec![Some("4".to_string()), None], doc.values("b", "key"));
+    }
+    #[test]
+    fn normalize() {
+        let mut doc =
+            document("\n\n  # top  \nKey=1  \n\n\n# a\n[a]\nempty=\nx   =  2\n# b\n[b]\n\n");
+        doc.normalize();
+        assert_eq!(
+            "# top\nKey = 1\n\n# a\n[a]\nempty =\nx = 2\n\n# b\n[b]\n",
+            doc.to_string()
+        );
+        let mut doc = document("[a]\nkey = 1");
+        doc.continue_option(vec!["".to_string()], "\t more", "more");
+        doc.normalize();
+        assert_eq!("[a]\nkey = 1\n\n    more\n", doc.to_string());
+        let mut doc = document("[a]\n");
+        doc.set_delimiter(':');
+        doc.set_value("a", "key", "key", "1");
+        doc.continue_option(vec![], "  bare", "bare");
+        doc.push(Line::option("flag", "flag", 4..4, false));
+        assert_eq!("[a]\nkey:1\n  bare\nflag\n", doc.to_string());
+        doc.normalize();
+        assert_eq!("[a]\nkey : 1\n    bare\nflag\n", doc.to_string());
+    }
+    #[test]
+    fn comments() {
+        let mut doc = document(
+            "; top\n;\n;   indented\n\n; about a\n[a]\nkey=1\n; about k\n\tk=2\n\n; end\n",
+        );
+        assert_eq!(Some("top\n\n  indented".to_string()), doc.header_comment());
+        assert_eq!(Some("about a".to_string()), doc.comment("a", None));
+        assert_eq!(None, doc.comment("a", Some("key")));
+        assert_eq!(Some("about k".to_string()), doc.comment("a", Some("k")));
+        assert_eq!(Some("end".to_string()), doc.footer_comment());
+        assert_eq!(None, doc.comment("b", None));
+
+        doc.set_comment("a", Some("k"), "first\nsecond", '#');
+        doc.set_comment("a", Some("key"), "the key\n", '#');
+        doc.set_comment("a", None, "", '#');
+        doc.set_footer_comment("", '#');
+        doc.set_header_comment("new top", '#');
+        assert_eq!(
+            "; new top\n\n[a]\n; the key\nkey=1\n\t; first\n\t; second\n\tk=2\n",
+            doc.to_string()
+        );
+        doc.set_header_comment("", '#');
+        doc.set_footer_comment("end", '#');
+        assert_eq!(
+            "[a]\n; the key\nkey=1\n\t; first\n\t; second\n\tk=2\n\n; end\n",
+            doc.to_string()
+        );
+        assert_eq!(None, doc.header_comment());
+
+        let mut doc = document("# only\n");
+        assert_eq!(Some("only".to_string()), doc.header_comment());
+        assert_eq!(None, doc.footer_comment());
+        let mut doc2 = document("[a]\n");
+        doc2.set_header_comment("top", '#');
+        doc2.set_comment("a", None, "a", '#');
+        assert_eq!("# top\n\n# a\n[a]\n", doc2.to_string());
+        assert_eq!(Some("top".to_string()), doc2.header_comment());
+        doc.set_footer_comment("bottom", ';');
+        assert_eq!("# only\n\n# bottom\n", doc.to_string());
+    }
+    #[test]
+    fn inline_comments() {
+        let mut doc = document("[a]\n");
+        doc.push(Line::option("k = v  ; note", "k", 4..5, true));
+        doc.push(Line::option("bare", "bare", 4..4, false));
+        assert_eq!(Some("; note"), doc.inline_comment("a", "k"));
+        assert_eq!(None, doc.inline_comment("a", "bare"));
+        doc.set_inline_comment("a", "k", "# other");
+        doc.set_inline_comment("a", "bare", "# flag");
+        doc.set_inline_comment("a", "missing", "# nothing");
+        assert_eq!("[a]\nk = v  # other\nbare # flag\n", doc.to_string());
+        doc.set_inline_comment("a", "k", "");
+        assert_eq!(None, doc.inline_comment("a", "k"));
+        assert_eq!("[a]\nk = v\nbare # flag\n", doc.to_string());
+    }
+    #[test]
+    fn spans() {
+        let mut doc = document("[a]\n  key = 1 \n[b]\nkey=\n");
+        doc.continue_option(vec![], "  more", "more");
+        let text = doc.to_string();
+        let span = doc.option_span("a", "key", true).unwrap();
+        assert_eq!("1", &text[span]);
+        let span = doc.option_span("a", "key", false).unwrap();
+        assert_eq!("key", &text[span]);
+        let span = doc.option_span("b", "key", true).unwrap();
+        assert_eq!("key", &text[span]);
+        assert_eq!("[b]", &text[doc.section_span("b").unwrap()]);
+        assert_eq!(None, doc.option_span("b", "other", true));
+        assert_eq!(None, doc.section_span("c"));
+    }
+    #[test]
+    fn line_numbers() {
+        let mut doc = document("# header\n[a]\nkey = 1\n[b]\nkey = 2\nkey = 3\n");
+        doc.continue_option(vec![], "  more", "more");
+        assert_eq!(Some(2), doc.section_line("a"));
+        assert_eq!(Some(3), doc.option_line("a", "key"));
+        assert_eq!(Some(6), doc.option_line("b", "key"));
+        assert_eq!(None, doc.option_line("a", "other"));
+        doc.push(Line::Section {
+            name: "c".to_string(),
+            raw: "[c]".to_string(),
+        });
+        assert_eq!(Some(8), doc.section_line("c"));
+    }
+    #[test]
+    fn continue_option() {
+        let mut doc = document("[a]\nkey = first");
+        doc.continue_option(vec!["".to_string()], "\t  second ", "second");
+        assert_eq!(Some("first\n\nsecond"), doc.last_value());
+        assert_eq!("[a]\nkey = first\n\n\t  second \n", doc.to_string());
+        doc.set_value("a", "key", "key", "1\n2\n\n3");
+        assert_eq!("[a]\nkey = 1\n\t  2\n\n\t  3\n", doc.to_string());
+        doc.set_value("a", "other", "other", "\nx");
+        assert_eq!(
+            "[a]\nkey = 1\n\t  2\n\n\t  3\nother = \n    x\n",
+            doc.to_string()
+        );
+    }
+    #[test]
+    fn index_matches_reference() {
+        let sections = ["DEFAULT", "a", "b", "c"];
+        let keys = ["k0", "k1", "k2"];
+        for seed in 1..40 {
+            let mut random = Random::new(seed);
+            let mut doc =
+                document("k0 = top\n# comment\n[a]\nk1 = 1\n\n[b]\nk0 = 2\n[a]\nk1 = 3\n");
+            for step in 0..120 {
+                let section = sections[random.below(sections.len())];
+                let key = keys[random.below(keys.len())];
+                let value = step.to_string();
+                match random.below(9) {
+                    0 | 1 => doc.set_value(section, key, key, &value),
+                    2 => doc.add_value(section, key, key, &value),
+                    3 => doc.remove_option(section, key),
+                    4 => doc.remove_section(section),
+                    5 => doc.add_section(section, &format!("[{}]", section)),
+                    6 => doc.set_comment(section, Some(key), &value, '#'),
+                    7 => doc.mark_conflict(section, key, Some(&value)),
+                    _ => doc.set_header_comment(&value, ';'),
+                }
+                if random.below(20) == 0 {
+                    doc.normalize();
+                }
+                // Naive model: the owner of each line is found by scanning the lines.
+                let mut owners = Vec::new();
+                let mut owner = "DEFAULT".to_string();
+                for line in doc.lines.iter() {
+                    if let Line::Section { ref name, .. } = *line {
+                        owner = name.clone();
+                    }
+                    owners.push(owner.clone());
+                }
+                for section in sections.iter() {
+                    let header = doc.lines.iter().position(|line| match *line {
+                        Line::Section { ref name, .. } => name == section,
+                        _ => false,
+                    });
+                    assert_eq!(header, doc.find_item(section, None), "seed {}", seed);
+                    for key in keys.iter() {
+                        let expected: Vec<usize> = (0..doc.lines.len())
+                            .filter(|&index| match doc.lines[index] {
+                                Line::Option { key: ref k, .. } => {
+                                    k == key && owners[index] == *section
+                                }
+                                _ => false,
+                            })
+                            .collect();
+                        assert_eq!(
+                            expected,
+                            doc.index.option_lines(section, key),
+                            "seed {} step {}",
+                            seed,
+                            step
+                        );
+                    }
+                }
+                let mut reindexed = doc.clone();
+                reindexed.reindex();
+                assert_eq!(reindexed.index, doc.index, "seed {} step {}", seed, step);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Character encodings and line endings of INI files, see `IniFile::set_encoding()`.
+//!
+//! A byte order mark tells the encoding of a file, and is removed; without one, a file is UTF-8, or UTF-16
+//! if its first character is ASCII followed or preceded by a zero byte. Latin-1 and Windows-1252 files can
+//! not be told from UTF-8 ones, their encoding must be set before reading them.
+//!
+//! Files are written back with the encoding, byte order mark and line endings they were read with.
+
+use std::convert::TryFrom;
+
+/**
+ * Character encoding of an INI file.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// UTF-16, little endian, as Windows writes it.
+    Utf16Le,
+    /// UTF-16, big endian.
+    Utf16Be,
+    /// ISO-8859-1: each byte is the character of the same code point.
+    Latin1,
+    /// Windows-1252: Latin-1 with printable characters, such as `€`, in place of the C1 control codes.
+    Windows1252,
+}
+
+/**
+ * Line ending of an INI file.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    CrLf,
+}
+
+/**
+ * How the text of a file is stored in bytes.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Format {
+    pub bom: bool,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+}
+
+/// Characters of the bytes 0x80 to 0x9F in Windows-1252; the 5 unassigned bytes are kept as C1 controls.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/**
+ * Text of a file, and its format: the byte order mark, if any, tells the encoding; otherwise `encoding`
+ * is used, UTF-8 files being told from UTF-16 ones. The line ending is the one of the first line.
+ */
+pub(crate) fn decode(bytes: &[u8], encoding: Encoding) -> Result<(String, Format), String> {
+    let (bom, encoding, content) = if let Some(content) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (true, Encoding::Utf8, content)
+    } else if let Some(content) = bytes.strip_prefix(b"\xFF\xFE") {
+        (true, Encoding::Utf16Le, content)
+    } else if let Some(content) = bytes.strip_prefix(b"\xFE\xFF") {
+        (true, Encoding::Utf16Be, content)
+    } else if encoding == Encoding::Utf8 && bytes.len() >= 2 && bytes[0] != 0 && bytes[1] == 0 {
+        (false, Encoding::Utf16Le, bytes)
+    } else if encoding == Encoding::Utf8 && bytes.len() >= 2 && bytes[0] == 0 && bytes[1] != 0 {
+        (false, Encoding::Utf16Be, bytes)
+    } else {
+        (false, encoding, bytes)
+    };
+    let text = match encoding {
+        Encoding::Utf8 => match String::from_utf8(content.to_vec()) {
+            Ok(text) => text,
+            Err(e) => {
+                return Err(format!(
+                    "invalid UTF-8 at byte {}; Latin-1 and Windows-1252 files must be read with set_encoding()",
+                    e.utf8_error().valid_up_to() + if bom { 3 } else { 0 }
+                ))
+            }
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if content.len() % 2 == 1 {
+                return Err("odd number of bytes in a UTF-16 file".to_string());
+            }
+            let units = content.chunks(2).map(|pair| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            });
+            let mut text = String::with_capacity(content.len() / 2);
+            for c in char::decode_utf16(units) {
+                match c {
+                    Ok(c) => text.push(c),
+                    Err(e) => {
+                        return Err(format!(
+                            "unpaired surrogate {:#X} in a UTF-16 file",
+                            e.unpaired_surrogate()
+                        ))
+                    }
+                }
+            }
+            text
+        }
+        Encoding::Latin1 => content.iter().map(|&byte| char::from(byte)).collect(),
+        Encoding::Windows1252 => content
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+                _ => char::from(byte),
+            })
+            .collect(),
+    };
+    let line_ending = match text.find('\n') {
+        Some(index) if text[..index].ends_with('\r') => LineEnding::CrLf,
+        _ => LineEnding::Lf,
+    };
+    let format = Format {
+        bom,
+        encoding,
+        line_ending,
+    };
+    Ok((text, format))
+}
+
+/**
+ * Bytes of a text in the given format, its line endings written as they are; or the first character the
+ * encoding can not represent.
+ */
+pub(crate) fn encode(text: &str, format: Format) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    let unencodable = |c: char| format!("{:?} can not be encoded in {:?}", c, format.encoding);
+    match format.encoding {
+        Encoding::Utf8 => {
+            if format.bom {
+                bytes.extend_from_slice(b"\xEF\xBB\xBF");
+            }
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let little_endian = format.encoding == Encoding::Utf16Le;
+            let units = if format.bom { Some(0xFEFF) } else { None };
+            for unit in units.into_iter().chain(text.encode_utf16()) {
+                if little_endian {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                } else {
+                    bytes.extend_from_slice(&unit.to_be_bytes());
+                }
+            }
+        }
+        Encoding::Latin1 => {
+            for c in text.chars() {
+                match u8::try_from(u32::from(c)) {
+                    Ok(byte) => bytes.push(byte),
+                    Err(_) => return Err(unencodable(c)),
+                }
+            }
+        }
+        Encoding::Windows1252 => {
+            for c in text.chars() {
+                match WINDOWS_1252.iter().position(|&special| special == c) {
+                    Some(index) => bytes.push(0x80 + index as u8),
+                    None => match u8::try_from(u32::from(c)) {
+                        Ok(byte) if !(0x80..=0x9F).contains(&byte) => bytes.push(byte),
+                        _ => return Err(unencodable(c)),
+                    },
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Encoding, Format, LineEnding};
+
+    #[test]
+    fn byte_order_marks() {
+        let (text, format) = decode(b"\xEF\xBB\xBF[a]\r\nk=v\r\n", Encoding::Utf8).unwrap();
+        assert_eq!("[a]\r\nk=v\r\n", text);
+        assert!(format.bom);
+        assert_eq!(LineEnding::CrLf, format.line_ending);
+        let utf16: Vec<u8> = b"\xFF\xFE[\0a\0]\0\n\0".to_vec();
+        let (text, format) = decode(&utf16, Encoding::Utf8).unwrap();
+        assert_eq!("[a]\n", text);
+        assert_eq!(Encoding::Utf16Le, format.encoding);
+        assert_eq!(utf16, encode(&text, format).unwrap());
+        let (text, format) = decode(b"\0[\0a\0]", Encoding::Utf8).unwrap();
+        assert_eq!(
+            ("[a]", Encoding::Utf16Be, false),
+            (&*text, format.encoding, format.bom)
+        );
+        assert!(decode(b"\xFF\xFE[", Encoding::Utf8).is_err());
+        assert!(decode(b"\xFF\xFE\x00\xD8", Encoding::Utf8).is_err());
+    }
+    #[test]
+    fn single_byte_encodings() {
+        assert!(decode(b"k=caf\xE9\n", Encoding::Utf8)
+            .unwrap_err()
+            .contains("byte 5"));
+        let (text, format) = decode(b"k=caf\xE9 \x80\n", Encoding::Latin1).unwrap();
+        assert_eq!("k=caf\u{e9} \u{80}\n", text);
+        assert_eq!(b"k=caf\xE9 \x80\n".to_vec(), encode(&text, format).unwrap());
+        let (text, format) = decode(b"k=caf\xE9 \x80\x81\n", Encoding::Windows1252).unwrap();
+        assert_eq!("k=caf\u{e9} \u{20ac}\u{81}\n", text);
+        assert_eq!(
+            b"k=caf\xE9 \x80\x81\n".to_vec(),
+            encode(&text, format).unwrap()
+        );
+        let latin1 = Format {
+            encoding: Encoding::Latin1,
+            ..Format::default()
+        };
+        assert!(encode("\u{20ac}", latin1).is_err());
+    }
+    #[test]
+    fn line_endings() {
+        let crlf = Format {
+            line_ending: LineEnding::CrLf,
+            ..Format::default()
+        };
+        assert_eq!(b"a\nb\r\n".to_vec(), encode("a\nb\r\n", crlf).unwrap());
+        assert_eq!(
+            b"a\nb\n".to_vec(),
+            encode("a\nb\n", Format::default()).unwrap()
+        );
+    }
+}
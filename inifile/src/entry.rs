@@ -0,0 +1,187 @@
+//! Entry API on the options of an `IniFile`, as `HashMap::entry()`, see `IniFile::entry()`.
+//!
+//! An entry is occupied when the section itself defines the option in the file: the overrides and the
+//! options of the default section are not considered, since inserting writes the option in the section.
+
+use IniFile;
+
+/**
+ * Option of a section, defined or not.
+ */
+pub enum Entry<'a> {
+    Occupied(OccupiedEntry<'a>),
+    Vacant(VacantEntry<'a>),
+}
+
+/**
+ * Option defined in the section.
+ */
+pub struct OccupiedEntry<'a> {
+    ini: &'a mut IniFile,
+    /// Normalized option name
+    key: String,
+    section: String,
+}
+
+/**
+ * Option not defined in the section.
+ */
+pub struct VacantEntry<'a> {
+    ini: &'a mut IniFile,
+    /// Normalized option name
+    key: String,
+    section: String,
+    /// Option name as written by the caller, kept in the file
+    spelling: String,
+}
+
+impl<'a> Entry<'a> {
+    /**
+     * Entry of an option; the section must exist or be the default section.
+     */
+    pub(crate) fn new(ini: &'a mut IniFile, section: &str, option: &str) -> Entry<'a> {
+        let key = ini.optionxform(option);
+        if ini.stored_value(section, &key).is_some() {
+            Entry::Occupied(OccupiedEntry {
+                ini,
+                key,
+                section: section.to_string(),
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                ini,
+                key,
+                section: section.to_string(),
+                spelling: option.to_string(),
+            })
+        }
+    }
+    /**
+     * Change the value of a defined option, as written in the file, before any `or_insert()`.
+     */
+    pub fn and_modify<F: FnOnce(&mut String)>(self, f: F) -> Entry<'a> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                let mut value = entry.get().to_string();
+                f(&mut value);
+                entry.insert(value);
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+    /**
+     * Normalized name of the option.
+     */
+    pub fn key(&self) -> &str {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+    /**
+     * Value of the option, defining it with `default` if needed.
+     */
+    pub fn or_insert(self, default: &str) -> &'a str {
+        self.or_insert_with(|| default.to_string())
+    }
+    /**
+     * Value of the option, defining it with the result of `default` if needed.
+     */
+    pub fn or_insert_with<F: FnOnce() -> String>(self, default: F) -> &'a str {
+        match self {
+            Entry::Occupied(entry) => entry.into_value(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+}
+
+impl<'a> OccupiedEntry<'a> {
+    /**
+     * Value of the option, as read or set.
+     */
+    pub fn get(&self) -> &str {
+        // The entry exists as long as it borrows the configuration.
+        self.ini.stored_value(&self.section, &self.key).unwrap()
+    }
+    /**
+     * Set the value of the option, keeping its line in the file, and return the former value.
+     */
+    pub fn insert(&mut self, value: String) -> String {
+        let previous = self.get().to_string();
+        let key = self.key.clone();
+        self.ini.set(self.section.clone(), key, value);
+        previous
+    }
+    /**
+     * Value of the option, borrowing the configuration for the lifetime of the entry.
+     */
+    pub fn into_value(self) -> &'a str {
+        self.ini.stored_value(&self.section, &self.key).unwrap()
+    }
+    /**
+     * Normalized name of the option.
+     */
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+    /**
+     * Remove the option from the section, and return its value.
+     */
+    pub fn remove(self) -> String {
+        let value = self.get().to_string();
+        self.ini.remove_option(self.section, self.key);
+        value
+    }
+}
+
+impl<'a> VacantEntry<'a> {
+    /**
+     * Define the option in the section, and return its value.
+     */
+    pub fn insert(self, value: String) -> &'a str {
+        self.ini.set(self.section.clone(), self.spelling, value);
+        self.ini.stored_value(&self.section, &self.key).unwrap()
+    }
+    /**
+     * Normalized name of the option.
+     */
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+    use IniFile;
+
+    #[test]
+    fn entries() {
+        let mut ini: IniFile = "[DEFAULT]\ncolor = red\n[server]\nport = 80 ; http\nhost = a\n"
+            .parse()
+            .unwrap();
+        assert_eq!("80 ; http", ini.entry("server", "Port").or_insert("8080"));
+        assert_eq!("blue", ini.entry("server", "Color").or_insert("blue"));
+        ini.entry("server", "retries")
+            .and_modify(|value| value.push('0'))
+            .or_insert_with(|| "3".to_string());
+        ini.entry("server", "retries")
+            .and_modify(|value| value.push('0'))
+            .or_insert("1");
+        assert_eq!("30", ini.get("server", "retries"));
+        match ini.entry("server", "host") {
+            Entry::Occupied(entry) => assert_eq!("a", entry.remove()),
+            Entry::Vacant(_) => panic!("host is defined"),
+        }
+        match ini.entry("server", "HOST") {
+            Entry::Vacant(entry) => assert_eq!("host", entry.key()),
+            Entry::Occupied(_) => panic!("host was removed"),
+        }
+        assert_eq!(
+            "[DEFAULT]\ncolor = red\n[server]\nport = 80 ; http\nColor = blue\nretries = 30\n",
+            ini.to_string()
+        );
+        assert!(ini.try_entry("missing", "key").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Errors returned by the `try_` methods of `IniFile`.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use diagnostic::Diagnostic;
+use interpolation::InterpolationError;
+use schema::ValidationReport;
+
+/// Result type of the fallible `IniFile` methods.
+pub type IniResult<T> = Result<T, IniError>;
+
+/**
+ * Every way an INI operation can fail.
+ */
+#[derive(Debug)]
+pub enum IniError {
+    /// The file could not be opened, read or written.
+    Io(io::Error),
+    /// A line could not be parsed; the diagnostic tells the file, line, column and span of the problem.
+    Syntax(Box<Diagnostic>),
+    /// The named section does not exist.
+    MissingSection(String),
+    /// The section exists but does not contain the option.
+    MissingOption { section: String, option: String },
+    /// The value can not be coerced to the expected type.
+    TypeMismatch {
+        section: String,
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// A section by the given name already exists.
+    DuplicateSection(String),
+    /// A structure can not be serialized or deserialized (with the `serde` feature).
+    Serde(String),
+    /// The references in the value of the option can not be expanded.
+    Interpolation {
+        section: String,
+        option: String,
+        cause: InterpolationError,
+    },
+    /// An override argument or variable can not be parsed.
+    InvalidOverride(String),
+    /// A `LayeredIni` has no layer at the given index.
+    MissingLayer(usize),
+    /// The file does not match its schema; the report lists every violation.
+    Validation(ValidationReport),
+    /// A file included by `!include` or `!includedir` can not be read or parsed.
+    Include { path: PathBuf, cause: Box<IniError> },
+    /// Files include each other; the chain starts and ends with the same file.
+    IncludeCycle(Vec<PathBuf>),
+    /// The bytes of a file are not valid in its encoding, or a character can not be written in it.
+    Encoding(String),
+    /// A comment can not be written so that it is read back.
+    InvalidComment(String),
+}
+
+impl fmt::Display for IniError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IniError::Io(ref e) => write!(f, "I/O error: {}", e),
+            IniError::Syntax(ref diagnostic) => match diagnostic.line() {
+                Some(line) => write!(f, "syntax error at line {}: {}", line, diagnostic.message),
+                None => write!(f, "syntax error: {}", diagnostic.message),
+            },
+            IniError::MissingSection(ref section) => {
+                write!(f, "section [{}] does not exist", section)
+            }
+            IniError::MissingOption {
+                ref section,
+                ref option,
+            } => write!(
+                f,
+                "option {:?} does not exist in section [{}]",
+                option, section
+            ),
+            IniError::TypeMismatch {
+                ref section,
+                ref option,
+                ref value,
+                expected,
+            } => write!(
+                f,
+                "[{}] {}: {:?} is not {}",
+                section, option, value, expected
+            ),
+            IniError::DuplicateSection(ref section) => {
+                write!(f, "section [{}] already exists", section)
+            }
+            IniError::Serde(ref message) => write!(f, "{}", message),
+            IniError::Interpolation {
+                ref section,
+                ref option,
+                ref cause,
+            } => write!(f, "[{}] {}: {}", section, option, cause),
+            IniError::InvalidOverride(ref message) => write!(f, "invalid override: {}", message),
+            IniError::MissingLayer(layer) => write!(f, "layer {} does not exist", layer),
+            IniError::Validation(ref report) => write!(f, "invalid configuration:\n{}", report),
+            IniError::Include {
+                ref path,
+                ref cause,
+            } => write!(f, "{}: {}", path.display(), cause),
+            IniError::IncludeCycle(ref chain) => {
+                let chain: Vec<String> = chain
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "include cycle: {}", chain.join(" -> "))
+            }
+            IniError::Encoding(ref message) => write!(f, "encoding error: {}", message),
+            IniError::InvalidComment(ref message) => write!(f, "invalid comment: {}", message),
+        }
+    }
+}
+
+impl IniError {
+    /**
+     * The problems behind the error, to render them: the syntax error, the violations of the schema
+     * in line order; other errors give a diagnostic without location.
+     */
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match *self {
+            IniError::Syntax(ref diagnostic) => vec![(**diagnostic).clone()],
+            IniError::Validation(ref report) => report.diagnostics(),
+            IniError::Include { ref cause, .. } if cause.is_located() => cause.diagnostics(),
+            _ => vec![Diagnostic::error(self.to_string(), None)],
+        }
+    }
+    /**
+     * Whether the diagnostics of the error tell where the problems are.
+     */
+    fn is_located(&self) -> bool {
+        match *self {
+            IniError::Syntax(_) | IniError::Validation(_) => true,
+            IniError::Include { ref cause, .. } => cause.is_located(),
+            _ => false,
+        }
+    }
+}
+
+impl error::Error for IniError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            IniError::Io(ref e) => Some(e),
+            IniError::Include { ref cause, .. } => Some(&**cause),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IniError {
+    fn from(e: io::Error) -> IniError {
+        IniError::Io(e)
+    }
+}
@@ -0,0 +1,109 @@
+//! Files included by the `!include path.ini` and `!includedir conf.d/` directives.
+//!
+//! Paths are relative to the directory of the including file. `!includedir` includes the `*.ini` files
+//! of a directory, hidden files excepted, in the byte order of their names. An included file is read as
+//! a file of its own: its options before any section header belong to the default section, and the
+//! including file goes on in the section it was in.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use document::Document;
+use encoding::Format;
+use error::{IniError, IniResult};
+
+/**
+ * A file read through an include directive, with its own layout so that it is written back in place.
+ */
+#[derive(Clone)]
+pub(crate) struct Included {
+    pub document: Document,
+    /// Encoding, byte order mark and line endings of the file
+    pub format: Format,
+    /// Whether an option of the file was edited since it was read: only such files are saved.
+    pub modified: bool,
+    pub path: PathBuf,
+}
+
+/**
+ * Paths of the files included by a directive of the file `including`.
+ */
+pub(crate) fn paths(including: &Path, name: &str, argument: &str) -> IniResult<Vec<PathBuf>> {
+    let base = including.parent().unwrap_or_else(|| Path::new(""));
+    let path = base.join(argument);
+    if name == "include" {
+        return Ok(vec![path]);
+    }
+    directory_files(&path).map_err(|e| IniError::Include {
+        path,
+        cause: Box::new(IniError::Io(e)),
+    })
+}
+
+/**
+ * Files of a directory included by `!includedir`, sorted.
+ */
+fn directory_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with('.') && name.ends_with(".ini") && entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::paths;
+    use error::IniError;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use test_directory;
+
+    #[test]
+    fn included_paths() {
+        let directory = test_directory("include-paths");
+        let main = &directory.join("main.ini");
+        assert_eq!(
+            vec![directory.join("common.ini")],
+            paths(main, "include", "common.ini").unwrap()
+        );
+        assert_eq!(
+            vec![PathBuf::from("common.ini")],
+            paths(Path::new("main.ini"), "include", "common.ini").unwrap()
+        );
+        let included = directory.join("conf.d");
+        fs::create_dir_all(&included).unwrap();
+        for name in [
+            "b.ini",
+            "a.ini",
+            "10.ini",
+            ".hidden.ini",
+            "c.ini.bak",
+            "d.txt",
+        ]
+        .iter()
+        {
+            fs::write(included.join(name), "").unwrap();
+        }
+        fs::create_dir_all(included.join("sub.ini")).unwrap();
+        let found = paths(main, "includedir", "conf.d/").unwrap();
+        let missing = paths(main, "includedir", "missing.d");
+        fs::remove_dir_all(&directory).unwrap();
+        let names: Vec<String> = found
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(vec!["10.ini", "a.ini", "b.ini"], names);
+        match missing {
+            Err(IniError::Include { path, .. }) => assert_eq!(directory.join("missing.d"), path),
+            other => panic!("IniError::Include expected, not {:?}", other),
+        }
+    }
+}
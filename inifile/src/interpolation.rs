@@ -0,0 +1,343 @@
+//! Value interpolation, as Python's `BasicInterpolation` and `ExtendedInterpolation`.
+//!
+//! @see https://docs.python.org/3/library/configparser.html#interpolation-of-values
+
+use std::fmt;
+
+/// Maximum number of nested references, as Python's `MAX_INTERPOLATION_DEPTH`.
+pub const MAX_INTERPOLATION_DEPTH: usize = 10;
+
+/**
+ * Interpolation applied by `IniFile::get()` and the typed getters.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Values are returned as written in the file.
+    None,
+    /// `%(name)s` refers to an option of the same section (or of DEFAULT), `%%` is a literal `%`.
+    Basic,
+    /// `${name}` refers to an option of the same section (or of DEFAULT),
+    /// `${section:name}` to an option of another section, `$$` is a literal `$`.
+    Extended,
+}
+
+/**
+ * Reasons why a value can not be interpolated.
+ */
+#[derive(Debug, PartialEq)]
+pub enum InterpolationError {
+    /// The value contains a malformed reference.
+    Syntax(String),
+    /// The referenced option (`section`, `option`) does not exist.
+    MissingReference(String, String),
+    /// The references loop; the chain of (`section`, `option`) leading back to the first one.
+    Cycle(Vec<(String, String)>),
+    /// More than the given number of nested references.
+    DepthExceeded(usize),
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InterpolationError::Syntax(ref message) => write!(f, "{}", message),
+            InterpolationError::MissingReference(ref section, ref option) => write!(
+                f,
+                "bad reference to option {:?} of section [{}]",
+                option, section
+            ),
+            InterpolationError::Cycle(ref chain) => {
+                let chain: Vec<String> = chain
+                    .iter()
+                    .map(|(section, option)| format!("[{}] {}", section, option))
+                    .collect();
+                write!(f, "reference cycle: {}", chain.join(" -> "))
+            }
+            InterpolationError::DepthExceeded(depth) => {
+                write!(f, "more than {} nested references", depth)
+            }
+        }
+    }
+}
+
+/**
+ * Piece of a value: literal text or a reference to an option.
+ */
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Reference(Option<String>, String),
+}
+
+/**
+ * Expand the references of `value`, read from option `option` of section `section`.
+ * `lookup(section, option)` returns the raw value of a referenced option.
+ */
+pub fn interpolate<F>(
+    mode: Interpolation,
+    lookup: &F,
+    section: &str,
+    option: &str,
+    value: &str,
+    max_depth: usize,
+) -> Result<String, InterpolationError>
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    let mut chain = vec![(section.to_string(), option.to_string())];
+    expand(mode, lookup, section, value, &mut chain, max_depth)
+}
+
+fn expand<F>(
+    mode: Interpolation,
+    lookup: &F,
+    section: &str,
+    value: &str,
+    chain: &mut Vec<(String, String)>,
+    max_depth: usize,
+) -> Result<String, InterpolationError>
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    let tokens = match mode {
+        Interpolation::None => return Ok(value.to_string()),
+        Interpolation::Basic => tokenize_basic(value)?,
+        Interpolation::Extended => tokenize_extended(value)?,
+    };
+    let mut expanded = String::new();
+    for token in tokens {
+        match token {
+            Token::Text(text) => expanded.push_str(&text),
+            Token::Reference(ref_section, ref_option) => {
+                let ref_section = ref_section.unwrap_or_else(|| section.to_string());
+                let link = (ref_section.clone(), ref_option.clone());
+                if chain.contains(&link) {
+                    chain.push(link);
+                    return Err(InterpolationError::Cycle(chain.clone()));
+                }
+                if chain.len() > max_depth {
+                    return Err(InterpolationError::DepthExceeded(max_depth));
+                }
+                let raw = match lookup(&ref_section, &ref_option) {
+                    Some(raw) => raw,
+                    None => {
+                        return Err(InterpolationError::MissingReference(
+                            ref_section,
+                            ref_option,
+                        ))
+                    }
+                };
+                chain.push(link);
+                expanded.push_str(&expand(mode, lookup, &ref_section, &raw, chain, max_depth)?);
+                chain.pop();
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/**
+ * Split a value using `%(name)s` references.
+ */
+fn tokenize_basic(value: &str) -> Result<Vec<Token>, InterpolationError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = value;
+    while let Some(index) = rest.find('%') {
+        text.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if rest.starts_with("%%") {
+            text.push('%');
+            rest = &rest[2..];
+        } else if rest.starts_with("%(") {
+            let end = match rest.find(")s") {
+                Some(end) => end,
+                None => {
+                    return Err(InterpolationError::Syntax(format!(
+                        "bad interpolation variable reference {:?}",
+                        rest
+                    )))
+                }
+            };
+            if !text.is_empty() {
+                tokens.push(Token::Text(text));
+                text = String::new();
+            }
+            tokens.push(Token::Reference(None, rest[2..end].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            return Err(InterpolationError::Syntax(format!(
+                "'%' must be followed by '%' or '(', found: {:?}",
+                rest
+            )));
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+/**
+ * Split a value using `${name}` and `${section:name}` references.
+ */
+fn tokenize_extended(value: &str) -> Result<Vec<Token>, InterpolationError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        text.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if rest.starts_with("$$") {
+            text.push('$');
+            rest = &rest[2..];
+        } else if rest.starts_with("${") {
+            let end = match rest.find('}') {
+                Some(end) => end,
+                None => {
+                    return Err(InterpolationError::Syntax(format!(
+                        "bad interpolation variable reference {:?}",
+                        rest
+                    )))
+                }
+            };
+            let path: Vec<&str> = rest[2..end].split(':').collect();
+            let reference = match path.len() {
+                1 => Token::Reference(None, path[0].to_string()),
+                2 => Token::Reference(Some(path[0].to_string()), path[1].to_string()),
+                _ => {
+                    return Err(InterpolationError::Syntax(format!(
+                        "more than one ':' found: {:?}",
+                        &rest[..end + 1]
+                    )))
+                }
+            };
+            if !text.is_empty() {
+                tokens.push(Token::Text(text));
+                text = String::new();
+            }
+            tokens.push(reference);
+            rest = &rest[end + 1..];
+        } else {
+            return Err(InterpolationError::Syntax(format!(
+                "'$' must be followed by '$' or '{{', found: {:?}",
+                rest
+            )));
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpolate, Interpolation, InterpolationError, MAX_INTERPOLATION_DEPTH};
+    use std::collections::HashMap;
+
+    fn values() -> HashMap<(String, String), String> {
+        let mut values = HashMap::new();
+        let mut insert = |section: &str, option: &str, value: &str| {
+            values.insert((section.to_string(), option.to_string()), value.to_string());
+        };
+        insert("paths", "home", "/home/user");
+        insert("paths", "docs", "%(home)s/docs");
+        insert("paths", "music", "${home}/music");
+        insert("paths", "loop1", "%(loop2)s");
+        insert("paths", "loop2", "%(loop1)s");
+        insert("paths", "pictures", "${home}/pictures");
+        insert("other", "backup", "${paths:pictures}.bak");
+        values
+    }
+
+    fn expand(
+        mode: Interpolation,
+        section: &str,
+        value: &str,
+    ) -> Result<String, InterpolationError> {
+        let values = values();
+        let lookup = |s: &str, o: &str| values.get(&(s.to_string(), o.to_string())).cloned();
+        interpolate(
+            mode,
+            &lookup,
+            section,
+            "test",
+            value,
+            MAX_INTERPOLATION_DEPTH,
+        )
+    }
+
+    #[test]
+    fn none() {
+        let found = expand(Interpolation::None, "paths", "%(home)s ${home}");
+        assert_eq!(Ok("%(home)s ${home}".to_string()), found);
+    }
+    #[test]
+    fn basic() {
+        let found = expand(Interpolation::Basic, "paths", "%(docs)s/a%%b");
+        assert_eq!(Ok("/home/user/docs/a%b".to_string()), found);
+    }
+    #[test]
+    fn basic_syntax() {
+        match expand(Interpolation::Basic, "paths", "100%") {
+            Err(InterpolationError::Syntax(_)) => (),
+            other => panic!("InterpolationError::Syntax expected, not {:?}", other),
+        }
+        match expand(Interpolation::Basic, "paths", "%(home") {
+            Err(InterpolationError::Syntax(_)) => (),
+            other => panic!("InterpolationError::Syntax expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn basic_missing_reference() {
+        let found = expand(Interpolation::Basic, "paths", "%(unknown)s");
+        assert_eq!(
+            Err(InterpolationError::MissingReference(
+                "paths".to_string(),
+                "unknown".to_string()
+            )),
+            found
+        );
+    }
+    #[test]
+    fn basic_cycle() {
+        match expand(Interpolation::Basic, "paths", "%(loop1)s") {
+            Err(InterpolationError::Cycle(chain)) => assert_eq!(4, chain.len()),
+            other => panic!("InterpolationError::Cycle expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn extended() {
+        let found = expand(
+            Interpolation::Extended,
+            "other",
+            "${backup} ${paths:music} $$",
+        );
+        assert_eq!(
+            Ok("/home/user/pictures.bak /home/user/music $".to_string()),
+            found
+        );
+    }
+    #[test]
+    fn extended_syntax() {
+        match expand(Interpolation::Extended, "paths", "${a:b:c}") {
+            Err(InterpolationError::Syntax(_)) => (),
+            other => panic!("InterpolationError::Syntax expected, not {:?}", other),
+        }
+        match expand(Interpolation::Extended, "paths", "$home") {
+            Err(InterpolationError::Syntax(_)) => (),
+            other => panic!("InterpolationError::Syntax expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn depth_exceeded() {
+        let lookup = |_: &str, o: &str| {
+            let n: usize = o[1..].parse().unwrap();
+            Some(format!("%(o{})s", n + 1))
+        };
+        let found = interpolate(Interpolation::Basic, &lookup, "s", "o0", "%(o1)s", 3);
+        assert_eq!(Err(InterpolationError::DepthExceeded(3)), found);
+    }
+}
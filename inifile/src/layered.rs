@@ -0,0 +1,462 @@
+//! Configuration stacked from several INI files, such as `/etc/app.ini`, `~/.app.ini` then `./app.ini`.
+//!
+//! Layers are added by increasing precedence, and merged as Python's `ConfigParser.read([...])` would:
+//! an option of a section comes from the last layer defining it in that section, otherwise from the last
+//! layer defining it in the default section. Each value knows its source, and edits go to one chosen layer.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use error::{IniError, IniResult};
+use IniFile;
+
+/**
+ * Where a value comes from. `layer` is the index of the layer, 0 being the layer of lowest precedence;
+ * `section` is the section of the layer defining the option: the requested one or the default section.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// Value read from the file of the layer or from a file it includes, or set in the layer.
+    File {
+        layer: usize,
+        /// File path, may be empty.
+        path: String,
+        section: String,
+        /// Line defining the option, starting at 1.
+        line: Option<usize>,
+    },
+    /// Override of the value of the file, see `IniFile::set_override()`.
+    Override {
+        layer: usize,
+        section: String,
+        /// Where the override comes from, such as `env APP__SERVER__PORT`.
+        source: String,
+    },
+}
+
+impl Source {
+    /**
+     * Index of the layer defining the value.
+     */
+    pub fn layer(&self) -> usize {
+        match *self {
+            Source::File { layer, .. } | Source::Override { layer, .. } => layer,
+        }
+    }
+    /**
+     * Section of the layer defining the value.
+     */
+    pub fn section(&self) -> &str {
+        match *self {
+            Source::File { ref section, .. } | Source::Override { ref section, .. } => section,
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::File {
+                layer,
+                ref path,
+                ref section,
+                line,
+            } => {
+                let path = if path.is_empty() {
+                    format!("layer {}", layer)
+                } else {
+                    path.clone()
+                };
+                match line {
+                    Some(line) => write!(f, "{}:{} [{}]", path, line, section),
+                    None => write!(f, "{} [{}]", path, section),
+                }
+            }
+            Source::Override {
+                ref section,
+                ref source,
+                ..
+            } => write!(f, "{} [{}]", source, section),
+        }
+    }
+}
+
+/**
+ * Stack of `IniFile` layers, see the module documentation for the precedence rules.
+ */
+#[derive(Default)]
+pub struct LayeredIni {
+    /// Layers, by increasing precedence
+    layers: Vec<IniFile>,
+}
+
+impl LayeredIni {
+    /**
+     * Read the file and add it as the layer of highest precedence.
+     */
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> IniResult<()> {
+        let ini = IniFile::load(path)?;
+        self.layers.push(ini);
+        Ok(())
+    }
+    /**
+     * Add a configuration as the layer of highest precedence.
+     */
+    pub fn add_layer(&mut self, ini: IniFile) {
+        self.layers.push(ini);
+    }
+    /**
+     * Read the file and add it as the layer of highest precedence, if it exists.
+     * Return whether the file was added.
+     */
+    pub fn add_optional_file<P: AsRef<Path>>(&mut self, path: P) -> IniResult<bool> {
+        match self.add_file(path) {
+            Ok(()) => Ok(true),
+            Err(IniError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    /**
+     * Get the merged value of an option.
+     * If no layer defines it, panic!()
+     */
+    pub fn get(&self, section: &str, option: &str) -> String {
+        self.try_get(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Indicates whether a layer defines the option in the section or in the default section,
+     * the section existing in one of the layers.
+     */
+    pub fn has_option(&self, section: &str, option: &str) -> bool {
+        self.source(section, option).is_some()
+    }
+    /**
+     * Indicates whether a layer has the section. The default section is not considered.
+     */
+    pub fn has_section(&self, section: &str) -> bool {
+        self.layers.iter().any(|ini| ini.has_section(section))
+    }
+    /**
+     * Indicates whether there is no layer.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+    /**
+     * Getter on a layer, 0 being the layer of lowest precedence.
+     */
+    pub fn layer(&self, index: usize) -> Option<&IniFile> {
+        self.layers.get(index)
+    }
+    /**
+     * Mutable getter on a layer, to edit it and save it alone.
+     */
+    pub fn layer_mut(&mut self, index: usize) -> Option<&mut IniFile> {
+        self.layers.get_mut(index)
+    }
+    /**
+     * Number of layers.
+     */
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+    pub fn new() -> LayeredIni {
+        LayeredIni { layers: Vec::new() }
+    }
+    /**
+     * Options available in the section, in the order of the layers defining them.
+     */
+    pub fn options(&self, section: &str) -> Vec<String> {
+        let mut options: Vec<String> = Vec::new();
+        if !self.has_section(section) {
+            return options;
+        }
+        for ini in self.layers.iter() {
+            for option in ini.own_options(section) {
+                if !options.contains(&option) {
+                    options.push(option);
+                }
+            }
+        }
+        for ini in self.layers.iter() {
+            for option in ini.own_options(&ini.default_section) {
+                if !options.contains(&option) {
+                    options.push(option);
+                }
+            }
+        }
+        options
+    }
+    /**
+     * Sections of every layer, in the order of the layers defining them.
+     */
+    pub fn sections(&self) -> Vec<String> {
+        let mut sections: Vec<String> = Vec::new();
+        for ini in self.layers.iter() {
+            for section in ini.sections() {
+                if !sections.contains(&section) {
+                    sections.push(section);
+                }
+            }
+        }
+        sections
+    }
+    /**
+     * Set an option in the given layer only, adding the section to the layer if needed.
+     * If the layer does not exist, panic!()
+     */
+    pub fn set(&mut self, layer: usize, section: &str, option: &str, value: &str) {
+        self.try_set(layer, section, option, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Where the merged value of an option comes from, or None if no layer defines it.
+     */
+    pub fn source(&self, section: &str, option: &str) -> Option<Source> {
+        if !self.has_section(section) && !self.is_default_section(section) {
+            return None;
+        }
+        let own = self
+            .layers
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|&(_, ini)| ini.own_value(section, &ini.optionxform(option)).is_some());
+        let found = own
+            .map(|(index, ini)| (index, ini, section.to_string()))
+            .or_else(|| {
+                self.layers
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|&(_, ini)| {
+                        ini.own_value(&ini.default_section, &ini.optionxform(option))
+                            .is_some()
+                    })
+                    .map(|(index, ini)| (index, ini, ini.default_section.clone()))
+            });
+        found.map(|(index, ini, section)| {
+            let option = ini.optionxform(option);
+            if let Some(o) = ini.find_override(&section, &option) {
+                return Source::Override {
+                    layer: index,
+                    section,
+                    source: o.source.clone(),
+                };
+            }
+            // The option may come from a file included by the layer.
+            let file = ini.stored(&section, &option).map_or(0, |value| value.file);
+            Source::File {
+                layer: index,
+                path: ini.file_path(file).display().to_string(),
+                line: ini.document(file).option_line(&section, &option),
+                section,
+            }
+        })
+    }
+    /**
+     * Get the merged value of an option, interpolated within the layer defining it.
+     */
+    pub fn try_get(&self, section: &str, option: &str) -> IniResult<String> {
+        match self.source(section, option) {
+            Some(source) => self.layers[source.layer()].try_get(source.section(), option),
+            None if self.has_section(section) || self.is_default_section(section) => {
+                Err(IniError::MissingOption {
+                    section: section.to_string(),
+                    option: option.to_string(),
+                })
+            }
+            None => Err(IniError::MissingSection(section.to_string())),
+        }
+    }
+    /**
+     * Save the given layer into its file; the other layers are left untouched.
+     */
+    pub fn try_save(&self, layer: usize) -> IniResult<()> {
+        self.try_layer(layer)?;
+        self.layers[layer].try_save()
+    }
+    /**
+     * Set an option in the given layer only, adding the section to the layer if needed.
+     */
+    pub fn try_set(
+        &mut self,
+        layer: usize,
+        section: &str,
+        option: &str,
+        value: &str,
+    ) -> IniResult<()> {
+        self.try_layer(layer)?;
+        let ini = &mut self.layers[layer];
+        if section != ini.default_section && !ini.has_section(section) {
+            ini.try_add_section(section)?;
+        }
+        ini.try_set(section.to_string(), option.to_string(), value.to_string())
+    }
+    /**
+     * Whether the section is the default section of a layer.
+     */
+    fn is_default_section(&self, section: &str) -> bool {
+        self.layers.iter().any(|ini| ini.default_section == section)
+    }
+    /**
+     * Check that the layer exists.
+     */
+    fn try_layer(&self, layer: usize) -> IniResult<()> {
+        if layer < self.layers.len() {
+            Ok(())
+        } else {
+            Err(IniError::MissingLayer(layer))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LayeredIni, Source};
+    use std::fs;
+    use test_directory;
+    use IniError;
+    use IniFile;
+
+    fn layered() -> LayeredIni {
+        let mut layered = LayeredIni::new();
+        let mut system: IniFile = "timeout = 10\n[server]\nhost = system\nport = 80\n"
+            .parse()
+            .unwrap();
+        system.set_path(::std::path::Path::new("/etc/app.ini"));
+        layered.add_layer(system);
+        layered.add_layer(
+            "timeout = 20\n\n[server]\nhost = user\n[user]\nname = me\n"
+                .parse()
+                .unwrap(),
+        );
+        layered
+    }
+
+    #[test]
+    fn precedence() {
+        let layered = layered();
+        assert_eq!("user", layered.get("server", "host"));
+        assert_eq!("80", layered.get("server", "port"));
+        assert_eq!("20", layered.get("server", "timeout"));
+        assert_eq!("20", layered.get("user", "TIMEOUT"));
+        assert_eq!(vec!["server", "user"], layered.sections());
+        assert_eq!(vec!["host", "port", "timeout"], layered.options("server"));
+        assert!(!layered.has_option("server", "name"));
+        match layered.try_get("missing", "host") {
+            Err(IniError::MissingSection(_)) => (),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+        match layered.try_get("server", "name") {
+            Err(IniError::MissingOption { .. }) => (),
+            other => panic!("IniError::MissingOption expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn source() {
+        let layered = layered();
+        let source = layered.source("server", "port").unwrap();
+        assert_eq!(
+            Source::File {
+                layer: 0,
+                path: "/etc/app.ini".to_string(),
+                section: "server".to_string(),
+                line: Some(4),
+            },
+            source
+        );
+        assert_eq!("/etc/app.ini:4 [server]", source.to_string());
+        let source = layered.source("server", "timeout").unwrap();
+        assert_eq!("layer 1:1 [DEFAULT]", source.to_string());
+        assert_eq!((1, "DEFAULT"), (source.layer(), source.section()));
+        assert_eq!(None, layered.source("server", "missing"));
+    }
+    #[test]
+    fn source_override() {
+        let mut layered = layered();
+        let system = layered.layer_mut(0).unwrap();
+        system.set_override("server", "port", "8080", "env APP__SERVER__PORT");
+        system.set_override("server", "host", "env", "env APP__SERVER__HOST");
+        let source = layered.source("server", "port").unwrap();
+        assert_eq!(
+            Source::Override {
+                layer: 0,
+                section: "server".to_string(),
+                source: "env APP__SERVER__PORT".to_string(),
+            },
+            source
+        );
+        assert_eq!("env APP__SERVER__PORT [server]", source.to_string());
+        assert_eq!("8080", layered.get("server", "port"));
+        // The file of a layer of higher precedence wins over the override of a lower layer.
+        let source = layered.source("server", "host").unwrap();
+        assert_eq!("layer 1:4 [server]", source.to_string());
+    }
+    #[test]
+    fn source_included() {
+        let directory = &test_directory("layered-include");
+        fs::write(
+            directory.join("main.ini"),
+            "[server]\nhost = main\n!include common.ini\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.join("common.ini"),
+            "# shared\ntimeout = 5\n[server]\nport = 80\n",
+        )
+        .unwrap();
+        let mut layered = LayeredIni::new();
+        layered.add_file(directory.join("main.ini")).unwrap();
+        let port = layered.source("server", "port");
+        let timeout = layered.source("server", "timeout");
+        let host = layered.source("server", "host");
+        fs::remove_dir_all(directory).unwrap();
+        let common = directory.join("common.ini");
+        assert_eq!(
+            format!("{}:4 [server]", common.display()),
+            port.unwrap().to_string()
+        );
+        assert_eq!(
+            format!("{}:2 [DEFAULT]", common.display()),
+            timeout.unwrap().to_string()
+        );
+        let main = directory.join("main.ini");
+        assert_eq!(
+            format!("{}:2 [server]", main.display()),
+            host.unwrap().to_string()
+        );
+    }
+    #[test]
+    fn set_one_layer() {
+        let filepath = "src/data/layered_test.ini";
+        let mut layered = layered();
+        layered
+            .layer_mut(1)
+            .unwrap()
+            .set_path(::std::path::Path::new(filepath));
+        layered.set(1, "server", "port", "8080");
+        layered.set(1, "new", "key", "value");
+        assert_eq!("8080", layered.get("server", "port"));
+        assert_eq!("80", layered.layer(0).unwrap().get("server", "port"));
+        layered.try_save(1).unwrap();
+        let found = fs::read_to_string(filepath).unwrap();
+        fs::remove_file(filepath).unwrap();
+        assert_eq!(
+            "timeout = 20\n\n[server]\nhost = user\nport = 8080\n[user]\nname = me\n\n[new]\nkey = value\n",
+            found
+        );
+        assert!(layered.try_set(2, "server", "port", "1").is_err());
+    }
+    #[test]
+    fn add_optional_file() {
+        let mut layered = LayeredIni::new();
+        assert!(!layered.add_optional_file("src/data/missing.ini").unwrap());
+        assert!(layered.add_optional_file("src/data/config.ini").unwrap());
+        assert_eq!(1, layered.len());
+        let source = layered.source("Integers", "integer2").unwrap();
+        assert!(source.to_string().starts_with("src/data/config.ini:"));
+    }
+}
@@ -16,45 +16,243 @@
 
 #[macro_use]
 extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+
+mod atomic;
+#[cfg(feature = "serde")]
+pub mod de;
+mod diagnostic;
+mod dialect;
+pub mod diff;
+mod document;
+mod encoding;
+mod entry;
+mod error;
+mod include;
+mod interpolation;
+pub mod layered;
+mod model;
+pub mod overrides;
+pub mod parser;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+mod syntax;
+pub mod value;
+mod view;
+pub mod watch;
+
+pub use atomic::Lock;
+#[cfg(feature = "serde")]
+pub use de::{from_ini, from_str};
+pub use diagnostic::{Diagnostic, Location};
+pub use dialect::Dialect;
+pub use diff::{merge, Change, Conflict, Merge};
+use document::{Document, Line};
+use encoding::Format;
+pub use encoding::{Encoding, LineEnding};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::{IniError, IniResult};
+use include::Included;
+pub use interpolation::{Interpolation, InterpolationError, MAX_INTERPOLATION_DEPTH};
+pub use layered::{LayeredIni, Source};
+use model::{OrderedMap, Value};
+pub use overrides::{Override, Overrides};
+pub use parser::{Event, Parser, Reader};
+pub use schema::{OptionSchema, Schema, SectionSchema, ValidationReport, ValueType};
+#[cfg(feature = "serde")]
+pub use ser::{to_ini, to_string};
+pub use syntax::Syntax;
+pub use value::Integer;
+pub use view::{Options, Section, Sections};
+pub use watch::Watcher;
 
 use std::collections::HashMap;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::path::Path;
-
-pub struct IniFile<'a> {
-    /// Comments on sections and options
-    comments: HashMap<String, HashMap<String, String>>,
-    /// Option names, used to keep order (as HashMap doesn't).
-    options: Vec<Vec<String>>,
-    /// INI structure: sections contain options (name=>value)
-    opts: HashMap<String, HashMap<String, String>>,
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct IniFile {
+    /// Number of backups kept by save()
+    backups: usize,
+    /// Boolean words recognized by get_bool(), lowercase
+    boolean_states: HashMap<String, bool>,
+    /// Name of the section whose options are visible in every section
+    default_section: String,
+    /// Syntax of the file
+    dialect: Dialect,
+    /// Lines of the file, comments and layout included
+    document: Document,
+    /// Encoding, byte order mark and line endings of the file, kept when writing it
+    format: Format,
+    /// Files read through `!include` and `!includedir`, in reading order
+    includes: Vec<Included>,
+    /// Interpolation applied by get()
+    interpolation: Interpolation,
+    /// Separator of the items of the lists returned by get_list()
+    list_delimiter: char,
+    /// Normalization of option names, as Python's `optionxform`
+    optionxform: fn(&str) -> String,
+    /// Values overriding those of the file, not written
+    overrides: Vec<Override>,
     /// File path
-    path: &'a Path,
-    /// Section names, used to keep order (as HashMap doesn't).
-    sections: Vec<String>,
+    path: PathBuf,
+    /// INI structure: sections contain options (name=>value), both in file order
+    sections: OrderedMap<OrderedMap<Value>>,
+    /// Parser options: delimiters, keys without value, inline comments, strictness
+    syntax: Syntax,
 }
 
 /**
  * IniFile implements a basic configuration which provides a structure similar to what's found in Microsoft Windows INI files.
  * You can use this to write programs which can be customized by end users easily.
+ *
+ * Methods which can fail panic!() on error; each of them has a `try_` counterpart returning an `IniResult`.
+ *
+ * As in Python, the options of the default section (`DEFAULT` unless redefined) are visible in every
+ * section: a section option overrides the default one, removing it makes the default value visible again.
+ * The default section is not listed by sections() and has_section().
+ *
+ * The file is written back as it was read: comments, blank lines, spaces around `=` and option order are kept.
+ * Keys and values are trimmed. set() only changes the line of the option, or adds one after the last option
+ * of the section.
+ *
+ * Comments are part of the API: `get_comment()` and `set_comment()` read and write the comment lines right
+ * above an option or section header, `get_inline_comment()` and `set_inline_comment()` the comment after a
+ * value, `get_header_comment()` and `get_footer_comment()` the comments at the top and the end of the file.
+ * Written comments keep the marker, `#` or `;`, of the comments they replace or of the file.
+ *
+ * `set_syntax()` adds Python's syntax options: `:` and other delimiters, keys without value, inline
+ * comments, untrimmed keys and values, and a strict mode where a section or option defined twice in a file
+ * is a syntax error. By default, only `=` is a delimiter and duplicates are allowed.
+ *
+ * Syntax errors and schema violations carry the file, line, column and span of the problem, and render
+ * as rustc does: see `IniError::diagnostics()` and `ValidationReport::diagnostics()`.
+ *
+ * Files are saved atomically: written to a temporary file which replaces the file once synced to disk,
+ * keeping its permissions, and optionally its former content as backups (see `set_backups()`).
+ * `load_locked()` reads the file under an advisory lock, which prevents other processes from editing it
+ * until the edits are saved.
+ *
+ * Besides strings, values can be read and written as any `FromStr` type, integers of any width and radix,
+ * lists, durations and sizes: see `get_as()`, `get_int_as()`, `get_list()`, `get_duration()` and `get_size()`,
+ * and the matching setters, which write values in a canonical form.
+ *
+ * `iter()` and `section()` borrow the sections and their options without cloning them, `entry()` and
+ * `retain()` edit the options of a section as those of a `HashMap`.
+ *
+ * A file may include other files with `!include path.ini` and `!includedir conf.d/` lines, relative to the
+ * including file; `!includedir` reads the `*.ini` files of the directory in name order. The options of the
+ * included files are merged as if they were written in place of the directive, each included file starting
+ * in the default section; an edited option is saved into the file defining it, see `option_path()`.
+ *
+ * Files are read as UTF-8, or UTF-16 when they start with a byte order mark or zero bytes; Latin-1 and
+ * Windows-1252 files are read with `set_encoding()`. A file is written back with its encoding, byte order
+ * mark and line endings (LF or CRLF), see `encoding()`, `has_bom()` and `line_ending()`.
+ *
+ * Values can be overridden without changing the file, see `set_override()` and the `overrides` module.
+ *
+ * Option names are normalized by `optionxform` (lowercase by default, see `set_optionxform()`) when they are
+ * read, looked up, set or removed; the file keeps the spelling the keys were first written with.
+ *
+ * The syntax is Python's by default; git-config and Windows INI files are read and written with
+ * `set_dialect()`. An option may be defined several times: get() returns the last value (the first one
+ * for Windows INI files), get_all() every value, and add() adds one.
  */
-impl<'a> IniFile<'a> {
+impl IniFile {
+    /**
+     * Add a value to the option, after its other values, as git-config multi-valued keys;
+     * see `try_add()`. If the section does not exist, panic!()
+     */
+    pub fn add(&mut self, section: &str, option: &str, value: &str) {
+        self.try_add(section, option, value)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
     /**
      * Add a section named section to the instance.
      * If a section by the given name already exists, panic!()
      */
     pub fn add_section(&mut self, section: &str) {
-        if !self.has_section(section) {
-            self.comments.insert(section.to_string(), HashMap::new());
-            self.opts.insert(section.to_string(), HashMap::new());
-            self.sections.push(section.to_string());
-            self.options.push(Vec::new());
-        } else {
-            panic!("The section {:?} already exists!", section);
+        self.try_add_section(section)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Remove every override, the values of the file become visible again.
+     */
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+    }
+    /**
+     * Getter on the name of the default section.
+     */
+    pub fn default_section(&self) -> String {
+        self.default_section.clone()
+    }
+    /**
+     * Getter on the syntax of the file.
+     */
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+    /**
+     * Text listing the value of every option, as get_raw() returns it: the default section first,
+     * then each section. Overridden values are followed by a comment telling where they come from.
+     */
+    pub fn dump(&self) -> String {
+        let mut sections = vec![self.default_section.clone()];
+        sections.extend(self.sections());
+        let mut dump = String::new();
+        for section in sections {
+            let options = self.own_options(&section);
+            if options.is_empty() && section == self.default_section {
+                continue;
+            }
+            if !dump.is_empty() {
+                dump.push('\n');
+            }
+            dump.push_str(&format!("[{}]\n", section));
+            for option in options {
+                let value = self.own_value(&section, &option).unwrap();
+                dump.push_str(&format!("{} = {}", option, value.replace('\n', "\n    ")));
+                if let Some(o) = self.find_override(&section, &option) {
+                    dump.push_str(&format!(" ; overridden by {}", o.source));
+                }
+                dump.push('\n');
+            }
         }
+        dump
+    }
+    /**
+     * Changes turning this configuration into `other`, section by section and option by option,
+     * in the order of the sections and of the options.
+     */
+    pub fn diff(&self, other: &IniFile) -> Vec<Change> {
+        diff::changes(self, other)
+    }
+    /**
+     * Encoding of the file, as detected when reading it or set by set_encoding(); UTF-8 by default.
+     */
+    pub fn encoding(&self) -> Encoding {
+        self.format.encoding
+    }
+    /**
+     * Entry of the option in the section, to read, insert or remove it in place, as `HashMap::entry()`.
+     * If the section does not exist, panic!()
+     */
+    pub fn entry(&mut self, section: &str, option: &str) -> Entry<'_> {
+        self.try_entry(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
      * Getter on filepath.
@@ -63,355 +261,1797 @@ impl<'a> IniFile<'a> {
         format!("{}", self.path.display())
     }
     /**
-     * Get an option value for the named section.
+     * Parse configuration data from a reader, such as stdin or a socket.
+     * The file path is empty.
+     */
+    pub fn from_reader<R: BufRead>(reader: R) -> IniResult<IniFile> {
+        let mut ini = IniFile::new();
+        ini.try_read_from(reader)?;
+        Ok(ini)
+    }
+    /**
+     * Get an option value for the named section, after interpolation (see `set_interpolation()`).
+     * If the section or the option does not exist, panic!()
      */
-    //pub fn get<'a>(&self, section: &'a str, option: &'a str) -> String {
     pub fn get(&self, section: &str, option: &str) -> String {
-        if !self.has_option(section, option) {
-            ()
-        }
-        self.opts[section][option].clone()
+        self.try_get(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Get every value of an option defined several times, in file order, without interpolation.
+     * If the section or the option does not exist, panic!()
+     */
+    pub fn get_all(&self, section: &str, option: &str) -> Vec<String> {
+        self.try_get_all(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Parse the option in the specified section as any `FromStr` type, after interpolation.
+     * If the section or the option does not exist, or if the value can not be parsed, panic!()
+     */
+    pub fn get_as<T: FromStr>(&self, section: &str, option: &str) -> T {
+        self.try_get_as(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
      * A convenience method which coerces the option in the specified section to a boolean.
-     * Note that the accepted values for the option are '1', 'yes', 'true', and 'on', which cause this method to return True, and '0', 'no', 'false', and 'off', which cause it to return False.
-     * @todo These string values are checked in a case-insensitive manner.
+     * Note that the accepted values for the option are '1', 'yes', 'true', 'on' and 't', which cause this method to return True, and '0', 'no', 'false', 'off' and 'f', which cause it to return False.
+     * Unlike Python's `getboolean()`, 't' and 'f' are accepted, as this crate always did.
+     * These string values are checked in a case-insensitive manner, see `set_boolean_states()` to change them.
      */
     pub fn get_bool(&self, section: &str, option: &str) -> bool {
-        let value = self.get(section, option);
-        match &*value {
-            "1" | "yes" | "true" | "T" | "on" => true,
-            "0" | "no" | "false" | "F" | "off" => false,
-            _ => panic!("{} is not a boolean.", value),
-        }
+        self.try_get_bool(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Comment of the option, or of the section header if `option` is `None`: the comment lines right above
+     * it, without their `#` or `;` markers. None if there is no such comment, or no such option or header.
+     */
+    pub fn get_comment(&self, section: &str, option: Option<&str>) -> Option<String> {
+        let option = option.map(|option| self.optionxform(option));
+        let file = self.commented_file(section, option.as_deref())?;
+        self.document(file).comment(section, option.as_deref())
     }
     /**
      * A convenience method which coerces the option in the specified section to a float f64.
      */
     pub fn get_f64(&self, section: &str, option: &str) -> f64 {
-        let value = self.get(section, option);
-        match value.trim().parse() {
-            Ok(f) => f,
-            Err(_) => panic!("{} is not a float.", value),
-        }
+        self.try_get_f64(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * A convenience method which coerces the option in the specified section to a duration,
+     * such as `30s`, `5m` or `1h30m`; see `value::parse_duration()`.
+     */
+    pub fn get_duration(&self, section: &str, option: &str) -> Duration {
+        self.try_get_duration(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Comment at the end of the file, after its last section or option.
+     */
+    pub fn get_footer_comment(&self) -> Option<String> {
+        self.document.footer_comment()
     }
     /**
-     * A convenience method which coerces the option in the specified section to an integer.
+     * Comment at the top of the file, separated by a blank line from the comment of the first section.
+     */
+    pub fn get_header_comment(&self) -> Option<String> {
+        self.document.header_comment()
+    }
+    /**
+     * Inline comment of the option, after its value, without its prefix: `default` for
+     * `port = 3306 ; default`. Only the dialects and syntaxes with inline comments read them, see
+     * `Syntax::inline_comment_prefixes()`.
+     */
+    pub fn get_inline_comment(&self, section: &str, option: &str) -> Option<String> {
+        let option = self.optionxform(option);
+        let file = self.option_file(section, &option);
+        let raw = self.document(file).inline_comment(section, &option)?;
+        let prefix = self
+            .inline_comment_prefixes()
+            .into_iter()
+            .find(|prefix| raw.starts_with(prefix))
+            .unwrap_or("");
+        Some(raw[prefix.len()..].trim().to_string())
+    }
+    /**
+     * A convenience method which coerces the option in the specified section to an integer,
+     * written in decimal, or in hexadecimal, octal or binary with a `0x`, `0o` or `0b` prefix.
      */
     pub fn get_int(&self, section: &str, option: &str) -> isize {
-        let value = self.get(section, option);
-        match value.trim().parse() {
-            Ok(f) => f,
-            Err(_) => panic!("{} is not an integer.", value),
-        }
+        self.try_get_int(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
-     * Indicates whether the given section exists and contains the given option.
+     * Coerce the option in the specified section to an integer of any width, as `get_int()` does:
+     * `ini.get_int_as::<u16>("server", "port")`.
+     */
+    pub fn get_int_as<T: Integer>(&self, section: &str, option: &str) -> T {
+        self.try_get_int_as(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Split the option in the specified section into a list, on the list delimiter (`,` unless redefined
+     * by `set_list_delimiter()`) and on new lines. Items are trimmed, empty items are ignored.
+     */
+    pub fn get_list(&self, section: &str, option: &str) -> Vec<String> {
+        self.try_get_list(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Parse the option in the specified section as `get_as()` does, or return `default` if the section or
+     * the option does not exist. If the value can not be parsed, panic!()
+     */
+    pub fn get_or<T: FromStr>(&self, section: &str, option: &str, default: T) -> T {
+        self.try_get_or(section, option, default)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Get an option value for the named section, without interpolation.
+     * If the section or the option does not exist, panic!()
+     */
+    pub fn get_raw(&self, section: &str, option: &str) -> String {
+        self.try_get_raw(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * A convenience method which coerces the option in the specified section to a number of bytes,
+     * such as `512`, `10MB` or `4KiB`; see `value::parse_size()`.
+     */
+    pub fn get_size(&self, section: &str, option: &str) -> u64 {
+        self.try_get_size(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Indicates whether the file starts with a byte order mark, written back by save().
+     */
+    pub fn has_bom(&self) -> bool {
+        self.format.bom
+    }
+    /**
+     * Indicates whether the given section exists and contains the given option,
+     * either its own or one of the default section.
      */
     pub fn has_option(&self, section: &str, option: &str) -> bool {
-        self.has_section(section) && self.opts[section].contains_key(option)
+        if section != self.default_section && !self.has_section(section) {
+            return false;
+        }
+        self.lookup_value(section, &(self.optionxform)(option))
+            .is_some()
     }
     /**
      * Indicates whether the named section is present in the configuration.
+     * The default section is not considered.
      */
     pub fn has_section(&self, section: &str) -> bool {
-        self.opts.contains_key(&section.to_string())
+        section != self.default_section
+            && (self.sections.contains_key(section)
+                || self.overrides.iter().any(|o| o.section == section))
+    }
+    /**
+     * Getter on the interpolation applied by get().
+     */
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+    /**
+     * Paths of the files read through `!include` and `!includedir`, in reading order.
+     */
+    pub fn includes(&self) -> Vec<&Path> {
+        self.includes
+            .iter()
+            .map(|included| included.path.as_path())
+            .collect()
+    }
+    /**
+     * Indicates whether the value of the option in the section is overridden.
+     */
+    pub fn is_overridden(&self, section: &str, option: &str) -> bool {
+        self.find_override(section, &(self.optionxform)(option))
+            .is_some()
+    }
+    /**
+     * Iterator over the sections, the default section excluded, as sections() lists them.
+     */
+    pub fn iter(&self) -> Sections<'_> {
+        Sections::new(self)
+    }
+    /**
+     * Line ending of the file, as detected from its first line or set by set_line_ending(); LF by default.
+     */
+    pub fn line_ending(&self) -> LineEnding {
+        self.format.line_ending
+    }
+    /**
+     * Read and parse configuration data from the file, which becomes the file path used by save().
+     */
+    pub fn load<P: AsRef<Path>>(path: P) -> IniResult<IniFile> {
+        let mut ini = IniFile::new();
+        let file = File::open(path.as_ref())?;
+        debug!("open of {:?} succeeded", path.as_ref());
+        // Included files are relative to the file.
+        ini.path = path.as_ref().to_path_buf();
+        ini.try_read_from(BufReader::new(file))?;
+        Ok(ini)
+    }
+    /**
+     * Acquire the advisory lock of the file, waiting for other processes to release it, then read the
+     * file as `load()` does. Edits saved before the lock is dropped do not interleave with those of other
+     * processes locking the file.
+     */
+    pub fn load_locked<P: AsRef<Path>>(path: P) -> IniResult<(IniFile, Lock)> {
+        let lock = Lock::acquire(path.as_ref())?;
+        let ini = IniFile::load(path)?;
+        Ok((ini, lock))
     }
-    pub fn new() -> IniFile<'a> {
+    /**
+     * Acquire the advisory lock of the file, waiting for other processes to release it.
+     * If the lock can not be acquired, panic!()
+     */
+    pub fn lock(&self) -> Lock {
+        self.try_lock().unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn new() -> IniFile {
         IniFile {
-            comments: HashMap::new(),
-            options: Vec::new(),
-            path: Path::new(""),
-            opts: HashMap::new(),
-            sections: Vec::new(),
+            backups: 0,
+            boolean_states: default_boolean_states(),
+            default_section: "DEFAULT".to_string(),
+            dialect: Dialect::Python,
+            document: Document::new("DEFAULT"),
+            format: Format::default(),
+            includes: Vec::new(),
+            interpolation: Interpolation::None,
+            list_delimiter: ',',
+            optionxform: optionxform_lower,
+            path: PathBuf::new(),
+            overrides: Vec::new(),
+            sections: OrderedMap::new(),
+            syntax: Syntax::new(),
         }
     }
     /**
-     * Return a list of options available in the specified section.
+     * Rewrite the layout of the file in a canonical form, keeping the comments and the order of the
+     * sections and of the options: `key = value`, no indentation but for continuation lines, a blank
+     * line before each section.
      */
-    pub fn options(&self, section: String) -> Vec<String> {
-        match self.sections.iter().position(|x| x == &section) {
-            //match self.sections.iter().position_elem(&section) {
-            Some(section_index) => self.options[section_index].to_vec(),
-            None => {
-                vec![]
-            }
+    pub fn normalize(&mut self) {
+        self.document.normalize();
+        for included in self.includes.iter_mut() {
+            included.document.normalize();
+            included.modified = true;
         }
     }
     /**
-     * Read and parse configuration data from filepath.
+     * Normalize an option name, as the option names of the file, see `set_optionxform()`.
      */
-    pub fn read(&mut self, filepath: &'a str) {
-        self.path = Path::new(filepath);
-        match File::open(&self.path) {
-            Err(e) => panic!("open of {:?} failed: {}", self.path, e),
-            Ok(file) => {
-                debug!("open of {:?} succeeded", self.path);
-                let reader = BufReader::new(file);
-                let mut lines: Vec<String> = Vec::new();
-                for line in reader.lines() {
-                    match line {
-                        Ok(nread) => lines.push(nread),
-                        Err(e) => println!("error reading: {}", e),
-                    }
+    pub fn optionxform(&self, option: &str) -> String {
+        (self.optionxform)(option)
+    }
+    /**
+     * Return a list of options available in the specified section:
+     * its own options, then the options of the default section it does not override.
+     */
+    pub fn options(&self, section: String) -> Vec<String> {
+        if section != self.default_section && !self.has_section(&section) {
+            return vec![];
+        }
+        let mut options = self.own_options(&section);
+        if section != self.default_section && self.dialect.default_fallback() {
+            for option in self.own_options(&self.default_section) {
+                if !options.contains(&option) {
+                    options.push(option);
                 }
-                self.read_string(lines);
             }
         }
+        options
+    }
+    /**
+     * Path of the file defining the value get_raw() returns: the file itself or an included file.
+     * `None` if the option does not exist or is overridden.
+     */
+    pub fn option_path(&self, section: &str, option: &str) -> Option<&Path> {
+        let option = (self.optionxform)(option);
+        if self.find_override(section, &option).is_some() {
+            return None;
+        }
+        let mut value = self.stored(section, &option);
+        if value.is_none() && self.dialect.default_fallback() {
+            value = self.stored(&self.default_section, &option);
+        }
+        value.map(|value| self.file_path(value.file))
+    }
+    /**
+     * Getter on the overrides, in the order they were set.
+     */
+    pub fn overrides(&self) -> &[Override] {
+        &self.overrides
+    }
+    /**
+     * Read and parse configuration data from filepath.
+     * If the file can not be read or parsed, panic!()
+     */
+    pub fn read(&mut self, filepath: &str) {
+        self.try_read(filepath).unwrap_or_else(|e| panic!("{}", e))
     }
     /**
      * Parse configuration data from a vector of strings (file lines).
+     * If a line can not be parsed, panic!()
      */
     pub fn read_string(&mut self, lines: Vec<String>) {
-        let mut section: String = "Default".to_string();
-        let mut comment_lines = String::new();
-        for line in lines.iter() {
-            let mut line_len = line.len();
-            let line_slice = line;
-            if line_len > 0 && &line_slice[line_len - 1..line_len] == "\n" {
-                line_len = line_len - 1;
-            }
-            if line_len == 0 {
-                comment_lines.push_str(line_slice);
-                comment_lines.push_str("\n");
-                continue;
-            }
-            if &line_slice[0..1] == "#" || &line_slice[0..1] == ";" {
-                comment_lines.push_str(line_slice);
-                comment_lines.push_str("\n");
-                continue;
-            }
-            if &line_slice[0..1] == "[" {
-                section = line_slice[1..line_len - 1].to_string();
-                if !self.opts.contains_key(&section) {
-                    self.add_section(&section);
-                    self.comments
-                        .get_mut(&section)
-                        .unwrap()
-                        .insert("__section_comment__".to_string(), comment_lines);
-                    comment_lines = String::new();
-                }
-                continue;
-            }
-            let index = line_slice.find("=").unwrap();
-            let optkey = line_slice[0..index].to_string();
-            let optval = line_slice[index + 1..line_len].to_string();
-            self.comments
-                .get_mut(&section)
-                .unwrap()
-                .insert(optkey.clone(), comment_lines);
-            comment_lines = String::new();
-            self.opts
-                .get_mut(&section)
-                .unwrap()
-                .insert(optkey.clone(), optval);
-            let section_index = self.sections.iter().position(|x| x == &section).unwrap();
-            self.options
-                .get_mut(section_index)
-                .unwrap()
-                .push(optkey.clone());
-        }
-    }
-    /**
-     * Remove the specified option from the specified section. If the section does not exist, fails.
-     * If the option existed to be removed, return True; otherwise return False.
+        self.try_read_string(lines)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Remove the specified option from the specified section, and its override. If the section does not
+     * exist, fails. If the option existed to be removed, return True; otherwise return False.
      */
     pub fn remove_option(&mut self, section: String, option: String) -> bool {
-        if !self.has_section(&section) {
-            panic!("Section [{:?}] does not exist!", section);
-        }
-        /*
-            if !self.has_option(section.to_string(), option.to_string()) {
-                false
-            }
-        */
-        let section_index = self.sections.iter().position(|x| x == &section).unwrap();
-        self.options
-            .get_mut(section_index)
-            .unwrap()
-            .remove(section_index);
-        self.comments.get_mut(&section).unwrap().remove(&option);
-        self.opts.get_mut(&section).unwrap().remove(&option);
-        true
+        self.try_remove_option(section, option)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
-     * Remove the specified section from the configuration.
+     * Remove the specified section from the configuration, with the overrides of its options.
      * If the section in fact existed, return True; otherwise return False.
+     * The default section can not be removed.
      */
     pub fn remove_section(&mut self, section: String) -> bool {
-        /*
-            if (!self.has_section(section.clone())) {
-                false
-            }
-        */
-        self.opts.remove(&section);
-        self.comments.remove(&section);
-        match self.sections.iter().position(|x| x == &section) {
-            Some(index) => {
-                self.sections.remove(index);
-                self.options.remove(index);
-                true
+        if !self.has_section(&section) {
+            return false;
+        }
+        self.overrides.retain(|o| o.section != section);
+        self.document.remove_section(&section);
+        for included in self.includes.iter_mut() {
+            if included.document.section_line(&section).is_some() {
+                included.document.remove_section(&section);
+                included.modified = true;
             }
-            None => false,
         }
+        self.sections.remove(&section);
+        true
     }
     /**
-     * Save the current configuration into the original file.
+     * Keep only the own options of the section for which `f(option, value)` returns true;
+     * options are given normalized, values as read or set. If the section does not exist, panic!()
+     */
+    pub fn retain<F: FnMut(&str, &str) -> bool>(&mut self, section: &str, f: F) {
+        self.try_retain(section, f)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Save the current configuration into the original file, atomically.
+     * If the file can not be written, panic!()
      */
     pub fn save(&self) {
-        self.write(&self.filepath());
+        self.try_save().unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Save the current configuration into the given file, atomically; it becomes the file path used by save().
+     */
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> IniResult<()> {
+        self.try_write_path(path.as_ref())?;
+        self.path = path.as_ref().to_path_buf();
+        Ok(())
+    }
+    /**
+     * View on the options of a section, the default section included, or `None` if it does not exist.
+     */
+    pub fn section(&self, section: &str) -> Option<Section<'_>> {
+        view::section(self, section)
     }
     /**
-     * Return a list of the available sections.
+     * Return a list of the available sections, the default section excluded.
      */
     pub fn sections(&self) -> Vec<String> {
-        /*
-        let mut sections: ~[~str] = ~[];
-        self.opts.iter().advance(|(k, _)| {sections.push(k.to_string()); true});
+        let mut sections: Vec<String> = self
+            .sections
+            .keys()
+            .filter(|section| *section != self.default_section)
+            .map(str::to_string)
+            .collect();
+        for o in self.overrides.iter() {
+            if o.section != self.default_section && !sections.contains(&o.section) {
+                sections.push(o.section.clone());
+            }
+        }
         sections
-        */
-        self.sections.clone()
     }
     /**
-     * If the given section exists, set the given option to the specified value; otherwise panic!().
+     * If the given section exists or is the default section, set the given option to the specified value;
+     * otherwise panic!().
      */
     pub fn set(&mut self, section: String, option: String, value: String) {
-        if !self.has_section(&section) {
-            panic!("Section [{:?}] does not exist!", section);
-        }
-        if !self.has_option(&section, &option) {
-            self.opts
-                .get_mut(&section)
-                .unwrap()
-                .insert(option.clone(), value);
-            let section_index = self.sections.iter().position(|x| x == &section).unwrap();
-            self.options
-                .get_mut(section_index)
-                .unwrap()
-                .push(option.clone());
-        } else {
-            self.opts.get_mut(&section).unwrap().insert(option, value);
-        }
+        self.try_set(section, option, value)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
-     * Redefine file path.
+     * Set the option to the text of a value, such as a number or a boolean.
+     * If the section does not exist, panic!()
      */
-    pub fn set_path(&mut self, filepath: &'a Path) {
-        self.path = filepath;
+    pub fn set_as<T: fmt::Display>(&mut self, section: &str, option: &str, value: T) {
+        self.try_set_as(section, option, value)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
     /**
-     * Write a representation of the configuration to the specified file path.
-     * This representation can be parsed by a future read() call.
+     * Define whether the file is written with a byte order mark (UTF-8 and UTF-16 only).
      */
-    pub fn write(&self, filepath: &str) {
-        // http://doc.rust-lang.org/std/fs/struct.File.html
-        match File::create(&Path::new(filepath)) {
-            Ok(mut file) => match file.write_all(self.to_string().as_bytes()) {
-                Ok(()) => debug!("INI file {:?} written", self.path),
-                Err(e) => println!("failed to write to {:?}: {}", self.path, e),
-            },
-            Err(e) => println!("failed to create {:?}: {}", self.path, e),
-        }
+    pub fn set_bom(&mut self, bom: bool) {
+        self.format.bom = bom;
     }
-}
-
-/**
- * Formatting trait <https://doc.rust-lang.org/std/fmt/>.
- * Operator overloading
- * @see http://maniagnosis.crsr.net/2013/04/operator-overloading-in-rust.html
- */
-impl<'a> fmt::Display for IniFile<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut lines = String::new();
-        let sections = self.sections().clone();
-        for section in sections.iter() {
-            if self.comments.contains_key(section)
-                && self.comments[section].contains_key(&"__section_comment__".to_string())
-            {
-                lines.push_str(&self.comments[section]["__section_comment__"]);
-            }
-            let line = format!("[{}]\n", section);
-            lines.push_str(&line);
-            let options = self.options(section.clone()).clone();
-            for key in options.iter() {
-                if self.comments.contains_key(section) && self.comments[section].contains_key(key) {
-                    lines.push_str(&self.comments[section][key]);
-                }
-                lines.push_str(&format!("{}={}\n", key.to_string(), self.get(section, key)));
-            }
-        }
-        write!(f, "{}", lines)
+    /**
+     * Define the number of backups kept when saving: the former content of `config.ini` is copied to
+     * `config.ini.bak`, the previous backups to `config.ini.bak.1`, `config.ini.bak.2`... Default is 0.
+     */
+    pub fn set_backups(&mut self, count: usize) {
+        self.backups = count;
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-    use std::fs;
-    use std::fs::File;
-    use std::io::prelude::*;
-    use std::io::BufReader;
-    use std::path::Path;
-
-    static FILEPATH: &'static str = "src/data/config.ini";
-
     /**
-     * Path::exists() is unstable in Rust-1.3.0.
+     * Redefine the words recognized by get_bool(), compared in a case-insensitive manner.
+     * Default is '1', 'yes', 'true', 'on', 't' for true and '0', 'no', 'false', 'off', 'f' for false.
      */
-    fn exists(path: &Path) -> bool {
-        match fs::metadata(path) {
-            Ok(_) => true,
-            Err(_) => false,
+    pub fn set_boolean_states(&mut self, states: HashMap<String, bool>) {
+        self.boolean_states = states
+            .into_iter()
+            .map(|(word, state)| (word.to_lowercase(), state))
+            .collect();
+    }
+    /**
+     * Write a comment above the option, or above the section header if `option` is `None`, replacing its
+     * comment: one comment line per line of `comment`, with the marker of the former comment, or of the
+     * other comments of the file, `#` or `;`. An empty comment removes it.
+     * If the option or the section header does not exist, panic!()
+     */
+    pub fn set_comment(&mut self, section: &str, option: Option<&str>, comment: &str) {
+        self.try_set_comment(section, option, comment)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Redefine the name of the default section, to call before reading.
+     */
+    pub fn set_default_section(&mut self, section: &str) {
+        self.default_section = section.to_string();
+        self.document.set_default_section(section);
+        for included in self.includes.iter_mut() {
+            included.document.set_default_section(section);
         }
     }
-
-    #[test]
-    fn default_filepath_is_empty() {
-        let ini = super::IniFile::new();
-        let expected = "".to_string();
-        let found = ini.filepath();
-        assert!(
-            expected == found,
-            format!(
-                "Default file path must be \"{}\", not \"{}\".",
-                expected, found
-            )
-        );
+    /**
+     * Redefine the syntax of the file, to call before reading. Default is `Dialect::Python`.
+     */
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
     }
-    #[test]
-    fn filepath() {
-        let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        let expected = FILEPATH.to_string();
-        let found = ini.filepath();
-        assert!(
-            expected == found,
-            format!(
-                "Default file path must be \"{}\", not \"{}\".",
-                expected, found
-            )
-        );
+    /**
+     * Define the encoding of the files read without byte order mark, and of the file written.
+     * Called before reading, it is needed for Latin-1 and Windows-1252 files; called after reading, it
+     * converts the file when it is saved.
+     */
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.format.encoding = encoding;
     }
-    #[test]
-    fn sections_length() {
-        let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        let expected = 5;
-        let sections = ini.sections();
-        let found = sections.len();
-        assert!(
-            expected == found,
-            format!("{} sections are expected, not {}.", expected, found)
-        );
+    /**
+     * Set the option to a duration, written as `1h30m`. If the section does not exist, panic!()
+     */
+    pub fn set_duration(&mut self, section: &str, option: &str, value: Duration) {
+        self.try_set_duration(section, option, value)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
-    #[test]
-    fn sections_names() {
-        let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        let expected = vec![
-            "section1".to_string(),
-            "section2".to_string(),
-            "Booleans".to_string(),
+    /**
+     * Write a comment at the end of the file, replacing its footer comment; an empty comment removes it.
+     */
+    pub fn set_footer_comment(&mut self, comment: &str) {
+        let marker = self.dialect.comment_marker();
+        self.document.set_footer_comment(comment, marker);
+    }
+    /**
+     * Write a comment at the top of the file, followed by a blank line, replacing its header comment;
+     * an empty comment removes it.
+     */
+    pub fn set_header_comment(&mut self, comment: &str) {
+        let marker = self.dialect.comment_marker();
+        self.document.set_header_comment(comment, marker);
+    }
+    /**
+     * Write a comment after the value of the option, replacing its inline comment; an empty comment
+     * removes it. The comment keeps the prefix of the former one, or uses the first prefix of the syntax.
+     * If the option does not exist, if the comment has several lines, or if inline comments would not be
+     * read back, see `Syntax::inline_comment_prefixes()`, panic!()
+     */
+    pub fn set_inline_comment(&mut self, section: &str, option: &str, comment: &str) {
+        self.try_set_inline_comment(section, option, comment)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Define the interpolation applied by get() and the typed getters.
+     * Default is `Interpolation::None`: values are returned as written.
+     */
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+    /**
+     * Define the line ending of the file written, LF or CRLF: every line ends with it, the lines already
+     * read included.
+     */
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.format.line_ending = line_ending;
+        self.document.set_line_ending(line_ending);
+    }
+    /**
+     * Set the option to a list, its items separated by the list delimiter and a space.
+     * If the section does not exist, panic!()
+     */
+    pub fn set_list<S: AsRef<str>>(&mut self, section: &str, option: &str, items: &[S]) {
+        self.try_set_list(section, option, items)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Redefine the separator of the items of the lists, see `get_list()`. Default is `,`.
+     */
+    pub fn set_list_delimiter(&mut self, delimiter: char) {
+        self.list_delimiter = delimiter;
+    }
+    /**
+     * Redefine the normalization of option names, to call before reading.
+     * Default is `optionxform_lower`, `optionxform_preserve` makes option names case-sensitive.
+     */
+    pub fn set_optionxform(&mut self, optionxform: fn(&str) -> String) {
+        self.optionxform = optionxform;
+    }
+    /**
+     * Override the value of an option, without changing the file: getters return the new value,
+     * save() writes the value of the file. The section does not need to exist.
+     * `source` tells where the override comes from, see `dump()`.
+     */
+    pub fn set_override(&mut self, section: &str, option: &str, value: &str, source: &str) {
+        let option = (self.optionxform)(option);
+        self.overrides
+            .retain(|o| !(o.section == section && o.option == option));
+        self.overrides.push(Override {
+            section: section.to_string(),
+            option,
+            value: value.to_string(),
+            source: source.to_string(),
+        });
+    }
+    /**
+     * Redefine file path.
+     */
+    pub fn set_path(&mut self, filepath: &Path) {
+        self.path = filepath.to_path_buf();
+    }
+    /**
+     * Set the option to a number of bytes, written with the largest unit dividing it, as `4KiB`.
+     * If the section does not exist, panic!()
+     */
+    pub fn set_size(&mut self, section: &str, option: &str, bytes: u64) {
+        self.try_set_size(section, option, bytes)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Redefine the parser options, to call before reading; new options are written with the first
+     * delimiter. Default is `Syntax::new()`: `=` delimiter, duplicates allowed.
+     */
+    pub fn set_syntax(&mut self, syntax: Syntax) {
+        self.document.set_delimiter(syntax.delimiter());
+        for included in self.includes.iter_mut() {
+            included.document.set_delimiter(syntax.delimiter());
+        }
+        self.syntax = syntax;
+    }
+    /**
+     * Getter on the parser options.
+     */
+    pub fn syntax(&self) -> &Syntax {
+        &self.syntax
+    }
+    /**
+     * Add a value to the option, on a new line after its last definition; get() returns the new value,
+     * but for Windows INI files, where the first definition wins. If the option does not exist, set it.
+     * If the section does not exist, return `IniError::MissingSection`.
+     */
+    pub fn try_add(&mut self, section: &str, option: &str, value: &str) -> IniResult<()> {
+        let key = (self.optionxform)(option);
+        if self.stored_value(section, &key).is_none() {
+            return self.try_set(section.to_string(), option.to_string(), value.to_string());
+        }
+        let file = self.option_file(section, &key);
+        let raw = self.dialect.encode(value);
+        self.document_mut(file)
+            .add_value(section, &key, option, &raw);
+        if !self.dialect.first_wins() {
+            self.store(section, &key, value.to_string(), file);
+        }
+        Ok(())
+    }
+    /**
+     * Add a section named section to the instance.
+     * If a section by the given name already exists, or if it is the default section,
+     * return `IniError::DuplicateSection`.
+     */
+    pub fn try_add_section(&mut self, section: &str) -> IniResult<()> {
+        if self.has_section(section) || section == self.default_section {
+            return Err(IniError::DuplicateSection(section.to_string()));
+        }
+        self.create_section(section);
+        self.document
+            .add_section(section, &self.dialect.header(section));
+        Ok(())
+    }
+    /**
+     * Entry of the option in the section, see `entry()`.
+     * If the section does not exist, return `IniError::MissingSection`.
+     */
+    pub fn try_entry(&mut self, section: &str, option: &str) -> IniResult<Entry<'_>> {
+        if section != self.default_section && !self.has_section(section) {
+            return Err(IniError::MissingSection(section.to_string()));
+        }
+        Ok(Entry::new(self, section, option))
+    }
+    /**
+     * Get an option value for the named section, after interpolation.
+     */
+    pub fn try_get(&self, section: &str, option: &str) -> IniResult<String> {
+        let raw = self.try_get_raw(section, option)?;
+        if self.interpolation == Interpolation::None {
+            return Ok(raw);
+        }
+        let lookup = |section: &str, option: &str| {
+            self.lookup_value(section, &(self.optionxform)(option))
+                .cloned()
+        };
+        interpolation::interpolate(
+            self.interpolation,
+            &lookup,
+            section,
+            option,
+            &raw,
+            MAX_INTERPOLATION_DEPTH,
+        )
+        .map_err(|cause| IniError::Interpolation {
+            section: section.to_string(),
+            option: option.to_string(),
+            cause,
+        })
+    }
+    /**
+     * Get an option value for the named section, without interpolation.
+     */
+    pub fn try_get_raw(&self, section: &str, option: &str) -> IniResult<String> {
+        if section != self.default_section && !self.has_section(section) {
+            return Err(IniError::MissingSection(section.to_string()));
+        }
+        match self.lookup_value(section, &(self.optionxform)(option)) {
+            Some(value) => Ok(value.clone()),
+            None => Err(IniError::MissingOption {
+                section: section.to_string(),
+                option: option.to_string(),
+            }),
+        }
+    }
+    /**
+     * Get every value of an option defined several times, in file order, without interpolation.
+     * An overridden option has a single value; a git-config key without value is `true`, and empty in
+     * other dialects.
+     */
+    pub fn try_get_all(&self, section: &str, option: &str) -> IniResult<Vec<String>> {
+        let value = self.try_get_raw(section, option)?;
+        let key = (self.optionxform)(option);
+        if self.find_override(section, &key).is_some() {
+            return Ok(vec![value]);
+        }
+        let (owner, file) = match self.stored(section, &key) {
+            Some(own) => (section, own.file),
+            None => match self.stored(&self.default_section, &key) {
+                Some(default) => (self.default_section.as_str(), default.file),
+                None => return Ok(vec![value]),
+            },
+        };
+        Ok(self
+            .document(file)
+            .values(owner, &key)
+            .into_iter()
+            .map(|raw| match raw {
+                // The values were checked when they were read or set.
+                Some(raw) => self.dialect.decode(&raw).unwrap_or(raw),
+                None => self.dialect.implicit_value().to_string(),
+            })
+            .collect())
+    }
+    /**
+     * Parse the option in the specified section as any `FromStr` type, after interpolation.
+     */
+    pub fn try_get_as<T: FromStr>(&self, section: &str, option: &str) -> IniResult<T> {
+        let value = self.try_get(section, option)?;
+        match value.trim().parse() {
+            Ok(parsed) => Ok(parsed),
+            Err(_) => Err(type_mismatch(
+                section,
+                option,
+                value,
+                std::any::type_name::<T>(),
+            )),
+        }
+    }
+    /**
+     * Coerce the option in the specified section to a boolean, see `get_bool()`.
+     */
+    pub fn try_get_bool(&self, section: &str, option: &str) -> IniResult<bool> {
+        let value = self.try_get(section, option)?;
+        match self.parse_bool(&value) {
+            Some(b) => Ok(b),
+            None => Err(type_mismatch(section, option, value, "a boolean")),
+        }
+    }
+    /**
+     * Coerce the option in the specified section to a float f64.
+     */
+    pub fn try_get_f64(&self, section: &str, option: &str) -> IniResult<f64> {
+        let value = self.try_get(section, option)?;
+        match value.trim().parse() {
+            Ok(f) => Ok(f),
+            Err(_) => Err(type_mismatch(section, option, value, "a float")),
+        }
+    }
+    /**
+     * Coerce the option in the specified section to a duration, see `get_duration()`.
+     */
+    pub fn try_get_duration(&self, section: &str, option: &str) -> IniResult<Duration> {
+        let value = self.try_get(section, option)?;
+        match value::parse_duration(&value) {
+            Some(duration) => Ok(duration),
+            None => Err(type_mismatch(section, option, value, "a duration")),
+        }
+    }
+    /**
+     * Coerce the option in the specified section to an integer, see `get_int()`.
+     */
+    pub fn try_get_int(&self, section: &str, option: &str) -> IniResult<isize> {
+        self.try_get_int_as(section, option)
+    }
+    /**
+     * Coerce the option in the specified section to an integer of any width, see `get_int_as()`.
+     */
+    pub fn try_get_int_as<T: Integer>(&self, section: &str, option: &str) -> IniResult<T> {
+        let value = self.try_get(section, option)?;
+        match value::parse_int(&value) {
+            Some(i) => Ok(i),
+            None => Err(type_mismatch(section, option, value, "an integer")),
+        }
+    }
+    /**
+     * Split the option in the specified section into a list, see `get_list()`.
+     */
+    pub fn try_get_list(&self, section: &str, option: &str) -> IniResult<Vec<String>> {
+        let value = self.try_get(section, option)?;
+        Ok(self
+            .split_list(&value)
+            .into_iter()
+            .map(str::to_string)
+            .collect())
+    }
+    /**
+     * Parse the option in the specified section as `get_as()` does, or return `default` if the section or
+     * the option does not exist.
+     */
+    pub fn try_get_or<T: FromStr>(&self, section: &str, option: &str, default: T) -> IniResult<T> {
+        match self.try_get_as(section, option) {
+            Err(IniError::MissingSection(_)) | Err(IniError::MissingOption { .. }) => Ok(default),
+            result => result,
+        }
+    }
+    /**
+     * Coerce the option in the specified section to a number of bytes, see `get_size()`.
+     */
+    pub fn try_get_size(&self, section: &str, option: &str) -> IniResult<u64> {
+        let value = self.try_get(section, option)?;
+        match value::parse_size(&value) {
+            Some(bytes) => Ok(bytes),
+            None => Err(type_mismatch(section, option, value, "a size")),
+        }
+    }
+    /**
+     * Read and parse configuration data from filepath.
+     */
+    pub fn try_read(&mut self, filepath: &str) -> IniResult<()> {
+        self.path = PathBuf::from(filepath);
+        let file = File::open(&self.path)?;
+        debug!("open of {:?} succeeded", self.path);
+        self.try_read_from(BufReader::new(file))
+    }
+    /**
+     * Parse configuration data from a reader, such as stdin or a socket.
+     */
+    pub fn try_read_from<R: BufRead>(&mut self, mut reader: R) -> IniResult<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (text, format) =
+            encoding::decode(&bytes, self.format.encoding).map_err(IniError::Encoding)?;
+        self.format = format;
+        self.read_file(&text)
+    }
+    /**
+     * Parse configuration data from a vector of strings (file lines), according to the dialect.
+     * Options before the first section header belong to the default section.
+     * Lines indented deeper than the key of the previous option continue its value:
+     * the value is the lines joined with `\n`, blank lines between them included.
+     * In git-config files, a value ending with a backslash continues on the next line.
+     * On error, the options parsed before the faulty line are kept.
+     */
+    pub fn try_read_string(&mut self, lines: Vec<String>) -> IniResult<()> {
+        let mut text = String::new();
+        for line in lines.iter() {
+            text.push_str(line.trim_end_matches('\n'));
+            text.push('\n');
+        }
+        self.read_file(&text)
+    }
+    /**
+     * Remove the specified option from the specified section, and its override.
+     * If the section does not exist, return `IniError::MissingSection`.
+     * Only the own options of the section are removed, the default value becomes visible again.
+     */
+    pub fn try_remove_option(&mut self, section: String, option: String) -> IniResult<bool> {
+        if section != self.default_section && !self.has_section(&section) {
+            return Err(IniError::MissingSection(section));
+        }
+        let option = (self.optionxform)(&option);
+        let overrides = self.overrides.len();
+        self.overrides
+            .retain(|o| !(o.section == section && o.option == option));
+        let overridden = self.overrides.len() < overrides;
+        let removed = self
+            .sections
+            .get_mut(&section)
+            .and_then(|options| options.remove(&option));
+        match removed {
+            Some(value) => {
+                self.document_mut(value.file)
+                    .remove_option(&section, &option);
+                Ok(true)
+            }
+            None => Ok(overridden),
+        }
+    }
+    /**
+     * Keep only the own options of the section for which `f(option, value)` returns true, see `retain()`.
+     * If the section does not exist, return `IniError::MissingSection`.
+     */
+    pub fn try_retain<F: FnMut(&str, &str) -> bool>(
+        &mut self,
+        section: &str,
+        mut f: F,
+    ) -> IniResult<()> {
+        if section != self.default_section && !self.has_section(section) {
+            return Err(IniError::MissingSection(section.to_string()));
+        }
+        let removed: Vec<String> = match self.sections.get(section) {
+            Some(options) => options
+                .iter()
+                .filter(|&(option, value)| !f(option, &value.text))
+                .map(|(option, _)| option.to_string())
+                .collect(),
+            None => return Ok(()),
+        };
+        for option in removed {
+            self.try_remove_option(section.to_string(), option)?;
+        }
+        Ok(())
+    }
+    /**
+     * Acquire the advisory lock of the file, waiting for other processes to release it; the lock is
+     * released when dropped. The lock is held on `config.ini.lock`, next to the file. The configuration
+     * may be outdated if the file was read before the lock was acquired: to edit the file without
+     * interleaving with other processes, read it with `load_locked()`.
+     */
+    pub fn try_lock(&self) -> IniResult<Lock> {
+        if self.path.as_os_str().is_empty() {
+            return Err(IniError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the configuration has no file path",
+            )));
+        }
+        Ok(Lock::acquire(&self.path)?)
+    }
+    /**
+     * Save the current configuration into the original file, atomically.
+     */
+    pub fn try_save(&self) -> IniResult<()> {
+        self.try_write(&self.filepath())
+    }
+    /**
+     * If the given section exists or is the default section, set the given option to the specified value;
+     * otherwise return `IniError::MissingSection`.
+     */
+    pub fn try_set(&mut self, section: String, option: String, value: String) -> IniResult<()> {
+        if section != self.default_section && !self.has_section(&section) {
+            return Err(IniError::MissingSection(section));
+        }
+        if !self.sections.contains_key(&section) {
+            // The default section, or a section which only has overrides
+            self.create_section(&section);
+            if section != self.default_section {
+                self.document
+                    .add_section(&section, &self.dialect.header(&section));
+            }
+        }
+        let spelling = option;
+        let option = (self.optionxform)(&spelling);
+        let file = self.option_file(&section, &option);
+        let raw = self.dialect.encode(&value);
+        self.document_mut(file)
+            .set_value(&section, &option, &spelling, &raw);
+        self.store(&section, &option, value, file);
+        Ok(())
+    }
+    /**
+     * Set the option to the text of a value, see `set_as()`.
+     */
+    pub fn try_set_as<T: fmt::Display>(
+        &mut self,
+        section: &str,
+        option: &str,
+        value: T,
+    ) -> IniResult<()> {
+        self.try_set(section.to_string(), option.to_string(), value.to_string())
+    }
+    /**
+     * Write a comment above the option or the section header, see `set_comment()`.
+     */
+    pub fn try_set_comment(
+        &mut self,
+        section: &str,
+        option: Option<&str>,
+        comment: &str,
+    ) -> IniResult<()> {
+        let normalized = option.map(|option| self.optionxform(option));
+        let file = match self.commented_file(section, normalized.as_deref()) {
+            Some(file) => file,
+            None => return Err(self.missing(section, option)),
+        };
+        let marker = self.dialect.comment_marker();
+        self.document_mut(file)
+            .set_comment(section, normalized.as_deref(), comment, marker);
+        Ok(())
+    }
+    /**
+     * Set the option to a duration, see `set_duration()`.
+     */
+    pub fn try_set_duration(
+        &mut self,
+        section: &str,
+        option: &str,
+        value: Duration,
+    ) -> IniResult<()> {
+        self.try_set_as(section, option, value::format_duration(value))
+    }
+    /**
+     * Write a comment after the value of the option, see `set_inline_comment()`.
+     */
+    pub fn try_set_inline_comment(
+        &mut self,
+        section: &str,
+        option: &str,
+        comment: &str,
+    ) -> IniResult<()> {
+        if comment.contains('\n') {
+            return Err(IniError::InvalidComment(
+                "an inline comment has a single line".to_string(),
+            ));
+        }
+        let normalized = self.optionxform(option);
+        let file = match self.commented_file(section, Some(&normalized)) {
+            Some(file) => file,
+            None => return Err(self.missing(section, Some(option))),
+        };
+        let mut raw = String::new();
+        if !comment.is_empty() {
+            let prefixes = self.inline_comment_prefixes();
+            let current = self.document(file).inline_comment(section, &normalized);
+            let prefix = current
+                .and_then(|raw| prefixes.iter().find(|prefix| raw.starts_with(**prefix)))
+                .or_else(|| prefixes.first())
+                .ok_or_else(|| {
+                    IniError::InvalidComment(
+                        "inline comments are not read without Syntax::inline_comment_prefixes()"
+                            .to_string(),
+                    )
+                })?;
+            raw = format!("{} {}", prefix, comment);
+        }
+        self.document_mut(file)
+            .set_inline_comment(section, &normalized, &raw);
+        Ok(())
+    }
+    /**
+     * Set the option to a list, see `set_list()`.
+     */
+    pub fn try_set_list<S: AsRef<str>>(
+        &mut self,
+        section: &str,
+        option: &str,
+        items: &[S],
+    ) -> IniResult<()> {
+        let separator = format!("{} ", self.list_delimiter);
+        let items: Vec<&str> = items.iter().map(|item| item.as_ref()).collect();
+        self.try_set_as(section, option, items.join(&separator))
+    }
+    /**
+     * Set the option to a number of bytes, see `set_size()`.
+     */
+    pub fn try_set_size(&mut self, section: &str, option: &str, bytes: u64) -> IniResult<()> {
+        self.try_set_as(section, option, value::format_size(bytes))
+    }
+    /**
+     * Write a representation of the configuration to the specified file path, atomically: the file is
+     * replaced once the new content is synced to disk, or left unchanged on error.
+     */
+    pub fn try_write(&self, filepath: &str) -> IniResult<()> {
+        self.try_write_path(Path::new(filepath))
+    }
+    /**
+     * Check the configuration against the schema and return the report, warnings included.
+     * If the configuration has errors, return `IniError::Validation` with the report.
+     */
+    pub fn try_validate(&self, schema: &Schema) -> IniResult<ValidationReport> {
+        let report = schema.validate(self);
+        if report.is_valid() {
+            Ok(report)
+        } else {
+            Err(IniError::Validation(report))
+        }
+    }
+    /**
+     * Check the configuration against the schema and return the report, warnings included.
+     * If the configuration has errors, panic!() with the report, to fail before using it.
+     */
+    pub fn validate(&self, schema: &Schema) -> ValidationReport {
+        self.try_validate(schema)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    /**
+     * Write a representation of the configuration to the specified file path, atomically.
+     * This representation can be parsed by a future read() call.
+     * If the file can not be written, panic!()
+     */
+    pub fn write(&self, filepath: &str) {
+        self.try_write(filepath)
+            .unwrap_or_else(|e| panic!("failed to write to {:?}: {}", filepath, e))
+    }
+    /**
+     * Write a representation of the configuration to a writer, such as stdout or a socket,
+     * in the encoding and with the line endings of the file.
+     */
+    pub fn write_to<W: Write>(&self, mut writer: W) -> IniResult<()> {
+        writer.write_all(&self.to_bytes()?)?;
+        writer.flush()?;
+        Ok(())
+    }
+    /**
+     * File whose lines define the option, or the first header of the section if `option` is `None`;
+     * None if no line does. The option name must be normalized.
+     */
+    fn commented_file(&self, section: &str, option: Option<&str>) -> Option<usize> {
+        match option {
+            Some(option) => {
+                let file = self.option_file(section, option);
+                self.document(file)
+                    .option_line(section, option)
+                    .map(|_| file)
+            }
+            None => (0..=self.includes.len())
+                .find(|&file| self.document(file).section_line(section).is_some()),
+        }
+    }
+    /**
+     * Create the structures of a section, without any check.
+     */
+    fn create_section(&mut self, section: &str) {
+        self.sections.insert(section.to_string(), OrderedMap::new());
+    }
+    /**
+     * Layout of the file `file`: 0 for the file itself, `n` for the `n`-th included file.
+     */
+    fn document(&self, file: usize) -> &Document {
+        match file {
+            0 => &self.document,
+            n => &self.includes[n - 1].document,
+        }
+    }
+    /**
+     * Layout of the file `file`, to edit it: the file will be saved.
+     */
+    fn document_mut(&mut self, file: usize) -> &mut Document {
+        match file {
+            0 => &mut self.document,
+            n => {
+                let included = &mut self.includes[n - 1];
+                included.modified = true;
+                &mut included.document
+            }
+        }
+    }
+    /**
+     * Layout of the file `file` being read.
+     */
+    fn file_document(&mut self, file: usize) -> &mut Document {
+        match file {
+            0 => &mut self.document,
+            n => &mut self.includes[n - 1].document,
+        }
+    }
+    /**
+     * Option names of the section as read or set, without the overrides and the default section.
+     */
+    fn file_options(&self, section: &str) -> Vec<String> {
+        match self.sections.get(section) {
+            Some(options) => options.keys().map(str::to_string).collect(),
+            None => vec![],
+        }
+    }
+    /**
+     * Path of the file `file`, see `document()`.
+     */
+    fn file_path(&self, file: usize) -> &Path {
+        match file {
+            0 => &self.path,
+            n => &self.includes[n - 1].path,
+        }
+    }
+    /**
+     * Sections as read or added, without the default section and the sections which only have overrides.
+     */
+    fn file_sections(&self) -> Vec<String> {
+        self.sections
+            .keys()
+            .filter(|section| *section != self.default_section)
+            .map(str::to_string)
+            .collect()
+    }
+    /**
+     * Override of the option in the section; the option name must be normalized.
+     */
+    fn find_override(&self, section: &str, option: &str) -> Option<&Override> {
+        self.overrides
+            .iter()
+            .find(|o| o.section == section && o.option == option)
+    }
+    /**
+     * Read a file included by a directive; `chain` holds the files being read, to detect cycles.
+     */
+    fn include(&mut self, path: PathBuf, chain: &mut Vec<PathBuf>) -> IniResult<()> {
+        let failed = |path: &Path, cause: IniError| IniError::Include {
+            path: path.to_path_buf(),
+            cause: Box::new(cause),
+        };
+        let canonical = fs::canonicalize(&path).map_err(|e| failed(&path, e.into()))?;
+        if let Some(start) = chain.iter().position(|file| *file == canonical) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(canonical);
+            return Err(IniError::IncludeCycle(cycle));
+        }
+        let bytes = fs::read(&path).map_err(|e| failed(&path, e.into()))?;
+        // A byte order mark is needed to read a file as UTF-16, not to read it as Latin-1.
+        let encoding = match self.format.encoding {
+            Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+            encoding => encoding,
+        };
+        let (text, format) = encoding::decode(&bytes, encoding)
+            .map_err(|message| failed(&path, IniError::Encoding(message)))?;
+        debug!("INI file {:?} included", path);
+        let mut document = Document::new(&self.default_section);
+        document.set_delimiter(self.syntax.delimiter());
+        document.set_line_ending(format.line_ending);
+        self.includes.push(Included {
+            document,
+            format,
+            modified: false,
+            path: path.clone(),
+        });
+        let file = self.includes.len();
+        chain.push(canonical);
+        let result = self.read_text(&text, file, chain);
+        chain.pop();
+        result.map_err(|e| match e {
+            IniError::IncludeCycle(_) => e,
+            e => failed(&path, e),
+        })
+    }
+    /**
+     * The error, the location of its diagnostic being in the file `file`.
+     */
+    fn in_file(&self, e: IniError, file: usize) -> IniError {
+        match e {
+            IniError::Syntax(mut diagnostic) => {
+                if let Some(ref mut location) = diagnostic.location {
+                    location.path = self.file_path(file).to_path_buf();
+                }
+                IniError::Syntax(diagnostic)
+            }
+            e => e,
+        }
+    }
+    /**
+     * Prefixes of the inline comments, as read by the syntax and the dialect.
+     */
+    fn inline_comment_prefixes(&self) -> Vec<&str> {
+        let mut prefixes: Vec<&str> = self
+            .syntax
+            .comment_prefixes()
+            .iter()
+            .map(String::as_str)
+            .collect();
+        if self.dialect.inline_comments() {
+            prefixes.extend(["#", ";"].iter());
+        }
+        prefixes
+    }
+    /**
+     * Location of the option in the file defining it, of its value if `value` (see
+     * `Document::option_span()`); or of the section header if `option` is `None`.
+     */
+    pub(crate) fn location(
+        &self,
+        section: &str,
+        option: Option<&str>,
+        value: bool,
+    ) -> Option<Location> {
+        let (file, span) = match option {
+            Some(option) => {
+                let file = self.option_file(section, option);
+                (
+                    file,
+                    self.document(file).option_span(section, option, value)?,
+                )
+            }
+            None => (0..=self.includes.len()).find_map(|file| {
+                let span = self.document(file).section_span(section)?;
+                Some((file, span))
+            })?,
+        };
+        let mut location = Location::in_text(&self.document(file).to_string(), span);
+        location.path = self.file_path(file).to_path_buf();
+        Some(location)
+    }
+    /**
+     * Value of the option in the section, overridden or not, or else the value of the default section
+     * if the dialect has one. The option name must be normalized.
+     */
+    fn lookup_value(&self, section: &str, option: &str) -> Option<&String> {
+        self.own_value(section, option).or_else(|| {
+            if self.dialect.default_fallback() {
+                self.own_value(&self.default_section, option)
+            } else {
+                None
+            }
+        })
+    }
+    /**
+     * Error of a comment written above or after an option or section header which does not exist.
+     */
+    fn missing(&self, section: &str, option: Option<&str>) -> IniError {
+        match option {
+            Some(option) if section == self.default_section || self.has_section(section) => {
+                IniError::MissingOption {
+                    section: section.to_string(),
+                    option: option.to_string(),
+                }
+            }
+            _ => IniError::MissingSection(section.to_string()),
+        }
+    }
+    /**
+     * File in which the option is set: the file defining it, or else the file of the last option of
+     * the section, or else the first file with the section header, the file itself by default.
+     * The option name must be normalized.
+     */
+    fn option_file(&self, section: &str, option: &str) -> usize {
+        let options = match self.sections.get(section) {
+            Some(options) => options,
+            None => return 0,
+        };
+        if let Some(value) = options.get(option) {
+            return value.file;
+        }
+        if let Some((_, last)) = options.iter().last() {
+            return last.file;
+        }
+        (0..=self.includes.len())
+            .find(|&file| self.document(file).section_line(section).is_some())
+            .unwrap_or(0)
+    }
+    /**
+     * Option names of the section, without those of the default section.
+     * Overridden options missing from the file come last.
+     */
+    fn own_options(&self, section: &str) -> Vec<String> {
+        let mut options = self.file_options(section);
+        for o in self.overrides.iter() {
+            if o.section == section && !options.contains(&o.option) {
+                options.push(o.option.clone());
+            }
+        }
+        options
+    }
+    /**
+     * Value of the option in the section, overridden or not, without looking into the default section.
+     * The option name must be normalized.
+     */
+    fn own_value(&self, section: &str, option: &str) -> Option<&String> {
+        match self.find_override(section, option) {
+            Some(o) => Some(&o.value),
+            None => self.stored_value(section, option),
+        }
+    }
+    /**
+     * Parse configuration data from the text of the file itself, see `try_read_string()`.
+     */
+    fn read_file(&mut self, text: &str) -> IniResult<()> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        self.document.set_line_ending(self.format.line_ending);
+        let mut chain: Vec<PathBuf> = fs::canonicalize(&self.path).into_iter().collect();
+        self.read_text(text, 0, &mut chain)
+    }
+    /**
+     * Parse configuration data from the text of the file `file`, see `document()`;
+     * `chain` holds the files being read.
+     */
+    fn read_text(&mut self, text: &str, file: usize, chain: &mut Vec<PathBuf>) -> IniResult<()> {
+        let dialect = self.dialect;
+        let mut section = self.default_section.clone();
+        // Key of the last option, which may be continued
+        let mut current: Option<String> = None;
+        // Whether the last option has continuation lines not stored yet
+        let mut unstored = false;
+        // Span of the first line of the value continued by a trailing backslash
+        let mut continued: Option<Range<usize>> = None;
+        // Blank and comment lines which may belong to a multi-line value
+        let mut skipped_lines: Vec<String> = Vec::new();
+        // Line numbers of the sections and options defined in the file, for the strict syntax
+        let mut defined_sections: HashMap<String, usize> = HashMap::new();
+        let mut defined_options: HashMap<(String, String), usize> = HashMap::new();
+        let strict = self.syntax.is_strict();
+        let mut parser = Parser::with_syntax(text, dialect, self.syntax.clone());
+        while let Some(event) = parser.next() {
+            let event = event.map_err(|e| self.in_file(e, file))?;
+            let line_span = event.span();
+            let line = &text[line_span.clone()];
+            // The line as kept in the document, with the `\r` of a CRLF line ending
+            let cr = usize::from(text[line_span.end..].starts_with("\r\n"));
+            let raw = &text[line_span.start..line_span.end + cr];
+            // Span of the text of the line, without indentation
+            let indent = line.len() - line.trim_start().len();
+            let text_span = line_span.start + indent..line_span.start + line.trim_end().len();
+            if let Event::Continuation { value, .. } = event {
+                self.file_document(file)
+                    .continue_option(skipped_lines, raw, value);
+                skipped_lines = Vec::new();
+                match continued.take() {
+                    Some(first) if dialect.continues(line) => continued = Some(first),
+                    Some(first) => {
+                        let key = current.clone().unwrap();
+                        let value = self.document(file).last_value().unwrap().to_string();
+                        self.store_raw(&section, &key, &value, file)
+                            .map_err(|message| self.value_error(file, text, first, message))?;
+                    }
+                    // Stored once complete, the value growing with each line
+                    None => unstored = true,
+                }
+                continue;
+            }
+            if let Event::Blank { .. } | Event::Comment { .. } = event {
+                if current.is_some() {
+                    skipped_lines.push(raw.to_string());
+                } else {
+                    self.file_document(file).push(Line::Text(raw.to_string()));
+                }
+                continue;
+            }
+            if unstored {
+                self.store_continued(&section, &current.unwrap(), file);
+                unstored = false;
+            }
+            current = None;
+            for skipped_line in skipped_lines.drain(..) {
+                self.file_document(file).push(Line::Text(skipped_line));
+            }
+            match event {
+                Event::SectionStart { name, .. } => {
+                    if strict {
+                        if let Some(first) =
+                            defined_sections.insert(name.to_string(), parser.line())
+                        {
+                            let message =
+                                format!("section [{}] already defined at line {}", name, first);
+                            let help = "merge the two sections, or read the file without \
+                                        `Syntax::strict()`";
+                            return Err(self.syntax_error(file, text, text_span, message, help));
+                        }
+                    }
+                    if !self.sections.contains_key(&name) {
+                        self.create_section(&name);
+                    }
+                    self.file_document(file).push(Line::Section {
+                        name: name.to_string(),
+                        raw: raw.to_string(),
+                    });
+                    section = name.into_owned();
+                }
+                Event::KeyValue {
+                    key,
+                    value,
+                    span,
+                    value_span,
+                } => {
+                    let key_span = text_span.start..text_span.start + key.len();
+                    let key = (self.optionxform)(key);
+                    if strict {
+                        let defined = (section.clone(), key.clone());
+                        if let Some(first) = defined_options.insert(defined, parser.line()) {
+                            let message = format!(
+                                "option {:?} already defined in section [{}] at line {}",
+                                key, section, first
+                            );
+                            let help = "remove one of the definitions, or read the file without \
+                                        `Syntax::strict()`";
+                            return Err(self.syntax_error(file, text, key_span, message, help));
+                        }
+                    }
+                    if dialect.first_wins() && self.stored_value(&section, &key).is_some() {
+                        // Ignored, as Windows does
+                        self.file_document(file).push(Line::Text(raw.to_string()));
+                        continue;
+                    }
+                    let relative = value_span.start - span.start..value_span.end - span.start;
+                    self.file_document(file).push(Line::option(
+                        raw,
+                        &key,
+                        relative,
+                        value.is_some(),
+                    ));
+                    match value {
+                        Some(value) if dialect.continues(value) => continued = Some(value_span),
+                        Some(value) => self
+                            .store_raw(&section, &key, value, file)
+                            .map_err(|message| self.value_error(file, text, value_span, message))?,
+                        None => {
+                            let implicit = dialect.implicit_value().to_string();
+                            self.store(&section, &key, implicit, file)
+                        }
+                    }
+                    current = Some(key);
+                }
+                Event::Directive { name, argument, .. } => {
+                    self.file_document(file).push(Line::Text(raw.to_string()));
+                    let including = self.file_path(file).to_path_buf();
+                    for path in include::paths(&including, name, argument)? {
+                        self.include(path, chain)?;
+                    }
+                }
+                _ => self.file_document(file).push(Line::Text(raw.to_string())),
+            }
+        }
+        if let (Some(first), Some(key)) = (continued, current.as_ref()) {
+            let value = self.document(file).last_value().unwrap().to_string();
+            self.store_raw(&section, key, &value, file)
+                .map_err(|message| self.value_error(file, text, first, message))?;
+        }
+        if unstored {
+            self.store_continued(&section, &current.unwrap(), file);
+        }
+        for skipped_line in skipped_lines {
+            self.file_document(file).push(Line::Text(skipped_line));
+        }
+        let final_newline = text.is_empty() || text.ends_with('\n');
+        self.file_document(file).set_final_newline(final_newline);
+        Ok(())
+    }
+    /**
+     * Items of a list value, see `get_list()`.
+     */
+    fn split_list<'a>(&self, value: &'a str) -> Vec<&'a str> {
+        value
+            .split([self.list_delimiter, '\n'])
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+    /**
+     * Define the value of the option in the section, creating the section if needed, `file` being the file
+     * defining it. The option name must be normalized.
+     */
+    fn store(&mut self, section: &str, option: &str, text: String, file: usize) {
+        if !self.sections.contains_key(section) {
+            self.create_section(section);
+        }
+        self.sections
+            .get_mut(section)
+            .unwrap()
+            .insert(option.to_string(), Value { file, text });
+    }
+    /**
+     * Define the value of the option from the last line of the file `file`, an option with continuation
+     * lines.
+     */
+    fn store_continued(&mut self, section: &str, option: &str, file: usize) {
+        let value = self.document(file).last_value().unwrap().to_string();
+        self.store(section, option, value, file);
+    }
+    /**
+     * Decode the raw value of an option, and define it; or tell why the value is invalid.
+     */
+    fn store_raw(
+        &mut self,
+        section: &str,
+        option: &str,
+        raw: &str,
+        file: usize,
+    ) -> Result<(), String> {
+        let value = self.dialect.decode(raw)?;
+        self.store(section, option, value, file);
+        Ok(())
+    }
+    /**
+     * Value of the option in the section and the file defining it, as read or set, ignoring the overrides.
+     */
+    fn stored(&self, section: &str, option: &str) -> Option<&Value> {
+        self.sections
+            .get(section)
+            .and_then(|options| options.get(option))
+    }
+    /**
+     * Value of the option in the section, as read or set, ignoring the overrides.
+     */
+    fn stored_value(&self, section: &str, option: &str) -> Option<&String> {
+        self.stored(section, option).map(|value| &value.text)
+    }
+    /**
+     * Coerce a value to a boolean, see `get_bool()`.
+     */
+    pub(crate) fn parse_bool(&self, value: &str) -> Option<bool> {
+        self.boolean_states
+            .get(&value.trim().to_lowercase())
+            .cloned()
+    }
+    /**
+     * Syntax error at `span` in `text`, the text of the file `file`.
+     */
+    fn syntax_error(
+        &self,
+        file: usize,
+        text: &str,
+        span: Range<usize>,
+        message: String,
+        help: &str,
+    ) -> IniError {
+        let location = Location::in_text(text, span);
+        let diagnostic = Diagnostic::error(message, Some(location)).with_help(help.to_string());
+        self.in_file(IniError::Syntax(Box::new(diagnostic)), file)
+    }
+    /**
+     * Content of the file, in its encoding and with its line endings.
+     */
+    fn to_bytes(&self) -> IniResult<Vec<u8>> {
+        encoding::encode(&self.to_string(), self.format).map_err(IniError::Encoding)
+    }
+    /**
+     * Replace the content of the file by the configuration, atomically, keeping the backups.
+     */
+    fn try_write_path(&self, path: &Path) -> IniResult<()> {
+        atomic::write(path, &self.to_bytes()?, self.backups)?;
+        debug!("INI file {:?} written", path);
+        for included in self.includes.iter().filter(|included| included.modified) {
+            let content = encoding::encode(&included.document.to_string(), included.format)
+                .map_err(IniError::Encoding)?;
+            atomic::write(&included.path, &content, self.backups)?;
+            debug!("INI file {:?} written", included.path);
+        }
+        Ok(())
+    }
+    /**
+     * Syntax error of the raw value at `span` in `text`, the text of the file `file`, which can not be
+     * decoded.
+     */
+    fn value_error(
+        &self,
+        file: usize,
+        text: &str,
+        span: Range<usize>,
+        message: String,
+    ) -> IniError {
+        let help = "quote the value with `\"`, and escape `\\` and `\"` in it with a backslash";
+        self.syntax_error(file, text, span, message, help)
+    }
+    /**
+     * Empty configuration with the same settings, overrides and file path, to read the file again.
+     */
+    fn with_settings(&self) -> IniFile {
+        let mut ini = IniFile::new();
+        ini.backups = self.backups;
+        ini.boolean_states = self.boolean_states.clone();
+        ini.set_default_section(&self.default_section);
+        ini.dialect = self.dialect;
+        ini.format = self.format;
+        ini.interpolation = self.interpolation;
+        ini.list_delimiter = self.list_delimiter;
+        ini.optionxform = self.optionxform;
+        ini.overrides = self.overrides.clone();
+        ini.path = self.path.clone();
+        ini.set_syntax(self.syntax.clone());
+        ini
+    }
+}
+
+impl Default for IniFile {
+    fn default() -> IniFile {
+        IniFile::new()
+    }
+}
+
+/**
+ * Parse configuration data from a string, such as `include_str!()` data.
+ */
+impl FromStr for IniFile {
+    type Err = IniError;
+
+    fn from_str(text: &str) -> IniResult<IniFile> {
+        IniFile::from_reader(text.as_bytes())
+    }
+}
+
+/**
+ * Boolean words recognized by default by `IniFile::get_bool()`: Python's, and `t` and `f`.
+ */
+fn default_boolean_states() -> HashMap<String, bool> {
+    let mut states = HashMap::new();
+    for word in ["1", "yes", "true", "on", "t"].iter() {
+        states.insert(word.to_string(), true);
+    }
+    for word in ["0", "no", "false", "off", "f"].iter() {
+        states.insert(word.to_string(), false);
+    }
+    states
+}
+
+/**
+ * Default normalization of option names: lowercase, as Python's `optionxform`.
+ */
+pub fn optionxform_lower(option: &str) -> String {
+    option.to_lowercase()
+}
+
+/**
+ * Normalization keeping option names as written, making them case-sensitive.
+ */
+pub fn optionxform_preserve(option: &str) -> String {
+    option.to_string()
+}
+
+fn type_mismatch(section: &str, option: &str, value: String, expected: &'static str) -> IniError {
+    IniError::TypeMismatch {
+        section: section.to_string(),
+        option: option.to_string(),
+        value,
+        expected,
+    }
+}
+
+impl<'a> IntoIterator for &'a IniFile {
+    type Item = Section<'a>;
+    type IntoIter = Sections<'a>;
+
+    fn into_iter(self) -> Sections<'a> {
+        self.iter()
+    }
+}
+
+/**
+ * Formatting trait <https://doc.rust-lang.org/std/fmt/>.
+ * Operator overloading
+ * @see http://maniagnosis.crsr.net/2013/04/operator-overloading-in-rust.html
+ */
+impl fmt::Display for IniFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/**
+ * Empty directory for the files written by a test, under the temporary directory and unique to the test
+ * process: tests do not write into the source tree, nor race with another run of the tests.
+ */
+#[cfg(test)]
+fn test_directory(name: &str) -> PathBuf {
+    let directory =
+        ::std::env::temp_dir().join(format!("inifile-{}-{}", name, ::std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[cfg(test)]
+#[allow(non_fmt_panics, noop_method_call)]
+#[allow(
+    clippy::identity_op,
+    clippy::needless_borrows_for_generic_args,
+    clippy::nonminimal_bool,
+    clippy::redundant_pattern_matching,
+    clippy::redundant_static_lifetimes,
+    clippy::zero_prefixed_literal
+)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use super::{
+        test_directory, Diagnostic, Dialect, Encoding, IniError, IniFile, IniResult, Interpolation,
+        InterpolationError, LineEnding, Syntax,
+    };
+    use model::Random;
+    use std::time::{Duration, Instant};
+
+    static FILEPATH: &'static str = "src/data/config.ini";
+
+    /**
+     * Path::exists() is unstable in Rust-1.3.0.
+     */
+    fn exists(path: &Path) -> bool {
+        match fs::metadata(path) {
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
+
+    #[test]
+    fn default_filepath_is_empty() {
+        let ini = super::IniFile::new();
+        let expected = "".to_string();
+        let found = ini.filepath();
+        assert!(
+            expected == found,
+            format!(
+                "Default file path must be \"{}\", not \"{}\".",
+                expected, found
+            )
+        );
+    }
+    #[test]
+    fn filepath() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        let expected = FILEPATH.to_string();
+        let found = ini.filepath();
+        assert!(
+            expected == found,
+            format!(
+                "Default file path must be \"{}\", not \"{}\".",
+                expected, found
+            )
+        );
+    }
+    #[test]
+    fn sections_length() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        let expected = 5;
+        let sections = ini.sections();
+        let found = sections.len();
+        assert!(
+            expected == found,
+            format!("{} sections are expected, not {}.", expected, found)
+        );
+    }
+    #[test]
+    fn sections_names() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        let expected = vec![
+            "section1".to_string(),
+            "section2".to_string(),
+            "Booleans".to_string(),
             "Integers".to_string(),
             "Floats".to_string(),
         ];
@@ -575,159 +2215,1273 @@ mod tests {
         );
     }
     #[test]
-    #[should_panic]
-    fn add_section_twice() {
+    #[should_panic]
+    fn add_section_twice() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        ini.add_section("New section");
+        ini.add_section("New section");
+    }
+    #[test]
+    fn remove_section() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        ini.remove_section("section1".to_string());
+        let expected = vec![
+            "section2".to_string(),
+            "Booleans".to_string(),
+            "Integers".to_string(),
+            "Floats".to_string(),
+        ];
+        let found = ini.sections();
+        assert!(
+            expected == found,
+            format!("Sections must be \"{:?}\", not {:?}.", expected, found)
+        );
+    }
+    #[test]
+    fn remove_overridden() {
+        let mut ini: IniFile = "a = 1\n[s]\nkey = file\n".parse().unwrap();
+        ini.set_override("s", "key", "env", "env APP__S__KEY");
+        ini.set_override("s", "a", "env", "env APP__S__A");
+        ini.set_override("only", "x", "1", "--set only.x=1");
+        assert!(ini.remove_option("s".to_string(), "key".to_string()));
+        assert!(!ini.has_option("s", "key"));
+        assert!(ini.remove_option("s".to_string(), "a".to_string()));
+        assert_eq!("1", ini.get("s", "a"));
+        assert!(!ini.remove_option("s".to_string(), "a".to_string()));
+        assert!(ini.remove_section("only".to_string()));
+        assert!(!ini.has_section("only"));
+        assert_eq!(vec!["s"], ini.sections());
+        assert!(ini.overrides().is_empty());
+        assert_eq!("a = 1\n[s]\n", ini.to_string());
+    }
+    #[test]
+    fn set() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        ini.set(
+            "section1".to_string(),
+            "value2".to_string(),
+            "string 2".to_string(),
+        );
+        let expected = "string 2".to_string();
+        let found = ini.get("section1", "value2");
+        assert!(
+            expected == found,
+            format!(
+                "[section1] value2 must be \"{}\", not \"{}\".",
+                expected, found
+            )
+        );
+    }
+    #[test]
+    fn options() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        let expected = vec!["value11".to_string(), "value".to_string()];
+        let found = ini.options("section1".to_string());
+        assert!(
+            expected == found,
+            format!(
+                "Items of [section1] must be \"{:?}\", not {:?}.",
+                expected, found
+            )
+        );
+    }
+    #[test]
+    fn to_string() {
+        let path = Path::new(FILEPATH);
+        let file = File::open(&path);
+        match file {
+            Err(e) => panic!("open of {:?} failed: {}", path, e),
+            _ => debug!("open of {:?} succeeded", path),
+        }
+        let reader = BufReader::new(file.unwrap());
+        let mut lines: Vec<String> = Vec::new();
+        for line in reader.lines() {
+            match line {
+                Ok(nread) => lines.push(nread + "\n"),
+                Err(e) => println!("error reading: {}", e),
+            }
+        }
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        let found = ini.to_string();
+        let expected = lines.concat();
+        assert_eq!(expected, found);
+    }
+    #[test]
+    fn write() {
+        // Copy config.ini to write_test.ini using `write()`.
+        let writepath = "src/data/write_test.ini";
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        ini.write(writepath);
+
+        // Check that the new file exists
+        let path = Path::new(writepath);
+        assert!(
+            exists(path),
+            format!("{} should exist after writing inifile!", writepath)
+        );
+
+        // Check the contents
+        let mut ini2 = super::IniFile::new();
+        ini2.read(writepath);
+        let found = ini2.to_string();
+        let expected = ini.to_string();
+        assert_eq!(expected, found);
+
+        // Clean
+        assert!(
+            exists(path),
+            format!("{} should exist after reading the new inifile!", writepath)
+        );
+        let result = fs::remove_file(&path);
+        assert!(
+            !result.is_err(),
+            format!("Unlinking {} should not panic!", writepath)
+        );
+    }
+    #[test]
+    fn save() {
+        let filepath = "src/data/save_test.ini";
+        let path = Path::new(filepath);
+        if exists(path) {
+            match fs::remove_file(&path) {
+                Err(e) => panic!("removing {:?} failed: {}", path, e),
+                _ => debug!("removing {:?} succeeded", path),
+            }
+        }
+
+        let mut ini = super::IniFile::new();
+        ini.add_section("section1");
+        ini.set(
+            "section1".to_string(),
+            "key1".to_string(),
+            "value1".to_string(),
+        );
+        ini.set_path(path.clone());
+        ini.save();
+
+        let file = File::open(&path);
+        match file {
+            Err(e) => panic!("open of {:?} failed: {}", path, e),
+            _ => debug!("open of {:?} succeeded", path),
+        }
+        let reader = BufReader::new(file.unwrap());
+        let mut lines: Vec<String> = Vec::new();
+        for line in reader.lines() {
+            match line {
+                Ok(nread) => lines.push(nread + "\n"),
+                Err(e) => println!("error reading: {}", e),
+            }
+        }
+
+        let found = lines.concat();
+        let expected = "[section1]\nkey1=value1\n".to_string();
+        assert_eq!(expected, found);
+        match fs::remove_file(&path) {
+            Err(e) => panic!("removing {:?} failed: {}", path, e),
+            _ => debug!("removing {:?} succeeded", path),
+        }
+    }
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+    #[test]
+    fn try_read_missing_file() {
+        let mut ini = super::IniFile::new();
+        match ini.try_read("src/data/missing.ini") {
+            Err(IniError::Io(_)) => (),
+            other => panic!("IniError::Io expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn try_read_string_without_equal() {
+        let mut ini = super::IniFile::new();
+        match ini.try_read_string(lines("[section]\nkey=value\n\nno equal sign")) {
+            Err(IniError::Syntax(diagnostic)) => assert_eq!(Some(4), diagnostic.line()),
+            other => panic!("IniError::Syntax expected, not {:?}", other),
+        }
+        assert_eq!("value", ini.get("section", "key"));
+    }
+    #[test]
+    fn try_read_string_unclosed_section() {
+        let mut ini = super::IniFile::new();
+        match ini.try_read_string(lines("# comment\n[section")) {
+            Err(IniError::Syntax(diagnostic)) => assert_eq!(Some(2), diagnostic.line()),
+            other => panic!("IniError::Syntax expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn try_get_missing_section() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        match ini.try_get("unknown section", "value11") {
+            Err(IniError::MissingSection(section)) => assert_eq!("unknown section", section),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn try_get_missing_option() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        match ini.try_get("section1", "unknown key") {
+            Err(IniError::MissingOption { section, option }) => {
+                assert_eq!("section1", section);
+                assert_eq!("unknown key", option);
+            }
+            other => panic!("IniError::MissingOption expected, not {:?}", other),
+        }
+    }
+    #[test]
+    #[should_panic]
+    fn get_missing_option() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        ini.get("section1", "unknown key");
+    }
+    #[test]
+    fn try_get_type_mismatch() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        match ini.try_get_bool("section1", "value11") {
+            Err(IniError::TypeMismatch {
+                value, expected, ..
+            }) => {
+                assert_eq!("string 11", value);
+                assert_eq!("a boolean", expected);
+            }
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+        match ini.try_get_int("section1", "value11") {
+            Err(IniError::TypeMismatch { expected, .. }) => assert_eq!("an integer", expected),
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+        match ini.try_get_f64("section1", "value11") {
+            Err(IniError::TypeMismatch { expected, .. }) => assert_eq!("a float", expected),
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+        assert_eq!(Some(1.1f64), ini.try_get_f64("Floats", "float11").ok());
+    }
+    #[test]
+    fn try_add_section_twice() {
+        let mut ini = super::IniFile::new();
+        assert!(ini.try_add_section("New section").is_ok());
+        match ini.try_add_section("New section") {
+            Err(IniError::DuplicateSection(section)) => assert_eq!("New section", section),
+            other => panic!("IniError::DuplicateSection expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn try_set_missing_section() {
+        let mut ini = super::IniFile::new();
+        let result = ini.try_set(
+            "section1".to_string(),
+            "key1".to_string(),
+            "value1".to_string(),
+        );
+        match result {
+            Err(IniError::MissingSection(section)) => assert_eq!("section1", section),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn try_remove_option() {
+        let mut ini = super::IniFile::new();
+        ini.read(FILEPATH);
+        match ini.try_remove_option("unknown section".to_string(), "value11".to_string()) {
+            Err(IniError::MissingSection(_)) => (),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+        let found = ini.try_remove_option("section1".to_string(), "unknown key".to_string());
+        assert_eq!(Some(false), found.ok());
+    }
+    #[test]
+    fn try_write_to_missing_directory() {
+        let ini = super::IniFile::new();
+        match ini.try_write("src/data/missing/write_test.ini") {
+            Err(IniError::Io(_)) => (),
+            other => panic!("IniError::Io expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn get_interpolation() {
+        let mut ini = super::IniFile::new();
+        ini.read_string(lines(
+            "[DEFAULT]\nroot=/srv\n[paths]\nhome=%(root)s/home\ndocs=${home}/docs\nbackup=${DEFAULT:root}/backup",
+        ));
+        assert_eq!("%(root)s/home", ini.get("paths", "home"));
+        ini.set_interpolation(Interpolation::Basic);
+        assert_eq!("/srv/home", ini.get("paths", "home"));
+        assert_eq!("%(root)s/home", ini.get_raw("paths", "home"));
+        ini.set_interpolation(Interpolation::Extended);
+        assert_eq!("/srv/backup", ini.get("paths", "backup"));
+        assert_eq!("%(root)s/home/docs", ini.get("paths", "docs"));
+        assert_eq!("${home}/docs", ini.get_raw("paths", "docs"));
+    }
+    #[test]
+    fn try_get_interpolation_cycle() {
+        let mut ini = super::IniFile::new();
+        ini.read_string(lines("[section]\na=%(b)s\nb=%(a)s\nsize=100%"));
+        ini.set_interpolation(Interpolation::Basic);
+        match ini.try_get("section", "a") {
+            Err(IniError::Interpolation {
+                cause: InterpolationError::Cycle(_),
+                ..
+            }) => (),
+            other => panic!("InterpolationError::Cycle expected, not {:?}", other),
+        }
+        match ini.try_get_int("section", "size") {
+            Err(IniError::Interpolation {
+                cause: InterpolationError::Syntax(_),
+                ..
+            }) => (),
+            other => panic!("InterpolationError::Syntax expected, not {:?}", other),
+        }
+        assert_eq!("a=%(b)s", ini.to_string().lines().nth(1).unwrap());
+    }
+    #[test]
+    fn default_section() {
+        let mut ini = super::IniFile::new();
+        ini.read_string(lines(
+            "key=default\nother=default other\n[section1]\nkey=value1\n[section2]\n",
+        ));
+        assert_eq!(
+            vec!["section1".to_string(), "section2".to_string()],
+            ini.sections()
+        );
+        assert!(!ini.has_section("DEFAULT"));
+        assert_eq!("value1", ini.get("section1", "key"));
+        assert_eq!("default", ini.get("section2", "key"));
+        assert_eq!("default", ini.get("DEFAULT", "key"));
+        assert!(ini.has_option("section2", "other"));
+        assert!(!ini.has_option("unknown section", "other"));
+        assert_eq!(
+            vec!["key".to_string(), "other".to_string()],
+            ini.options("section1".to_string())
+        );
+        assert!(ini.remove_option("section1".to_string(), "key".to_string()));
+        assert_eq!("default", ini.get("section1", "key"));
+        assert!(!ini.remove_option("section1".to_string(), "key".to_string()));
+        assert!(!ini.remove_section("DEFAULT".to_string()));
+        assert!(ini.try_add_section("DEFAULT").is_err());
+    }
+    #[test]
+    fn default_section_written_first() {
+        let mut ini = super::IniFile::new();
+        ini.set_default_section("common");
+        ini.read_string(lines("[section]\nkey=value\n[common]\nkey=default"));
+        ini.set("common".to_string(), "other".to_string(), "1".to_string());
+        assert_eq!(vec!["section".to_string()], ini.sections());
+        assert_eq!(
+            "[section]\nkey=value\n[common]\nkey=default\nother=1\n",
+            ini.to_string()
+        );
+        let mut ini = super::IniFile::new();
+        ini.read_string(lines("[section]\nkey=value"));
+        ini.set(
+            "DEFAULT".to_string(),
+            "key".to_string(),
+            "default".to_string(),
+        );
+        assert_eq!(
+            "[DEFAULT]\nkey=default\n[section]\nkey=value\n",
+            ini.to_string()
+        );
+    }
+    static ROUNDTRIP_FILES: [&str; 4] = [
+        "src/data/roundtrip/php.ini",
+        "src/data/roundtrip/smb.conf",
+        "src/data/roundtrip/system.ini",
+        "src/data/roundtrip/desktop.ini",
+    ];
+    #[test]
+    fn round_trip() {
+        for filepath in ROUNDTRIP_FILES.iter() {
+            let expected = fs::read_to_string(filepath).unwrap();
+            let mut ini = super::IniFile::new();
+            ini.read(filepath);
+            assert_eq!(
+                expected,
+                ini.to_string(),
+                "{} must be written as read.",
+                filepath
+            );
+        }
+    }
+    #[test]
+    fn round_trip_line_endings() {
+        let written = |ini: &IniFile| {
+            let mut bytes: Vec<u8> = Vec::new();
+            ini.write_to(&mut bytes).unwrap();
+            String::from_utf8(bytes).unwrap()
+        };
+        let mut ini: IniFile = "[a]\nk=1".parse().unwrap();
+        assert_eq!("[a]\nk=1", written(&ini));
+        ini.set("a".to_string(), "k".to_string(), "2".to_string());
+        ini.add_section("b");
+        assert_eq!("[a]\nk=2\n\n[b]", written(&ini));
+
+        let text = "[a]\r\nk=1\nj = 2\r\n; c\nm = x\r\n  y\n";
+        let mut ini: IniFile = text.parse().unwrap();
+        assert_eq!(LineEnding::CrLf, ini.line_ending());
+        assert_eq!(text, written(&ini));
+        assert_eq!("2", ini.get("a", "j"));
+        assert_eq!("x\ny", ini.get("a", "m"));
+        ini.set("a".to_string(), "j".to_string(), "3".to_string());
+        ini.set("a".to_string(), "m".to_string(), "x\nz\nw".to_string());
+        ini.set("a".to_string(), "n".to_string(), "4".to_string());
+        ini.set_comment("a", Some("k"), "key");
+        assert_eq!(
+            "[a]\r\n; key\r\nk=1\nj = 3\r\n; c\nm = x\r\n  z\r\n  w\r\nn = 4\r\n",
+            written(&ini)
+        );
+        ini.set_line_ending(LineEnding::Lf);
+        assert_eq!(
+            "[a]\n; key\nk=1\nj = 3\n; c\nm = x\n  z\n  w\nn = 4\n",
+            written(&ini)
+        );
+    }
+    #[test]
+    fn round_trip_set_changes_one_line() {
+        let filepath = "src/data/roundtrip/php.ini";
+        let original = fs::read_to_string(filepath).unwrap();
         let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        ini.add_section("New section");
-        ini.add_section("New section");
+        ini.read(filepath);
+        assert_eq!("128M", ini.get("PHP", "memory_limit"));
+        ini.set(
+            "PHP".to_string(),
+            "memory_limit".to_string(),
+            "256M".to_string(),
+        );
+        let found = ini.to_string();
+        let changed: Vec<(&str, &str)> = original
+            .lines()
+            .zip(found.lines())
+            .filter(|&(before, after)| before != after)
+            .collect();
+        assert_eq!(
+            vec![("memory_limit = 128M", "memory_limit = 256M")],
+            changed
+        );
+        assert_eq!(original.lines().count(), found.lines().count());
     }
     #[test]
-    fn remove_section() {
+    fn round_trip_edits() {
         let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        ini.remove_section("section1".to_string());
-        let expected = vec![
-            "section2".to_string(),
-            "Booleans".to_string(),
-            "Integers".to_string(),
-            "Floats".to_string(),
-        ];
-        let found = ini.sections();
-        assert!(
-            expected == found,
-            format!("Sections must be \"{:?}\", not {:?}.", expected, found)
+        ini.read("src/data/roundtrip/smb.conf");
+        assert_eq!("WORKGROUP", ini.get("global", "workgroup"));
+        ini.set(
+            "homes".to_string(),
+            "guest ok".to_string(),
+            "no".to_string(),
         );
+        ini.remove_option("homes".to_string(), "browseable".to_string());
+        ini.remove_section("global".to_string());
+        let expected = "# This is the main Samba configuration file.
+#
+
+#======================= Share Definitions =======================
+
+[homes]
+   comment = Home Directories
+   read only = yes
+guest ok = no
+
+;[printers]
+;   comment = All Printers
+";
+        assert_eq!(expected, ini.to_string());
     }
     #[test]
-    fn set() {
+    fn round_trip_save() {
+        let filepath = "src/data/round_trip_save_test.ini";
         let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
+        ini.read("src/data/roundtrip/system.ini");
         ini.set(
-            "section1".to_string(),
-            "value2".to_string(),
-            "string 2".to_string(),
-        );
-        let expected = "string 2".to_string();
-        let found = ini.get("section1", "value2");
-        assert!(
-            expected == found,
-            format!(
-                "[section1] value2 must be \"{}\", not \"{}\".",
-                expected, found
-            )
+            "boot".to_string(),
+            "shell".to_string(),
+            "cmd.exe".to_string(),
         );
+        ini.set_path(Path::new(filepath));
+        ini.save();
+        let found = fs::read_to_string(filepath).unwrap();
+        fs::remove_file(filepath).unwrap();
+        let expected = fs::read_to_string("src/data/roundtrip/system.ini")
+            .unwrap()
+            .replace("shell=Explorer.exe", "shell=cmd.exe");
+        assert_eq!(expected, found);
     }
     #[test]
-    fn options() {
+    fn multi_line_values() {
+        let text =
+            "[section]\nkey = first\n  second\n\n  third\n\nnext=1\n  [not a section]\n\n[other]\n";
         let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        let expected = vec!["value11".to_string(), "value".to_string()];
-        let found = ini.options("section1".to_string());
-        assert!(
-            expected == found,
-            format!(
-                "Items of [section1] must be \"{:?}\", not {:?}.",
-                expected, found
-            )
+        ini.read_string(lines(text));
+        assert_eq!("first\nsecond\n\nthird", ini.get("section", "key"));
+        assert_eq!("1\n[not a section]", ini.get("section", "next"));
+        assert_eq!(
+            vec!["section".to_string(), "other".to_string()],
+            ini.sections()
         );
+        assert_eq!(text, ini.to_string());
+        ini.set("other".to_string(), "list".to_string(), "a\nb".to_string());
+        ini.set("section".to_string(), "key".to_string(), "1\n2".to_string());
+        let expected =
+            "[section]\nkey = 1\n  2\n\nnext=1\n  [not a section]\n\n[other]\nlist = a\n    b\n";
+        assert_eq!(expected, ini.to_string());
+        let mut ini2 = super::IniFile::new();
+        ini2.read_string(lines(expected));
+        assert_eq!("a\nb", ini2.get("other", "list"));
     }
     #[test]
-    fn to_string() {
-        let path = Path::new(FILEPATH);
-        let file = File::open(&path);
-        match file {
-            Err(e) => panic!("open of {:?} failed: {}", path, e),
-            _ => debug!("open of {:?} succeeded", path),
+    fn multi_line_values_with_comments() {
+        let text = "[s]\nk=v\n  # c\n";
+        let ini: IniFile = text.parse().unwrap();
+        assert_eq!("v", ini.get("s", "k"));
+        assert_eq!(text, ini.to_string());
+        let text = "[s]\nk=v\n# c\n  more\n; d\n\n  last\nnext = 1\n";
+        let mut ini: IniFile = text.parse().unwrap();
+        assert_eq!("v\nmore\n\nlast", ini.get("s", "k"));
+        assert_eq!("1", ini.get("s", "next"));
+        assert_eq!(text, ini.to_string());
+        ini.set("s".to_string(), "k".to_string(), "1\n2".to_string());
+        assert_eq!("[s]\nk=1\n  2\nnext = 1\n", ini.to_string());
+    }
+    #[test]
+    fn from_reader() {
+        let reader = BufReader::new(File::open(FILEPATH).unwrap());
+        let ini = IniFile::from_reader(reader).unwrap();
+        assert_eq!("string 11", ini.get("section1", "value11"));
+        assert_eq!("", ini.filepath());
+        match IniFile::from_reader("[section\n".as_bytes()) {
+            Err(IniError::Syntax(diagnostic)) => assert_eq!(Some(1), diagnostic.line()),
+            other => panic!(
+                "IniError::Syntax expected, not {:?}",
+                other.map(|ini| ini.to_string())
+            ),
         }
-        let reader = BufReader::new(file.unwrap());
-        let mut lines: Vec<String> = Vec::new();
-        for line in reader.lines() {
-            match line {
-                Ok(nread) => lines.push(nread + "\n"),
-                Err(e) => println!("error reading: {}", e),
+    }
+    #[test]
+    fn from_str() {
+        let ini: IniFile = include_str!("data/config.ini").parse().unwrap();
+        assert_eq!(5, ini.sections().len());
+        let mut output: Vec<u8> = Vec::new();
+        ini.write_to(&mut output).unwrap();
+        assert_eq!(include_str!("data/config.ini").as_bytes(), &output[..]);
+    }
+    #[test]
+    fn from_str_linear() {
+        // Time to parse `sections` sections of 50 options.
+        let time = |sections: usize| {
+            let mut text = String::new();
+            for section in 0..sections {
+                text.push_str(&format!("[section{}]\n", section));
+                for option in 0..50 {
+                    text.push_str(&format!("option{} = value {}\n", option, option));
+                }
             }
+            let start = Instant::now();
+            let ini: IniFile = text.parse().unwrap();
+            assert_eq!(sections, ini.sections().len());
+            start.elapsed()
+        };
+        let small = (0..3).map(|_| time(100)).min().unwrap();
+        let large = time(800);
+        // 8 times the lines: about 8 times longer when linear, 64 times when quadratic.
+        assert!(
+            large < small * 24,
+            "{:?} for 40800 lines, {:?} for 5100 lines",
+            large,
+            small
+        );
+    }
+    #[test]
+    fn load_and_save_as() {
+        let filepath = "src/data/save_as_test.ini";
+        let mut ini = IniFile::load(FILEPATH).unwrap();
+        assert_eq!(FILEPATH, ini.filepath());
+        ini.save_as(filepath).unwrap();
+        assert_eq!(filepath, ini.filepath());
+        let found = IniFile::load(filepath).unwrap().to_string();
+        fs::remove_file(filepath).unwrap();
+        assert_eq!(ini.to_string(), found);
+        match IniFile::load("src/data/missing.ini") {
+            Err(IniError::Io(_)) => (),
+            other => panic!(
+                "IniError::Io expected, not {:?}",
+                other.map(|ini| ini.to_string())
+            ),
         }
-        let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        let found = ini.to_string();
-        let expected = lines.concat();
-        assert_eq!(expected, found);
     }
     #[test]
-    fn write() {
-        // Copy config.ini to write_test.ini using `write()`.
-        let writepath = "src/data/write_test.ini";
-        let mut ini = super::IniFile::new();
-        ini.read(FILEPATH);
-        ini.write(writepath);
-
-        // Check that the new file exists
-        let path = Path::new(writepath);
-        assert!(
-            exists(path),
-            format!("{} should exist after writing inifile!", writepath)
+    fn owned() {
+        struct App {
+            config: IniFile,
+        }
+        let app = App {
+            config: IniFile::load(String::from(FILEPATH)).unwrap(),
+        };
+        assert!(app.config.has_section("Floats"));
+    }
+    #[test]
+    fn optionxform() {
+        let text = "[Server]\nHostName = example.org\nPort=80\n";
+        let mut ini: IniFile = text.parse().unwrap();
+        assert_eq!(vec!["hostname", "port"], ini.options("Server".to_string()));
+        assert_eq!("example.org", ini.get("Server", "HOSTNAME"));
+        assert!(ini.has_option("Server", "hostName"));
+        ini.set(
+            "Server".to_string(),
+            "hostname".to_string(),
+            "example.com".to_string(),
         );
+        ini.set(
+            "Server".to_string(),
+            "TimeOut".to_string(),
+            "30".to_string(),
+        );
+        assert_eq!(
+            "[Server]\nHostName = example.com\nPort=80\nTimeOut=30\n",
+            ini.to_string()
+        );
+        assert!(ini.remove_option("Server".to_string(), "PORT".to_string()));
+        assert!(!ini.has_option("Server", "port"));
+        assert_eq!(
+            "[Server]\nHostName = example.com\nTimeOut=30\n",
+            ini.to_string()
+        );
+    }
+    #[test]
+    fn optionxform_preserve() {
+        let mut ini = IniFile::new();
+        ini.set_optionxform(super::optionxform_preserve);
+        ini.read_string(lines("[a]\nKey=1\nkey=2\n"));
+        assert_eq!(vec!["Key", "key"], ini.options("a".to_string()));
+        assert_eq!("1", ini.get("a", "Key"));
+        assert_eq!("2", ini.get("a", "key"));
+        assert!(!ini.has_option("a", "KEY"));
+    }
+    #[test]
+    fn get_bool_case_insensitive() {
+        let ini: IniFile = "[a]\nt1=True\nt2=YES\nt3=On\nf1=FALSE\nf2=No\n"
+            .parse()
+            .unwrap();
+        for option in ["t1", "t2", "t3"].iter() {
+            assert!(ini.get_bool("a", option), "{} should be true", option);
+        }
+        for option in ["f1", "f2"].iter() {
+            assert!(!ini.get_bool("a", option), "{} should be false", option);
+        }
+    }
+    #[test]
+    fn set_boolean_states() {
+        let mut ini: IniFile = "[a]\nenabled=Enabled\nflag=yes\n".parse().unwrap();
+        let mut states = HashMap::new();
+        states.insert("ENABLED".to_string(), true);
+        states.insert("disabled".to_string(), false);
+        ini.set_boolean_states(states);
+        assert!(ini.get_bool("a", "enabled"));
+        match ini.try_get_bool("a", "flag") {
+            Err(IniError::TypeMismatch { .. }) => (),
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+    }
+    fn read_dialect(dialect: Dialect, text: &str) -> IniResult<IniFile> {
+        let mut ini = IniFile::new();
+        ini.set_dialect(dialect);
+        ini.try_read_string(lines(text))?;
+        Ok(ini)
+    }
+    #[test]
+    fn git_dialect() {
+        let text = "[core]\n\tbare = false\n\tfilemode\n[remote \"origin\"]\n\turl = git@example.org:a/b.git\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n\tfetch = +refs/tags/*:refs/tags/* # tags\n[alias]\n\tlg = \"log --graph ; pretty\" ; comment\n\tsay = echo \\\n\t\t\\\"hi\\\"\n";
+        let mut ini = read_dialect(Dialect::Git, text).unwrap();
+        assert_eq!(text, ini.to_string());
+        assert_eq!(vec!["core", "remote.origin", "alias"], ini.sections());
+        assert!(ini.get_bool("core", "filemode"));
+        assert_eq!(
+            "+refs/tags/*:refs/tags/*",
+            ini.get("remote.origin", "fetch")
+        );
+        assert_eq!(
+            vec![
+                "+refs/heads/*:refs/remotes/origin/*",
+                "+refs/tags/*:refs/tags/*"
+            ],
+            ini.get_all("remote.origin", "fetch")
+        );
+        assert_eq!(vec!["true"], ini.get_all("core", "filemode"));
+        assert_eq!("log --graph ; pretty", ini.get("alias", "lg"));
+        assert_eq!("echo \"hi\"", ini.get("alias", "say"));
 
-        // Check the contents
-        let mut ini2 = super::IniFile::new();
-        ini2.read(writepath);
-        let found = ini2.to_string();
-        let expected = ini.to_string();
-        assert_eq!(expected, found);
-
-        // Clean
-        assert!(
-            exists(path),
-            format!("{} should exist after reading the new inifile!", writepath)
+        ini.add("remote.origin", "fetch", "+refs/notes/*:refs/notes/*");
+        ini.set(
+            "core".to_string(),
+            "filemode".to_string(),
+            "false".to_string(),
         );
-        let result = fs::remove_file(&path);
-        assert!(
-            !result.is_err(),
-            format!("Unlinking {} should not panic!", writepath)
+        ini.set(
+            "alias".to_string(),
+            "st".to_string(),
+            " status ".to_string(),
+        );
+        ini.add_section("remote.Upstream");
+        ini.add("remote.Upstream", "url", "u");
+        assert_eq!(
+            "[core]\n\tbare = false\n\tfilemode = false\n[remote \"origin\"]\n\turl = git@example.org:a/b.git\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n\tfetch = +refs/tags/*:refs/tags/* # tags\n\tfetch = +refs/notes/*:refs/notes/*\n[alias]\n\tlg = \"log --graph ; pretty\" ; comment\n\tsay = echo \\\n\t\t\\\"hi\\\"\nst = \" status \"\n\n[remote \"Upstream\"]\nurl = u\n",
+            ini.to_string()
         );
+        assert_eq!(3, ini.get_all("remote.origin", "fetch").len());
+        let reread = read_dialect(Dialect::Git, &ini.to_string()).unwrap();
+        assert!(reread.diff(&ini).is_empty());
+        assert_eq!(" status ", reread.get("alias", "st"));
+
+        assert!(ini.remove_option("remote.origin".to_string(), "fetch".to_string()));
+        assert!(!ini.has_option("remote.origin", "fetch"));
     }
     #[test]
-    fn save() {
-        let filepath = "src/data/save_test.ini";
-        let path = Path::new(filepath);
-        if exists(path) {
-            match fs::remove_file(&path) {
-                Err(e) => panic!("removing {:?} failed: {}", path, e),
-                _ => debug!("removing {:?} succeeded", path),
+    fn git_dialect_errors() {
+        for (text, line) in [
+            ("[remote \"origin]\n", 1),
+            ("[a]\nkey = \"open\n", 2),
+            ("[a]\nkey = \\q\n", 2),
+            ("[a]\nkey = a \\\n  b \\\n", 2),
+        ]
+        .iter()
+        {
+            match read_dialect(Dialect::Git, text) {
+                Err(IniError::Syntax(ref diagnostic)) if diagnostic.line() == Some(*line) => (),
+                Err(e) => panic!("syntax error on line {} expected, not {:?}", line, e),
+                Ok(_) => panic!("syntax error on line {} expected in {:?}", line, text),
             }
         }
+    }
+    #[test]
+    fn windows_dialect() {
+        let text = "[Boot]\n  shell = \" explorer.exe \"\nshell=other.exe\nnot an option\n[Other]\nkey=1\n";
+        let mut ini = read_dialect(Dialect::Windows, text).unwrap();
+        assert_eq!(text, ini.to_string());
+        assert_eq!(" explorer.exe ", ini.get("Boot", "shell"));
+        assert_eq!(vec!["shell"], ini.options("Boot".to_string()));
+        ini.set("Boot".to_string(), "shell".to_string(), "x".to_string());
+        assert_eq!("x", ini.get("Boot", "shell"));
+        assert!(ini
+            .to_string()
+            .starts_with("[Boot]\n  shell = x\nshell=other.exe\n"));
+        // No default section
+        let ini = read_dialect(Dialect::Windows, "key=1\n[a]\n").unwrap();
+        assert!(!ini.has_option("a", "key"));
+        assert_eq!(Dialect::Windows, ini.dialect());
+    }
+    #[test]
+    fn save_with_backup_and_lock() {
+        let directory = test_directory("save-backup");
+        let filepath = &directory.join("save_backup_test.ini");
+        fs::write(filepath, "[a]\nkey = 1\n").unwrap();
+        {
+            let (mut ini, lock) = IniFile::load_locked(filepath).unwrap();
+            assert_eq!(directory.join("save_backup_test.ini.lock"), lock.path());
+            ini.set_backups(1);
+            ini.set("a".to_string(), "key".to_string(), "2".to_string());
+            ini.save();
+        }
+        let backup = directory.join("save_backup_test.ini.bak");
+        assert_eq!("[a]\nkey = 1\n", fs::read_to_string(backup).unwrap());
+        assert_eq!("[a]\nkey = 2\n", fs::read_to_string(filepath).unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+        match IniFile::new().try_lock() {
+            Err(IniError::Io(_)) => (),
+            other => panic!("IniError::Io expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn typed_values() {
+        let mut ini = IniFile::new();
+        ini.read_string(vec![
+            "[server]".to_string(),
+            "port = 0x1F90".to_string(),
+            "ratio = 0.75".to_string(),
+            "hosts = a, b,, c".to_string(),
+            "timeout = 1h30m".to_string(),
+            "cache = 4KiB".to_string(),
+            "mask = 0b1111_0000".to_string(),
+        ]);
+        assert_eq!(8080u16, ini.get_int_as("server", "port"));
+        assert_eq!(240u8, ini.get_int_as("server", "mask"));
+        assert_eq!(0.75, ini.get_as::<f64>("server", "ratio"));
+        assert_eq!(30u32, ini.get_or("server", "workers", 30));
+        assert_eq!(30u32, ini.get_or("missing", "workers", 30));
+        assert_eq!(vec!["a", "b", "c"], ini.get_list("server", "hosts"));
+        assert_eq!(
+            Duration::from_secs(5400),
+            ini.get_duration("server", "timeout")
+        );
+        assert_eq!(4096, ini.get_size("server", "cache"));
+        match ini.try_get_or("server", "ratio", 1u8) {
+            Err(IniError::TypeMismatch { expected, .. }) => assert_eq!("u8", expected),
+            other => panic!("IniError::TypeMismatch expected, not {:?}", other),
+        }
+        assert!(ini.try_get_int_as::<i8>("server", "port").is_err());
+        assert!(ini.try_get_duration("server", "cache").is_err());
+        assert!(ini.try_get_size("server", "timeout").is_err());
+
+        ini.set_as("server", "port", 9000);
+        ini.set_list_delimiter(';');
+        ini.set_list("server", "hosts", &["x", "y"]);
+        ini.set_duration("server", "timeout", Duration::from_millis(90_500));
+        ini.set_size("server", "cache", 10_000_000);
+        assert_eq!(9000, ini.get_int_as::<u16>("server", "port"));
+        assert_eq!("x; y", ini.get("server", "hosts"));
+        assert_eq!(vec!["x", "y"], ini.get_list("server", "hosts"));
+        assert_eq!("1m30.5s", ini.get("server", "timeout"));
+        assert_eq!("10MB", ini.get("server", "cache"));
+        assert!(ini.try_set_as("missing", "key", 1).is_err());
+    }
+    #[test]
+    fn retain() {
+        let mut ini: IniFile = "[DEFAULT]\nkeep = 1\n[a]\n# comment\nx = 1\ny = 2\nz = 3\n"
+            .parse()
+            .unwrap();
+        ini.retain("a", |option, value| option == "x" || value == "3");
+        assert_eq!(vec!["x", "z", "keep"], ini.options("a".to_string()));
+        assert_eq!(
+            "[DEFAULT]\nkeep = 1\n[a]\n# comment\nx = 1\nz = 3\n",
+            ini.to_string()
+        );
+        ini.retain("DEFAULT", |_, _| false);
+        assert!(!ini.has_option("a", "keep"));
+        match ini.try_retain("missing", |_, _| true) {
+            Err(IniError::MissingSection(_)) => (),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn includes() {
+        let directory = &test_directory("includes");
+        fs::create_dir_all(directory.join("conf.d")).unwrap();
+        let files = [
+            (
+                "main.ini",
+                "[server]\nhost = main\n!include common.ini\nport = 80\n!includedir conf.d\n",
+            ),
+            ("common.ini", "timeout = 5\n[server]\nhost = common\n"),
+            ("conf.d/20-tls.ini", "[tls]\nenabled = yes\n"),
+            ("conf.d/10-server.ini", "[server]\nport = 8080\n"),
+        ];
+        for &(name, content) in files.iter() {
+            fs::write(directory.join(name), content).unwrap();
+        }
+        let main = directory.join("main.ini");
+        let mut ini = IniFile::load(&main).unwrap();
+        assert_eq!(
+            vec![
+                directory.join("common.ini"),
+                directory.join("conf.d/10-server.ini"),
+                directory.join("conf.d/20-tls.ini"),
+            ],
+            ini.includes()
+        );
+        assert_eq!("common", ini.get("server", "host"));
+        assert_eq!("8080", ini.get("server", "port"));
+        assert_eq!("5", ini.get("tls", "timeout"));
+        assert_eq!(vec!["server", "tls"], ini.sections());
+        assert_eq!(
+            Some(directory.join("common.ini").as_path()),
+            ini.option_path("server", "host")
+        );
+        assert_eq!(
+            Some(directory.join("common.ini").as_path()),
+            ini.option_path("tls", "timeout")
+        );
+        assert_eq!(None, ini.option_path("server", "missing"));
 
-        let mut ini = super::IniFile::new();
-        ini.add_section("section1");
         ini.set(
-            "section1".to_string(),
-            "key1".to_string(),
-            "value1".to_string(),
+            "server".to_string(),
+            "host".to_string(),
+            "edited".to_string(),
         );
-        ini.set_path(path.clone());
+        ini.set("tls".to_string(), "cert".to_string(), "a.pem".to_string());
+        ini.remove_option("server".to_string(), "port".to_string());
         ini.save();
+        let read = |name: &str| fs::read_to_string(directory.join(name)).unwrap();
+        assert_eq!(files[0].1, read("main.ini"));
+        assert_eq!("timeout = 5\n[server]\nhost = edited\n", read("common.ini"));
+        assert_eq!("[server]\n", read("conf.d/10-server.ini"));
+        assert_eq!(
+            "[tls]\nenabled = yes\ncert = a.pem\n",
+            read("conf.d/20-tls.ini")
+        );
+        let ini = IniFile::load(&main).unwrap();
+        assert_eq!("edited", ini.get("server", "host"));
+        assert_eq!("80", ini.get("server", "port"));
 
-        let file = File::open(&path);
-        match file {
-            Err(e) => panic!("open of {:?} failed: {}", path, e),
-            _ => debug!("open of {:?} succeeded", path),
+        fs::write(directory.join("common.ini"), "!include main.ini\n").unwrap();
+        match IniFile::load(&main) {
+            Err(IniError::IncludeCycle(chain)) => assert_eq!(3, chain.len()),
+            other => panic!(
+                "IniError::IncludeCycle expected, not {:?}",
+                other.map(|_| ())
+            ),
         }
-        let reader = BufReader::new(file.unwrap());
-        let mut lines: Vec<String> = Vec::new();
-        for line in reader.lines() {
-            match line {
-                Ok(nread) => lines.push(nread + "\n"),
-                Err(e) => println!("error reading: {}", e),
+        fs::write(directory.join("common.ini"), "!include missing.ini\n").unwrap();
+        match IniFile::load(&main) {
+            Err(IniError::Include { path, cause }) => {
+                assert_eq!(directory.join("common.ini"), path);
+                assert!(cause.to_string().contains("missing.ini"), "{}", cause);
+            }
+            other => panic!("IniError::Include expected, not {:?}", other.map(|_| ())),
+        }
+        fs::write(directory.join("common.ini"), "[server\n").unwrap();
+        match IniFile::load(&main) {
+            Err(IniError::Include { cause, .. }) => match *cause {
+                IniError::Syntax(ref diagnostic) if diagnostic.line() == Some(1) => (),
+                other => panic!("IniError::Syntax expected, not {:?}", other),
+            },
+            other => panic!("IniError::Include expected, not {:?}", other.map(|_| ())),
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn encodings() {
+        let path = "src/data/encoding_test.ini";
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "[Caf\u{e9}]\r\nkey=1\r\n".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        fs::write(path, &utf16).unwrap();
+        let mut ini = IniFile::load(path).unwrap();
+        assert_eq!(
+            (Encoding::Utf16Le, true, LineEnding::CrLf),
+            (ini.encoding(), ini.has_bom(), ini.line_ending())
+        );
+        assert_eq!(vec!["Caf\u{e9}"], ini.sections());
+        ini.save();
+        assert_eq!(utf16, fs::read(path).unwrap());
+        ini.set("Caf\u{e9}".to_string(), "key".to_string(), "2".to_string());
+        ini.set_encoding(Encoding::Utf8);
+        ini.set_bom(false);
+        ini.set_line_ending(LineEnding::Lf);
+        ini.save();
+        assert_eq!("[Caf\u{e9}]\nkey=2\n", fs::read_to_string(path).unwrap());
+
+        let ini: IniFile = "\u{feff}[section]\nkey = 1\n".parse().unwrap();
+        assert_eq!(vec!["section"], ini.sections());
+        fs::write(path, b"[section]\nkey = caf\xE9\n").unwrap();
+        match IniFile::load(path) {
+            Err(IniError::Encoding(message)) => assert!(message.contains("byte 19"), "{}", message),
+            other => panic!("IniError::Encoding expected, not {:?}", other.map(|_| ())),
+        }
+        let mut ini = IniFile::new();
+        ini.set_encoding(Encoding::Latin1);
+        ini.read(path);
+        assert_eq!("caf\u{e9}", ini.get("section", "key"));
+        ini.set(
+            "section".to_string(),
+            "key".to_string(),
+            "\u{20ac}".to_string(),
+        );
+        match ini.try_save() {
+            Err(IniError::Encoding(_)) => (),
+            other => panic!("IniError::Encoding expected, not {:?}", other),
+        }
+        ini.set_encoding(Encoding::Windows1252);
+        ini.save();
+        assert_eq!(b"[section]\nkey = \x80\n".to_vec(), fs::read(path).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+    fn read_syntax(syntax: Syntax, text: &str) -> IniResult<IniFile> {
+        let mut ini = IniFile::new();
+        ini.set_syntax(syntax);
+        ini.try_read_string(lines(text))?;
+        Ok(ini)
+    }
+    #[test]
+    fn syntax() {
+        let text = "[server]\nhost: example.org\nport = 80\n";
+        assert!(read_syntax(Syntax::new(), text).is_err());
+        let mut ini = read_syntax(Syntax::python(), text).unwrap();
+        assert_eq!("example.org", ini.get("server", "host"));
+        ini.set_as("server", "host", "localhost");
+        assert_eq!("[server]\nhost: localhost\nport = 80\n", ini.to_string());
+
+        // Lenient by default: sections are merged, the last value wins.
+        let text = "[a]\nkey = 1\n[b]\n[a]\nkey = 2\n";
+        let ini = read_syntax(Syntax::new(), text).unwrap();
+        assert_eq!("2", ini.get("a", "key"));
+        match read_syntax(Syntax::python(), text) {
+            Err(IniError::Syntax(ref diagnostic)) if diagnostic.line() == Some(4) => {
+                assert_eq!("section [a] already defined at line 1", diagnostic.message)
             }
+            other => panic!("IniError::Syntax expected, not {:?}", other.err()),
+        }
+        match read_syntax(Syntax::new().strict(), "k = 1\n[a]\nk = 2\nK = 3\n") {
+            Err(IniError::Syntax(ref diagnostic)) if diagnostic.line() == Some(4) => assert_eq!(
+                "option \"k\" already defined in section [a] at line 3",
+                diagnostic.message
+            ),
+            other => panic!("IniError::Syntax expected, not {:?}", other.err()),
         }
 
-        let found = lines.concat();
-        let expected = "[section1]\nkey1=value1\n".to_string();
-        assert_eq!(expected, found);
-        match fs::remove_file(&path) {
-            Err(e) => panic!("removing {:?} failed: {}", path, e),
-            _ => debug!("removing {:?} succeeded", path),
+        let text = "[mysqld]\nskip-external-locking\nport = 3306 ; default\n";
+        let syntax = Syntax::new()
+            .allow_no_value()
+            .inline_comment_prefixes(&[";"]);
+        let mut ini = read_syntax(syntax, text).unwrap();
+        assert!(ini.has_option("mysqld", "skip-external-locking"));
+        assert_eq!("", ini.get("mysqld", "skip-external-locking"));
+        assert_eq!(vec![""], ini.get_all("mysqld", "skip-external-locking"));
+        assert_eq!(3306, ini.get_int_as::<u16>("mysqld", "port"));
+        ini.set_as("mysqld", "skip-external-locking", 1);
+        assert_eq!(
+            "[mysqld]\nskip-external-locking = 1\nport = 3306 ; default\n",
+            ini.to_string()
+        );
+
+        let ini = read_syntax(Syntax::new().keep_whitespace(), "[a]\nkey =  a b \n").unwrap();
+        assert_eq!(vec!["key "], ini.options("a".to_string()));
+        assert_eq!("  a b ", ini.get("a", "key "));
+
+        let mut ini = IniFile::new();
+        ini.set_syntax(Syntax::new().delimiters(&[':']));
+        ini.add_section("a");
+        ini.set_as("a", "key", "a=b");
+        assert_eq!("[a]\nkey:a=b\n", ini.to_string());
+        ini.normalize();
+        assert_eq!("[a]\nkey : a=b\n", ini.to_string());
+        let ini = read_syntax(Syntax::new().delimiters(&[':']), &ini.to_string()).unwrap();
+        assert_eq!("a=b", ini.get("a", "key"));
+    }
+    #[test]
+    fn comments() {
+        let text = "; Generated file\n\n; Server settings\n[server]\n; TCP port\nPort = 80\nhost = a\n\n; end\n";
+        let mut ini: IniFile = text.parse().unwrap();
+        assert_eq!(Some("Generated file".to_string()), ini.get_header_comment());
+        assert_eq!(Some("end".to_string()), ini.get_footer_comment());
+        assert_eq!(
+            Some("Server settings".to_string()),
+            ini.get_comment("server", None)
+        );
+        assert_eq!(
+            Some("TCP port".to_string()),
+            ini.get_comment("server", Some("PORT"))
+        );
+        assert_eq!(None, ini.get_comment("server", Some("host")));
+        assert_eq!(None, ini.get_comment("missing", None));
+
+        ini.set_comment("server", Some("host"), "Host name\nor address");
+        ini.set_comment("server", Some("port"), "");
+        ini.set("server".to_string(), "timeout".to_string(), "5".to_string());
+        ini.set_comment("server", Some("timeout"), "Seconds");
+        ini.set_header_comment("Edited file");
+        ini.set_footer_comment("");
+        assert_eq!(
+            "; Edited file\n\n; Server settings\n[server]\nPort = 80\n; Host name\n; or address\nhost = a\n; Seconds\ntimeout = 5\n",
+            ini.to_string()
+        );
+        let read: IniFile = ini.to_string().parse().unwrap();
+        assert_eq!(
+            Some("Host name\nor address".to_string()),
+            read.get_comment("server", Some("host"))
+        );
+        match ini.try_set_comment("server", Some("missing"), "x") {
+            Err(IniError::MissingOption { option, .. }) => assert_eq!("missing", option),
+            other => panic!("IniError::MissingOption expected, not {:?}", other),
+        }
+        match ini.try_set_comment("missing", None, "x") {
+            Err(IniError::MissingSection(section)) => assert_eq!("missing", section),
+            other => panic!("IniError::MissingSection expected, not {:?}", other),
+        }
+
+        let mut ini = IniFile::new();
+        ini.set_dialect(Dialect::Windows);
+        ini.add_section("a");
+        ini.set_as("a", "k", 1);
+        ini.set_comment("a", None, "section a");
+        assert_eq!("; section a\n[a]\nk=1\n", ini.to_string());
+        match ini.try_set_inline_comment("a", "k", "one") {
+            Err(IniError::InvalidComment(_)) => (),
+            other => panic!("IniError::InvalidComment expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn inline_comments() {
+        let text = "[mysqld]\nport = 3306 // default\nuser = mysql\n";
+        let syntax = Syntax::new().inline_comment_prefixes(&[";", "//"]);
+        let mut ini = read_syntax(syntax.clone(), text).unwrap();
+        assert_eq!(
+            Some("default".to_string()),
+            ini.get_inline_comment("mysqld", "port")
+        );
+        assert_eq!(None, ini.get_inline_comment("mysqld", "user"));
+        ini.set_inline_comment("mysqld", "port", "changed");
+        ini.set_inline_comment("mysqld", "user", "system user");
+        assert_eq!(
+            "[mysqld]\nport = 3306 // changed\nuser = mysql ; system user\n",
+            ini.to_string()
+        );
+        let read = read_syntax(syntax, &ini.to_string()).unwrap();
+        assert_eq!("mysql", read.get("mysqld", "user"));
+        assert_eq!(
+            Some("system user".to_string()),
+            read.get_inline_comment("mysqld", "user")
+        );
+        assert!(ini
+            .try_set_inline_comment("mysqld", "port", "a\nb")
+            .is_err());
+        assert!(ini
+            .try_set_inline_comment("mysqld", "missing", "a")
+            .is_err());
+
+        let mut ini = read_dialect(Dialect::Git, "[core]\n\tbare = false ; not yet\n").unwrap();
+        assert_eq!(
+            Some("not yet".to_string()),
+            ini.get_inline_comment("core", "bare")
+        );
+        ini.set_inline_comment("core", "bare", "");
+        ini.set_comment("core", Some("bare"), "Repository without work tree");
+        assert_eq!(
+            "[core]\n\t# Repository without work tree\n\tbare = false\n",
+            ini.to_string()
+        );
+    }
+    #[test]
+    fn diagnostics() {
+        let filepath = "src/data/diagnostics_test.ini";
+        fs::write(filepath, "[server]\r\nhost = \"local\r\n").unwrap();
+        let mut ini = IniFile::new();
+        ini.set_dialect(Dialect::Git);
+        let result = ini.try_read(filepath);
+        fs::remove_file(filepath).unwrap();
+        let diagnostics = result.unwrap_err().diagnostics();
+        assert_eq!(1, diagnostics.len());
+        let location = diagnostics[0].location.clone().unwrap();
+        assert_eq!(Path::new(filepath), location.path);
+        assert_eq!((2, 8), (location.line, location.column));
+        assert_eq!("host = \"local", location.source_line);
+        assert!(diagnostics[0].help.is_some());
+        assert!(diagnostics[0]
+            .render()
+            .contains("--> src/data/diagnostics_test.ini:2:8\n"));
+
+        let text = "[a]\nkey = 1\n[b]\nkey  =  2\n";
+        match read_syntax(Syntax::python(), &text.replace("[b]", "[a]")) {
+            Err(e) => {
+                let location = e.diagnostics()[0].location.clone().unwrap();
+                assert_eq!(
+                    (3, 1, 12..15),
+                    (location.line, location.column, location.span)
+                );
+            }
+            Ok(_) => panic!("IniError::Syntax expected"),
+        }
+        let ini = read_syntax(Syntax::new(), text).unwrap();
+        let location = ini.location("b", Some("key"), true).unwrap();
+        assert_eq!(
+            (4, 9, 24..25),
+            (location.line, location.column, location.span)
+        );
+        let location = ini.location("b", Some("key"), false).unwrap();
+        assert_eq!(
+            (4, 1, 16..19),
+            (location.line, location.column, location.span)
+        );
+        assert_eq!(None, ini.location("c", None, false));
+        assert_eq!(
+            vec![Diagnostic::error(
+                "section [c] does not exist".to_string(),
+                None
+            )],
+            IniError::MissingSection("c".to_string()).diagnostics()
+        );
+    }
+    /**
+     * Sections and options of a configuration, with their raw values, as the getters see them.
+     */
+    fn contents(ini: &IniFile) -> Vec<(String, Vec<(String, String)>)> {
+        let mut sections = vec![ini.default_section()];
+        sections.extend(ini.sections());
+        sections
+            .into_iter()
+            .map(|section| {
+                let options = ini
+                    .options(section.clone())
+                    .into_iter()
+                    .map(|option| {
+                        let value = ini.get_raw(&section, &option);
+                        (option, value)
+                    })
+                    .collect();
+                (section, options)
+            })
+            .collect()
+    }
+    #[test]
+    fn matches_reference() {
+        for seed in 1..40 {
+            let mut random = Random::new(seed);
+            let mut ini = IniFile::new();
+            // Naive model: own options of the default section first, searched linearly.
+            let mut reference: Vec<(String, Vec<(String, String)>)> =
+                vec![("DEFAULT".to_string(), Vec::new())];
+            for step in 0..150 {
+                let section = match random.below(5) {
+                    0 => "DEFAULT".to_string(),
+                    n => format!("s{}", n),
+                };
+                let spelling = format!("{}{}", ["k", "K"][random.below(2)], random.below(6));
+                let option = spelling.to_lowercase();
+                let position = reference.iter().position(|(name, _)| *name == section);
+                match (random.below(6), position) {
+                    (0, None) => {
+                        ini.add_section(&section);
+                        reference.push((section, Vec::new()));
+                    }
+                    (1, Some(position)) => {
+                        let removed = ini.remove_option(section, spelling);
+                        let options = &mut reference[position].1;
+                        let index = options.iter().position(|(key, _)| *key == option);
+                        assert_eq!(index.is_some(), removed, "seed {}", seed);
+                        if let Some(index) = index {
+                            options.remove(index);
+                        }
+                    }
+                    (2, Some(position)) if position > 0 => {
+                        assert!(ini.remove_section(section), "seed {}", seed);
+                        reference.remove(position);
+                    }
+                    (_, Some(position)) => {
+                        let value = format!("v{}", step);
+                        ini.set(section, spelling, value.clone());
+                        let options = &mut reference[position].1;
+                        match options.iter_mut().find(|(key, _)| *key == option) {
+                            Some(entry) => entry.1 = value,
+                            None => options.push((option, value)),
+                        }
+                    }
+                    (_, None) => assert!(ini.try_set(section, spelling, String::new()).is_err()),
+                }
+                let defaults = reference[0].1.clone();
+                let expected: Vec<(String, Vec<(String, String)>)> = reference
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (section, options))| {
+                        let mut options = options.clone();
+                        for (key, value) in defaults.iter() {
+                            if index > 0 && !options.iter().any(|(k, _)| k == key) {
+                                options.push((key.clone(), value.clone()));
+                            }
+                        }
+                        (section.clone(), options)
+                    })
+                    .collect();
+                assert_eq!(expected, contents(&ini), "seed {}, step {}", seed, step);
+                let reread: IniFile = ini.to_string().parse().unwrap();
+                assert_eq!(expected, contents(&reread), "seed {}, step {}", seed, step);
+            }
         }
     }
 }
@@ -0,0 +1,176 @@
+//! Ordered storage of the sections and options of an `IniFile`: one map from section names to maps from
+//! option names to values, both keeping the order in which their keys were inserted.
+//!
+//! Lookups are O(1); removing a key keeps the order of the others. Each value records the file defining
+//! it, the file itself or one of the files it includes.
+
+use std::collections::HashMap;
+
+/**
+ * Map keeping the insertion order of its keys. Replacing the value of a key keeps its position.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct OrderedMap<V> {
+    entries: Vec<(String, V)>,
+    /// Position of each key in `entries`
+    index: HashMap<String, usize>,
+}
+
+impl<V> OrderedMap<V> {
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.index
+            .get(key)
+            .map(|&position| &self.entries[position].1)
+    }
+    /**
+     * Key as stored, and its value.
+     */
+    pub fn get_key_value(&self, key: &str) -> Option<(&str, &V)> {
+        self.index.get(key).map(|&position| {
+            let (ref key, ref value) = self.entries[position];
+            (key.as_str(), value)
+        })
+    }
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        match self.index.get(key) {
+            Some(&position) => Some(&mut self.entries[position].1),
+            None => None,
+        }
+    }
+    /**
+     * Set the value of a key, appended if it is new; return the former value.
+     */
+    pub fn insert(&mut self, key: String, value: V) -> Option<V> {
+        if let Some(&position) = self.index.get(&key) {
+            return Some(::std::mem::replace(&mut self.entries[position].1, value));
+        }
+        self.index.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+    /**
+     * Keys and values, in order.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+    /**
+     * Keys, in order.
+     */
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(key, _)| key.as_str())
+    }
+    pub fn new() -> OrderedMap<V> {
+        OrderedMap {
+            entries: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+    /**
+     * Remove a key, keeping the order of the others, and return its value.
+     */
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let position = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (offset, (key, _)) in self.entries[position..].iter().enumerate() {
+            // Every key of the entries is indexed.
+            *self.index.get_mut(key).unwrap() = position + offset;
+        }
+        Some(value)
+    }
+}
+
+/**
+ * Value of an option, and the file defining it: 0 for the file itself, `n` for the `n`-th included file.
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct Value {
+    pub file: usize,
+    pub text: String,
+}
+
+/**
+ * Pseudo-random numbers for the randomized tests, reproducible from their seed (xorshift64).
+ */
+#[cfg(test)]
+pub(crate) struct Random(u64);
+
+#[cfg(test)]
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random(seed.max(1))
+    }
+    /**
+     * Number lower than `bound`.
+     */
+    pub fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrderedMap, Random};
+
+    #[test]
+    fn insert_and_remove() {
+        let mut map = OrderedMap::new();
+        assert_eq!(None, map.insert("b".to_string(), 1));
+        assert_eq!(None, map.insert("a".to_string(), 2));
+        assert_eq!(None, map.insert("c".to_string(), 3));
+        assert_eq!(Some(1), map.insert("b".to_string(), 4));
+        assert_eq!(vec!["b", "a", "c"], map.keys().collect::<Vec<_>>());
+        assert_eq!(Some(4), map.remove("b"));
+        assert_eq!(None, map.remove("b"));
+        assert_eq!(Some(&3), map.get("c"));
+        *map.get_mut("c").unwrap() += 1;
+        assert_eq!(vec![("a", &2), ("c", &4)], map.iter().collect::<Vec<_>>());
+        map.insert("b".to_string(), 5);
+        assert_eq!(vec!["a", "c", "b"], map.keys().collect::<Vec<_>>());
+        assert_eq!(Some(("b", &5)), map.get_key_value("b"));
+    }
+    #[test]
+    fn matches_reference() {
+        for seed in 1..50 {
+            let mut random = Random::new(seed);
+            let mut map = OrderedMap::new();
+            // Naive model: a vector of pairs, searched linearly.
+            let mut reference: Vec<(String, usize)> = Vec::new();
+            for step in 0..200 {
+                let key = format!("k{}", random.below(12));
+                if random.below(3) == 0 {
+                    let position = reference.iter().position(|(k, _)| *k == key);
+                    let expected = position.map(|position| reference.remove(position).1);
+                    assert_eq!(expected, map.remove(&key), "seed {}", seed);
+                } else {
+                    let expected = match reference.iter_mut().find(|(k, _)| *k == key) {
+                        Some(entry) => Some(::std::mem::replace(&mut entry.1, step)),
+                        None => {
+                            reference.push((key.clone(), step));
+                            None
+                        }
+                    };
+                    assert_eq!(expected, map.insert(key, step), "seed {}", seed);
+                }
+                let entries: Vec<(&str, usize)> =
+                    map.iter().map(|(key, &value)| (key, value)).collect();
+                let expected: Vec<(&str, usize)> = reference
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), *value))
+                    .collect();
+                assert_eq!(expected, entries, "seed {}", seed);
+                for (key, value) in reference.iter() {
+                    assert_eq!(Some(value), map.get(key), "seed {}", seed);
+                }
+            }
+        }
+    }
+}
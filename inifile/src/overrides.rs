@@ -0,0 +1,302 @@
+//! Overrides of single values from the environment and the command line, without rewriting the file.
+//!
+//! `APP__SERVER__PORT=8080` and `--set server.port=8080` both override option `port` of section
+//! `server`; `APP__TIMEOUT=5` and `--set timeout=5` override an option of the default section.
+//! Overridden values are returned by the getters of `IniFile`, but they are not written by `save()`.
+
+use std::env;
+
+use error::{IniError, IniResult};
+use IniFile;
+
+/**
+ * How the section names of the overrides are matched against the sections of the file.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    /// Section names are used as written.
+    Preserve,
+    /// Section names are lowercased.
+    Lower,
+    /// Section names are uppercased.
+    Upper,
+    /// An existing section is matched ignoring case; otherwise the name is lowercased.
+    Insensitive,
+}
+
+/**
+ * A value overriding the one of the file.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Override {
+    pub section: String,
+    /// Normalized option name.
+    pub option: String,
+    pub value: String,
+    /// Where the override comes from, such as `env APP__SERVER__PORT` or `--set server.port=8080`.
+    pub source: String,
+}
+
+/**
+ * Rules mapping environment variables and command line arguments onto sections and options.
+ */
+#[derive(Clone, Debug)]
+pub struct Overrides {
+    case: Case,
+    prefix: String,
+    separator: String,
+}
+
+impl Overrides {
+    /**
+     * Override values from every `--set section.option=value` (or `--set=section.option=value`) argument.
+     * The section is what precedes the last `.` of the name; without `.`, the option belongs to the
+     * default section. Return the other arguments.
+     */
+    pub fn apply_args<I>(&self, ini: &mut IniFile, args: I) -> IniResult<Vec<String>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut others = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let assignment = if arg == "--set" {
+                match args.next() {
+                    Some(assignment) => assignment,
+                    None => {
+                        return Err(IniError::InvalidOverride(
+                            "--set expects section.option=value".to_string(),
+                        ))
+                    }
+                }
+            } else if let Some(assignment) = arg.strip_prefix("--set=") {
+                assignment.to_string()
+            } else {
+                others.push(arg);
+                continue;
+            };
+            let (name, value) = match assignment.find('=') {
+                Some(index) => (&assignment[..index], &assignment[index + 1..]),
+                None => {
+                    return Err(IniError::InvalidOverride(format!(
+                        "--set expects section.option=value, found {:?}",
+                        assignment
+                    )))
+                }
+            };
+            let (section, option) = match name.rfind('.') {
+                Some(index) => (self.section(ini, &name[..index]), &name[index + 1..]),
+                None => (ini.default_section(), name),
+            };
+            if option.is_empty() {
+                return Err(IniError::InvalidOverride(format!(
+                    "missing option name in {:?}",
+                    assignment
+                )));
+            }
+            let source = format!("--set {}", assignment);
+            ini.set_override(&section, option, value, &source);
+        }
+        Ok(others)
+    }
+    /**
+     * Override values from the environment variables starting with the prefix and the separator.
+     * Return the number of overridden values.
+     */
+    pub fn apply_env(&self, ini: &mut IniFile) -> usize {
+        self.apply_vars(ini, env::vars())
+    }
+    /**
+     * Override values from `(name, value)` pairs, as `apply_env()` does with the environment variables.
+     * `PREFIX__SECTION__OPTION` overrides an option of a section, `PREFIX__OPTION` one of the default
+     * section; the option name may contain the separator.
+     */
+    pub fn apply_vars<I>(&self, ini: &mut IniFile, vars: I) -> usize
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let start = format!("{}{}", self.prefix, self.separator);
+        let mut count = 0;
+        for (name, value) in vars {
+            if !name.starts_with(&start) {
+                continue;
+            }
+            let path = &name[start.len()..];
+            let (section, option) = match path.find(&self.separator) {
+                Some(index) => (
+                    self.section(ini, &path[..index]),
+                    &path[index + self.separator.len()..],
+                ),
+                None => (ini.default_section(), path),
+            };
+            if section.is_empty() || option.is_empty() {
+                continue;
+            }
+            ini.set_override(&section, option, &value, &format!("env {}", name));
+            count += 1;
+        }
+        count
+    }
+    /**
+     * Rules for the given prefix, such as `APP`: `__` separator, `Case::Insensitive` section names.
+     */
+    pub fn new(prefix: &str) -> Overrides {
+        Overrides {
+            case: Case::Insensitive,
+            prefix: prefix.to_string(),
+            separator: "__".to_string(),
+        }
+    }
+    /**
+     * Redefine how section names are matched.
+     */
+    pub fn set_case(&mut self, case: Case) {
+        self.case = case;
+    }
+    /**
+     * Redefine the prefix of the environment variables.
+     */
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+    }
+    /**
+     * Redefine the separator between the prefix, the section and the option of the environment variables.
+     */
+    pub fn set_separator(&mut self, separator: &str) {
+        self.separator = separator.to_string();
+    }
+    /**
+     * Section of the file designated by a name, according to the case rule.
+     */
+    fn section(&self, ini: &IniFile, name: &str) -> String {
+        match self.case {
+            Case::Preserve => name.to_string(),
+            Case::Lower => name.to_lowercase(),
+            Case::Upper => name.to_uppercase(),
+            Case::Insensitive => {
+                let mut sections = ini.sections();
+                sections.push(ini.default_section());
+                match sections
+                    .into_iter()
+                    .find(|section| section.to_lowercase() == name.to_lowercase())
+                {
+                    Some(section) => section,
+                    None => name.to_lowercase(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Case, Overrides};
+    use IniError;
+    use IniFile;
+
+    fn ini() -> IniFile {
+        "timeout = 10\n\n[Server]\nhost = example.org\nport = 80\n"
+            .parse()
+            .unwrap()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env() {
+        let mut ini = ini();
+        let overrides = Overrides::new("APP");
+        let count = overrides.apply_vars(
+            &mut ini,
+            vars(&[
+                ("APP__SERVER__PORT", "8080"),
+                ("APP__TIMEOUT", "5"),
+                ("APP__CACHE__MAX_SIZE", "10"),
+                ("OTHER__SERVER__HOST", "ignored"),
+                ("PATH", "/bin"),
+            ]),
+        );
+        assert_eq!(3, count);
+        assert_eq!("8080", ini.get("Server", "port"));
+        assert_eq!(8080, ini.get_int("Server", "port"));
+        assert_eq!("5", ini.get("Server", "timeout"));
+        assert_eq!("example.org", ini.get("Server", "host"));
+        assert!(ini.has_section("cache"));
+        assert_eq!(
+            vec!["max_size", "timeout"],
+            ini.options("cache".to_string())
+        );
+        assert!(ini.is_overridden("Server", "PORT"));
+        assert!(!ini.is_overridden("Server", "host"));
+        // The file is unchanged.
+        assert_eq!(
+            "timeout = 10\n\n[Server]\nhost = example.org\nport = 80\n",
+            ini.to_string()
+        );
+    }
+    #[test]
+    fn env_rules() {
+        let mut ini = ini();
+        let mut overrides = Overrides::new("my");
+        overrides.set_separator("_");
+        overrides.set_case(Case::Preserve);
+        overrides.apply_vars(
+            &mut ini,
+            vars(&[("my_SERVER_port", "1"), ("my_Server_port", "2")]),
+        );
+        assert_eq!("2", ini.get("Server", "port"));
+        assert!(ini.has_section("SERVER"));
+        overrides.set_prefix("APP");
+        overrides.set_case(Case::Upper);
+        overrides.apply_vars(&mut ini, vars(&[("APP_server_host", "h")]));
+        assert_eq!("h", ini.get("SERVER", "host"));
+    }
+    #[test]
+    fn args() {
+        let mut ini = ini();
+        let args = vec![
+            "run",
+            "--set",
+            "server.port=8080",
+            "-v",
+            "--set=timeout=1=2",
+        ];
+        let others = Overrides::new("APP")
+            .apply_args(&mut ini, args.into_iter().map(|arg| arg.to_string()))
+            .unwrap();
+        assert_eq!(vec!["run", "-v"], others);
+        assert_eq!("8080", ini.get("Server", "port"));
+        assert_eq!("1=2", ini.get("Server", "timeout"));
+        for args in [
+            vec!["--set"],
+            vec!["--set", "server.port"],
+            vec!["--set=server.=1"],
+        ]
+        .iter()
+        {
+            let args = args.iter().map(|arg| arg.to_string());
+            match Overrides::new("APP").apply_args(&mut ini, args) {
+                Err(IniError::InvalidOverride(_)) => (),
+                other => panic!("IniError::InvalidOverride expected, not {:?}", other),
+            }
+        }
+    }
+    #[test]
+    fn dump() {
+        let mut ini = ini();
+        ini.set_override("Server", "Port", "8080", "--set Server.Port=8080");
+        ini.set("Server".to_string(), "port".to_string(), "81".to_string());
+        assert_eq!("8080", ini.get("Server", "port"));
+        assert_eq!(
+            "[DEFAULT]\ntimeout = 10\n\n[Server]\nhost = example.org\nport = 8080 ; overridden by --set Server.Port=8080\n",
+            ini.dump()
+        );
+        ini.clear_overrides();
+        assert_eq!("81", ini.get("Server", "port"));
+        assert!(ini.overrides().is_empty());
+    }
+}
@@ -0,0 +1,659 @@
+//! Pull parser yielding the lines of an INI file as events, without building a configuration.
+//!
+//! `Parser` reads a string and its events borrow from it; `Reader` reads from a `BufRead`, one line at a
+//! time, to scan files too large to be kept in memory. Neither allocates per line, but for the names of
+//! git-config sections which need to be rewritten, such as `[remote "origin"]`.
+//!
+//! Spans are byte offsets in the input, line ending excluded. Values are raw: not decoded, as written
+//! in the file, without the inline comment.
+//!
+//! The dialect tells the structure of the file; a `Syntax` may add delimiters, keys without value and
+//! inline comments, see `Parser::with_syntax()`.
+
+use std::borrow::Cow;
+use std::io::BufRead;
+use std::ops::Range;
+
+use diagnostic::{Diagnostic, Location};
+use dialect::Dialect;
+use error::{IniError, IniResult};
+use syntax::Syntax;
+
+/**
+ * A line of an INI file.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    /// Section header, `name` being the name of the section.
+    SectionStart {
+        name: Cow<'a, str>,
+        span: Range<usize>,
+    },
+    /// Option definition: trimmed key as written, raw value, and the span of the value in the input.
+    /// The value is `None` for a key without delimiter; its span is then empty, after the key.
+    KeyValue {
+        key: &'a str,
+        value: Option<&'a str>,
+        span: Range<usize>,
+        value_span: Range<usize>,
+    },
+    /// Trimmed line continuing the value of the previous option. In Python files, it may follow `Blank`
+    /// and `Comment` events: the blank lines are then part of the value, the comments are not.
+    Continuation { value: &'a str, span: Range<usize> },
+    /// Comment, or a line ignored by the dialect.
+    Comment { text: &'a str, span: Range<usize> },
+    /// `!include path` or `!includedir directory` line: `name` is `include` or `includedir`, `argument`
+    /// the trimmed path.
+    Directive {
+        name: &'a str,
+        argument: &'a str,
+        span: Range<usize>,
+    },
+    /// Blank line.
+    Blank { span: Range<usize> },
+}
+
+impl<'a> Event<'a> {
+    /**
+     * Span of the line in the input.
+     */
+    pub fn span(&self) -> Range<usize> {
+        match *self {
+            Event::SectionStart { ref span, .. }
+            | Event::KeyValue { ref span, .. }
+            | Event::Continuation { ref span, .. }
+            | Event::Comment { ref span, .. }
+            | Event::Directive { ref span, .. }
+            | Event::Blank { ref span } => span.clone(),
+        }
+    }
+}
+
+/**
+ * What is known of the previous lines to parse the next one.
+ */
+struct State {
+    dialect: Dialect,
+    /// Indentation of the last key, whose value deeper indented lines continue
+    indent: Option<usize>,
+    /// Whether the last value ends with a backslash continuing it
+    continued: bool,
+    /// Whether a syntax error stopped the parsing
+    failed: bool,
+    /// Number of the last line read, starting at 1
+    line: usize,
+    syntax: Syntax,
+}
+
+impl State {
+    fn new(dialect: Dialect, syntax: Syntax) -> State {
+        State {
+            dialect,
+            indent: None,
+            continued: false,
+            failed: false,
+            line: 0,
+            syntax,
+        }
+    }
+    /**
+     * Event of a line starting at offset `start`, the line ending removed.
+     */
+    fn event<'b>(&mut self, line: &'b str, start: usize) -> IniResult<Event<'b>> {
+        let dialect = self.dialect;
+        let span = start..start + line.len();
+        if self.continued {
+            self.continued = dialect.continues(line);
+            return Ok(Event::Continuation {
+                value: line.trim(),
+                span,
+            });
+        }
+        if line.trim().is_empty() {
+            return Ok(Event::Blank { span });
+        }
+        let indent = line.len() - line.trim_start().len();
+        let (text, text_start) = if dialect.indented_lines() {
+            (line.trim(), indent)
+        } else {
+            (line, 0)
+        };
+        // As in Python, a comment line, indented or not, does not end a multi-line value.
+        if line.trim_start().starts_with(['#', ';']) {
+            return Ok(Event::Comment { text, span });
+        }
+        if let Some(key_indent) = self.indent {
+            if indent > key_indent {
+                let end = self.syntax.comment_start(line).unwrap_or(line.len());
+                return Ok(Event::Continuation {
+                    value: line[..end].trim(),
+                    span,
+                });
+            }
+        }
+        self.indent = None;
+        if let Some(directive) = text.strip_prefix('!') {
+            let end = directive
+                .find(char::is_whitespace)
+                .unwrap_or(directive.len());
+            let name = &directive[..end];
+            if name == "include" || name == "includedir" {
+                let argument = directive[end..].trim();
+                if argument.is_empty() {
+                    let example = if name == "include" {
+                        "common.ini"
+                    } else {
+                        "conf.d/"
+                    };
+                    return Err(self.error(
+                        line,
+                        start,
+                        text_start..text_start + 1 + end,
+                        format!("!{} expects a path", name),
+                        format!(
+                            "write the path after the directive, as `!{} {}`",
+                            name, example
+                        ),
+                    ));
+                }
+                return Ok(Event::Directive {
+                    name,
+                    argument,
+                    span,
+                });
+            }
+        }
+        if text.starts_with('[') {
+            let header = match self.syntax.comment_start(text) {
+                Some(end) => text[..end].trim_end(),
+                None => text,
+            };
+            let help = match dialect {
+                Dialect::Git => {
+                    "write section headers as `[section]` or `[section \"subsection\"]`"
+                }
+                _ => "write section headers as `[name]`",
+            };
+            let name = dialect.section_name(header).map_err(|message| {
+                let header_span = text_start..text_start + header.len();
+                self.error(line, start, header_span, message, help.to_string())
+            })?;
+            return Ok(Event::SectionStart { name, span });
+        }
+        let end = match self.syntax.comment_start(line) {
+            Some(comment) => dialect.value_length(line).min(comment),
+            None => dialect.value_length(line),
+        };
+        let syntax = &self.syntax;
+        // The indentation may be made of delimiters, as whitespace.
+        let key_start = indent.min(end);
+        let delimiter = syntax
+            .find_delimiter(&line[key_start..end])
+            .map(|delimiter| key_start + delimiter.start..key_start + delimiter.end);
+        match delimiter {
+            Some(delimiter) => {
+                let key = &line[key_start..delimiter.start];
+                if key.trim().is_empty() {
+                    if dialect.ignores_invalid_lines() {
+                        return Ok(Event::Comment { text, span });
+                    }
+                    let message = "option name must not be empty".to_string();
+                    let help = format!(
+                        "write the name of the option before `{}`",
+                        &line[delimiter.clone()]
+                    );
+                    return Err(self.error(line, start, delimiter, message, help));
+                }
+                let after = &line[delimiter.end..end];
+                let trimmed = syntax.trimmed(after);
+                let value_start = delimiter.end + trimmed.start;
+                let value = &after[trimmed];
+                self.continued = dialect.continues(value);
+                if !dialect.indented_lines() {
+                    self.indent = Some(indent);
+                }
+                Ok(Event::KeyValue {
+                    key: &key[syntax.trimmed(key)],
+                    value: Some(value),
+                    span,
+                    value_span: start + value_start..start + value_start + value.len(),
+                })
+            }
+            None if dialect.keys_without_value() || syntax.has_no_value() => {
+                let key = &line[key_start..end];
+                Ok(Event::KeyValue {
+                    key: &key[syntax.trimmed(key)],
+                    value: None,
+                    span,
+                    value_span: start + end..start + end,
+                })
+            }
+            None if dialect.ignores_invalid_lines() => Ok(Event::Comment { text, span }),
+            None => {
+                let delimiter = syntax.delimiter();
+                let message = format!("expected \"key{}value\", found {:?}", delimiter, line);
+                let help = format!(
+                    "write the option as `key{}value`, or start a comment with `;` or `#`",
+                    delimiter
+                );
+                let text_span = indent..line.trim_end().len();
+                Err(self.error(line, start, text_span, message, help))
+            }
+        }
+    }
+    /**
+     * Syntax error on the last line, starting at offset `start`, the problem being at `span` in the line;
+     * the parsing stops.
+     */
+    fn error(
+        &mut self,
+        line: &str,
+        start: usize,
+        span: Range<usize>,
+        message: String,
+        help: String,
+    ) -> IniError {
+        self.failed = true;
+        let span = start + span.start..start + span.end;
+        let location = Location::in_line(self.line, line, start, span);
+        IniError::Syntax(Box::new(
+            Diagnostic::error(message, Some(location)).with_help(help),
+        ))
+    }
+}
+
+/**
+ * Line without its line ending, `\n` or `\r\n`.
+ */
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/**
+ * Events of an INI text, borrowing from it. Iteration stops after a syntax error.
+ */
+pub struct Parser<'a> {
+    /// Offset of the next line
+    position: usize,
+    state: State,
+    text: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /**
+     * Number of the line of the last event, or of the syntax error, starting at 1.
+     */
+    pub fn line(&self) -> usize {
+        self.state.line
+    }
+    /**
+     * Parser of a text written in the given dialect.
+     */
+    pub fn new(text: &'a str, dialect: Dialect) -> Parser<'a> {
+        Parser::with_syntax(text, dialect, Syntax::new())
+    }
+    /**
+     * Parser of a text written in the given dialect, with syntax options. Duplicates are left to the
+     * caller: `Syntax::strict()` only applies to `IniFile`.
+     */
+    pub fn with_syntax(text: &'a str, dialect: Dialect, syntax: Syntax) -> Parser<'a> {
+        Parser {
+            position: 0,
+            state: State::new(dialect, syntax),
+            text,
+        }
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = IniResult<Event<'a>>;
+
+    fn next(&mut self) -> Option<IniResult<Event<'a>>> {
+        if self.state.failed || self.position >= self.text.len() {
+            return None;
+        }
+        let text = self.text;
+        let start = self.position;
+        let end = match text[start..].find('\n') {
+            Some(index) => start + index + 1,
+            None => text.len(),
+        };
+        self.position = end;
+        self.state.line += 1;
+        Some(self.state.event(trim_line_ending(&text[start..end]), start))
+    }
+}
+
+/**
+ * Events of an INI file read line by line, the line being kept in a buffer reused for each line:
+ * an event borrows from the reader until the next call to `next_event()`.
+ */
+pub struct Reader<R: BufRead> {
+    buffer: String,
+    /// Offset of the next line
+    position: usize,
+    reader: R,
+    state: State,
+}
+
+impl<R: BufRead> Reader<R> {
+    /**
+     * Number of the line of the last event, or of the error, starting at 1.
+     */
+    pub fn line(&self) -> usize {
+        self.state.line
+    }
+    /**
+     * Reader of a file written in the given dialect.
+     */
+    pub fn new(reader: R, dialect: Dialect) -> Reader<R> {
+        Reader::with_syntax(reader, dialect, Syntax::new())
+    }
+    /**
+     * Read the next line and return its event; `None` at the end of the file or after an error.
+     */
+    pub fn next_event(&mut self) -> Option<IniResult<Event<'_>>> {
+        if self.state.failed {
+            return None;
+        }
+        self.buffer.clear();
+        match self.reader.read_line(&mut self.buffer) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => {
+                self.state.failed = true;
+                return Some(Err(IniError::from(e)));
+            }
+        }
+        let start = self.position;
+        self.position += self.buffer.len();
+        self.state.line += 1;
+        Some(self.state.event(trim_line_ending(&self.buffer), start))
+    }
+    /**
+     * Reader of a file written in the given dialect, with syntax options, see `Parser::with_syntax()`.
+     */
+    pub fn with_syntax(reader: R, dialect: Dialect, syntax: Syntax) -> Reader<R> {
+        Reader {
+            buffer: String::new(),
+            position: 0,
+            reader,
+            state: State::new(dialect, syntax),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Parser, Reader};
+    use dialect::Dialect;
+    use syntax::Syntax;
+    use IniError;
+
+    #[test]
+    fn events() {
+        let text = "; top\n[a]\nkey = value ; not a comment\r\n  more\n\n  end\nempty=\n";
+        let events: Vec<Event> = Parser::new(text, Dialect::Python)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Event::Comment {
+                    text: "; top",
+                    span: 0..5
+                },
+                Event::SectionStart {
+                    name: "a".into(),
+                    span: 6..9
+                },
+                Event::KeyValue {
+                    key: "key",
+                    value: Some("value ; not a comment"),
+                    span: 10..37,
+                    value_span: 16..37
+                },
+                Event::Continuation {
+                    value: "more",
+                    span: 39..45
+                },
+                Event::Blank { span: 46..46 },
+                Event::Continuation {
+                    value: "end",
+                    span: 47..52
+                },
+                Event::KeyValue {
+                    key: "empty",
+                    value: Some(""),
+                    span: 53..59,
+                    value_span: 59..59
+                },
+            ],
+            events
+        );
+        assert_eq!("key = value ; not a comment", &text[events[2].span()]);
+    }
+    #[test]
+    fn comments_in_values() {
+        let text = "[a]\nkey = v\n  # c\n; d\n  more\n";
+        let events: Vec<Event> = Parser::new(text, Dialect::Python)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Event::Comment {
+                    text: "  # c",
+                    span: 12..17
+                },
+                Event::Comment {
+                    text: "; d",
+                    span: 18..21
+                },
+                Event::Continuation {
+                    value: "more",
+                    span: 22..28
+                },
+            ],
+            events[2..].to_vec()
+        );
+    }
+    #[test]
+    fn git_events() {
+        let text = "[Remote \"origin\"]\n\tfetch = a \\\n\t  b # comment\n\tbare # comment\n";
+        let events: Vec<Event> = Parser::new(text, Dialect::Git)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            Event::SectionStart {
+                name: "remote.origin".into(),
+                span: 0..17
+            },
+            events[0]
+        );
+        assert_eq!(
+            Event::Continuation {
+                value: "b # comment",
+                span: 31..45
+            },
+            events[2]
+        );
+        assert_eq!(
+            Event::KeyValue {
+                key: "bare",
+                value: None,
+                span: 46..61,
+                value_span: 51..51
+            },
+            events[3]
+        );
+    }
+    #[test]
+    fn directives() {
+        let text = "!include  common.ini \n[a]\n  !includedir conf.d/\n!includes = 1\n";
+        let events: Vec<Event> = Parser::new(text, Dialect::Windows)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            Event::Directive {
+                name: "include",
+                argument: "common.ini",
+                span: 0..21
+            },
+            events[0]
+        );
+        assert_eq!(
+            Event::Directive {
+                name: "includedir",
+                argument: "conf.d/",
+                span: 26..47
+            },
+            events[2]
+        );
+        match events[3] {
+            Event::KeyValue {
+                key: "!includes", ..
+            } => (),
+            ref other => panic!("Event::KeyValue expected, not {:?}", other),
+        }
+        let mut parser = Parser::new("!include\n", Dialect::Python);
+        assert!(parser.next().unwrap().is_err());
+    }
+    #[test]
+    fn syntax_options() {
+        let text = "[a] ; header\nkey: value ; comment\nbare\nmore // x\nurl = http://a;b\n";
+        let syntax = Syntax::python()
+            .allow_no_value()
+            .inline_comment_prefixes(&[";", "//"]);
+        let events: Vec<Event> = Parser::with_syntax(text, Dialect::Python, syntax)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Event::SectionStart {
+                    name: "a".into(),
+                    span: 0..12
+                },
+                Event::KeyValue {
+                    key: "key",
+                    value: Some("value"),
+                    span: 13..33,
+                    value_span: 18..23
+                },
+                Event::KeyValue {
+                    key: "bare",
+                    value: None,
+                    span: 34..38,
+                    value_span: 38..38
+                },
+                Event::KeyValue {
+                    key: "more",
+                    value: None,
+                    span: 39..48,
+                    value_span: 44..44
+                },
+                Event::KeyValue {
+                    key: "url",
+                    value: Some("http://a;b"),
+                    span: 49..65,
+                    value_span: 55..65
+                },
+            ],
+            events
+        );
+        let text = "[a]\n key = value \n";
+        let events: Vec<Event> =
+            Parser::with_syntax(text, Dialect::Windows, Syntax::new().keep_whitespace())
+                .map(|event| event.unwrap())
+                .collect();
+        assert_eq!(
+            Event::KeyValue {
+                key: "key ",
+                value: Some(" value "),
+                span: 4..17,
+                value_span: 10..17
+            },
+            events[1]
+        );
+    }
+    #[test]
+    fn whitespace_delimiter() {
+        let text = "[a]\n  key value\n\tother  two words\n";
+        let syntax = Syntax::new().delimiters(&[' ', '\t']);
+        let events: Vec<Event> = Parser::with_syntax(text, Dialect::Windows, syntax)
+            .map(|event| event.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Event::SectionStart {
+                    name: "a".into(),
+                    span: 0..3
+                },
+                Event::KeyValue {
+                    key: "key",
+                    value: Some("value"),
+                    span: 4..15,
+                    value_span: 10..15
+                },
+                Event::KeyValue {
+                    key: "other",
+                    value: Some("two words"),
+                    span: 16..33,
+                    value_span: 24..33
+                },
+            ],
+            events
+        );
+        let mut parser = Parser::with_syntax(
+            "  key value\n",
+            Dialect::Python,
+            Syntax::new().delimiters(&[' ']),
+        );
+        match parser.next() {
+            Some(Ok(Event::KeyValue {
+                key: "key",
+                value: Some("value"),
+                ..
+            })) => (),
+            other => panic!("Event::KeyValue expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn syntax_error() {
+        let mut parser = Parser::new("[a]\nkey\nother = 1\n", Dialect::Python);
+        assert!(parser.next().unwrap().is_ok());
+        match parser.next() {
+            Some(Err(IniError::Syntax(ref diagnostic))) if diagnostic.line() == Some(2) => (),
+            other => panic!("IniError::Syntax expected, not {:?}", other),
+        }
+        assert_eq!(2, parser.line());
+        assert!(parser.next().is_none());
+        for &(text, line) in [("[]\n", 1), ("[a]\n = 1\n", 2), ("=1\n", 1)].iter() {
+            let error = Parser::new(text, Dialect::Python).last();
+            match error {
+                Some(Err(IniError::Syntax(ref diagnostic))) => {
+                    assert_eq!(Some(line), diagnostic.line(), "{:?}", text)
+                }
+                other => panic!("IniError::Syntax expected for {:?}, not {:?}", text, other),
+            }
+        }
+        let mut parser = Parser::new("[a] ; comment\n", Dialect::Python);
+        match parser.next() {
+            Some(Ok(Event::SectionStart { ref name, .. })) if name == "a" => (),
+            other => panic!("Event::SectionStart expected, not {:?}", other),
+        }
+    }
+    #[test]
+    fn reader() {
+        let text = "[a]\nkey = 1\n\n[b]\nkey = 2";
+        let mut reader = Reader::new(text.as_bytes(), Dialect::Python);
+        let mut spans = Vec::new();
+        while let Some(event) = reader.next_event() {
+            spans.push(event.unwrap().span());
+        }
+        let expected: Vec<_> = Parser::new(text, Dialect::Python)
+            .map(|event| event.unwrap().span())
+            .collect();
+        assert_eq!(expected, spans);
+        assert_eq!(5, reader.line());
+    }
+}
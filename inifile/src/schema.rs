@@ -0,0 +1,933 @@
+//! Declaration of the expected layout of an INI file, and validation of an `IniFile` against it.
+//!
+//! ```ignore
+//! let schema = Schema::new().section(
+//!     SectionSchema::new("server")
+//!         .option(OptionSchema::new("host", ValueType::String).required().min_length(1))
+//!         .option(OptionSchema::new("port", ValueType::Int).default("80").range(1.0, 65535.0)),
+//! );
+//! let report = ini.try_validate(&schema)?;
+//! ```
+//!
+//! A schema can also be declared in an INI file, see `Schema::from_ini()`.
+//!
+//! Validation does not stop at the first problem: the report lists every violation, with its location,
+//! and can be rendered as diagnostics, see `ValidationReport::diagnostics()`.
+
+use std::fmt;
+
+use diagnostic::{Diagnostic, Location};
+use error::{IniError, IniResult};
+use value;
+use IniFile;
+
+/**
+ * Expected type of an option value.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    /// A word recognized by `IniFile::get_bool()`.
+    Bool,
+    /// A signed integer.
+    Int,
+    /// A floating point number.
+    Float,
+    /// Any text.
+    String,
+    /// One of the given words, case-sensitive.
+    Enum(Vec<String>),
+    /// Items of the given type: the lines of a multi-line value, or the parts delimited by `,`.
+    List(Box<ValueType>),
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValueType::Bool => write!(f, "a boolean"),
+            ValueType::Int => write!(f, "an integer"),
+            ValueType::Float => write!(f, "a float"),
+            ValueType::String => write!(f, "a string"),
+            ValueType::Enum(ref words) => write!(f, "one of {}", words.join(", ")),
+            ValueType::List(ref item) => write!(f, "a list of {}", item),
+        }
+    }
+}
+
+/**
+ * Expected option: its type and the constraints on its value.
+ * Numeric ranges apply to integers and floats, length and affix constraints to the text;
+ * for a list, they apply to each item.
+ */
+#[derive(Clone, Debug)]
+pub struct OptionSchema {
+    default: Option<String>,
+    ends_with: Option<String>,
+    max_length: Option<usize>,
+    maximum: Option<f64>,
+    min_length: Option<usize>,
+    minimum: Option<f64>,
+    name: String,
+    required: bool,
+    starts_with: Option<String>,
+    value_type: ValueType,
+}
+
+impl OptionSchema {
+    /**
+     * Value used by `Schema::apply_defaults()` when the option is missing.
+     */
+    pub fn default(mut self, value: &str) -> OptionSchema {
+        self.default = Some(value.to_string());
+        self
+    }
+    /**
+     * The text must end with `suffix`.
+     */
+    pub fn ends_with(mut self, suffix: &str) -> OptionSchema {
+        self.ends_with = Some(suffix.to_string());
+        self
+    }
+    /**
+     * The text must not be longer than `length` characters.
+     */
+    pub fn max_length(mut self, length: usize) -> OptionSchema {
+        self.max_length = Some(length);
+        self
+    }
+    /**
+     * The number must not be greater than `maximum`.
+     */
+    pub fn maximum(mut self, maximum: f64) -> OptionSchema {
+        self.maximum = Some(maximum);
+        self
+    }
+    /**
+     * The text must not be shorter than `length` characters.
+     */
+    pub fn min_length(mut self, length: usize) -> OptionSchema {
+        self.min_length = Some(length);
+        self
+    }
+    /**
+     * The number must not be less than `minimum`.
+     */
+    pub fn minimum(mut self, minimum: f64) -> OptionSchema {
+        self.minimum = Some(minimum);
+        self
+    }
+    /**
+     * Getter on the option name.
+     */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /**
+     * Optional option of the given type, without constraints.
+     */
+    pub fn new(name: &str, value_type: ValueType) -> OptionSchema {
+        OptionSchema {
+            default: None,
+            ends_with: None,
+            max_length: None,
+            maximum: None,
+            min_length: None,
+            minimum: None,
+            name: name.to_string(),
+            required: false,
+            starts_with: None,
+            value_type,
+        }
+    }
+    /**
+     * The number must be between `minimum` and `maximum`, both included.
+     */
+    pub fn range(self, minimum: f64, maximum: f64) -> OptionSchema {
+        self.minimum(minimum).maximum(maximum)
+    }
+    /**
+     * The option must be present, in its section or in the default section.
+     */
+    pub fn required(mut self) -> OptionSchema {
+        self.required = true;
+        self
+    }
+    /**
+     * The text must start with `prefix`.
+     */
+    pub fn starts_with(mut self, prefix: &str) -> OptionSchema {
+        self.starts_with = Some(prefix.to_string());
+        self
+    }
+    /**
+     * Problems of a value, as messages.
+     */
+    fn check(&self, ini: &IniFile, value: &str) -> Vec<String> {
+        match self.value_type {
+            ValueType::List(ref item_type) => ini
+                .split_list(value)
+                .iter()
+                .flat_map(|item| self.check_item(ini, item_type, item))
+                .collect(),
+            ref value_type => self.check_item(ini, value_type, value.trim()),
+        }
+    }
+    /**
+     * Problems of a scalar value, as messages.
+     */
+    fn check_item(&self, ini: &IniFile, value_type: &ValueType, value: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        let number = match *value_type {
+            ValueType::Bool => match ini.parse_bool(value) {
+                Some(_) => None,
+                None => return vec![format!("{:?} is not {}", value, value_type)],
+            },
+            ValueType::String | ValueType::List(_) => None,
+            ValueType::Int => match value::parse_int::<i64>(value) {
+                Some(i) => Some(i as f64),
+                None => return vec![format!("{:?} is not {}", value, value_type)],
+            },
+            ValueType::Float => match value.parse::<f64>() {
+                Ok(f) => Some(f),
+                Err(_) => return vec![format!("{:?} is not {}", value, value_type)],
+            },
+            ValueType::Enum(ref words) => {
+                if !words.iter().any(|word| word == value) {
+                    let mut message = format!("{:?} is not {}", value, value_type);
+                    if let Some(word) = suggest(value, words.iter()) {
+                        message.push_str(&format!(" (did you mean {:?}?)", word));
+                    }
+                    return vec![message];
+                }
+                None
+            }
+        };
+        if let Some(number) = number {
+            if let Some(minimum) = self.minimum {
+                if number < minimum {
+                    problems.push(format!("{} is less than the minimum {}", value, minimum));
+                }
+            }
+            if let Some(maximum) = self.maximum {
+                if number > maximum {
+                    problems.push(format!("{} is greater than the maximum {}", value, maximum));
+                }
+            }
+        }
+        let length = value.chars().count();
+        if let Some(min_length) = self.min_length {
+            if length < min_length {
+                problems.push(format!(
+                    "{:?} is shorter than {} characters",
+                    value, min_length
+                ));
+            }
+        }
+        if let Some(max_length) = self.max_length {
+            if length > max_length {
+                problems.push(format!(
+                    "{:?} is longer than {} characters",
+                    value, max_length
+                ));
+            }
+        }
+        if let Some(ref prefix) = self.starts_with {
+            if !value.starts_with(prefix.as_str()) {
+                problems.push(format!("{:?} does not start with {:?}", value, prefix));
+            }
+        }
+        if let Some(ref suffix) = self.ends_with {
+            if !value.ends_with(suffix.as_str()) {
+                problems.push(format!("{:?} does not end with {:?}", value, suffix));
+            }
+        }
+        problems
+    }
+}
+
+/**
+ * Expected section: its options.
+ */
+#[derive(Clone, Debug)]
+pub struct SectionSchema {
+    allow_unknown: bool,
+    name: String,
+    options: Vec<OptionSchema>,
+}
+
+impl SectionSchema {
+    /**
+     * Options which are not declared do not give warnings.
+     */
+    pub fn allow_unknown(mut self) -> SectionSchema {
+        self.allow_unknown = true;
+        self
+    }
+    /**
+     * Getter on the section name.
+     */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /**
+     * Section without options.
+     */
+    pub fn new(name: &str) -> SectionSchema {
+        SectionSchema {
+            allow_unknown: false,
+            name: name.to_string(),
+            options: Vec::new(),
+        }
+    }
+    /**
+     * Declare an option of the section.
+     */
+    pub fn option(mut self, option: OptionSchema) -> SectionSchema {
+        self.options.push(option);
+        self
+    }
+}
+
+/**
+ * Expected layout of an INI file.
+ * Sections which are not declared give warnings; to expect options in every section, declare the
+ * default section.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    sections: Vec<SectionSchema>,
+}
+
+impl Schema {
+    /**
+     * Set the missing options which have a default value.
+     * The sections of these options are added if needed.
+     */
+    pub fn apply_defaults(&self, ini: &mut IniFile) {
+        for section in self.sections.iter() {
+            for option in section.options.iter() {
+                let default = match option.default {
+                    Some(ref default) => default,
+                    None => continue,
+                };
+                if ini.has_option(&section.name, &option.name) {
+                    continue;
+                }
+                if section.name != ini.default_section() && !ini.has_section(&section.name) {
+                    ini.add_section(&section.name);
+                }
+                ini.set(section.name.clone(), option.name.clone(), default.clone());
+            }
+        }
+    }
+    /**
+     * Schema declared in an INI file: each option is declared by its type followed by its constraints,
+     * separated by spaces, such as `port = int required default=80 min=1 max=65535`.
+     * Types are `bool`, `int`, `float`, `string`, `enum:a|b|c` and `list:TYPE`; constraints are
+     * `required`, `default=`, `min=`, `max=`, `min_length=`, `max_length=`, `starts_with=` and `ends_with=`.
+     * An option `* = allow` accepts unknown options in its section.
+     * The options of the default section are declared in the default section.
+     */
+    pub fn from_ini(ini: &IniFile) -> IniResult<Schema> {
+        let mut schema = Schema::new();
+        let mut sections = vec![ini.default_section()];
+        sections.extend(ini.sections());
+        for name in sections {
+            let mut section = SectionSchema::new(&name);
+            for option in ini.own_options(&name) {
+                let declaration = ini.own_value(&name, &option).unwrap();
+                if option == "*" && declaration.trim() == "allow" {
+                    section = section.allow_unknown();
+                    continue;
+                }
+                let parsed = parse_declaration(&option, declaration).map_err(|message| {
+                    let location = ini.location(&name, Some(&option), true);
+                    let help = "declare the option as a type and constraints, such as \
+                                `int required min=1`";
+                    IniError::Syntax(Box::new(
+                        Diagnostic::error(message, location).with_help(help.to_string()),
+                    ))
+                });
+                section = section.option(parsed?);
+            }
+            if !section.options.is_empty() || name != ini.default_section() {
+                schema = schema.section(section);
+            }
+        }
+        Ok(schema)
+    }
+    pub fn new() -> Schema {
+        Schema {
+            sections: Vec::new(),
+        }
+    }
+    /**
+     * Declare a section.
+     */
+    pub fn section(mut self, section: SectionSchema) -> Schema {
+        self.sections.push(section);
+        self
+    }
+    /**
+     * Check every declared option of the file, and report the unknown sections and options.
+     */
+    pub fn validate(&self, ini: &IniFile) -> ValidationReport {
+        let mut violations = Vec::new();
+        let default_section = ini.default_section();
+        for section in self.sections.iter() {
+            let exists = section.name == default_section || ini.has_section(&section.name);
+            let section_location = ini.location(&section.name, None, false);
+            for option in section.options.iter() {
+                let key = ini.optionxform(&option.name);
+                if !exists || !ini.has_option(&section.name, &key) {
+                    if option.required {
+                        let help =
+                            format!("define `{}` in section [{}]", option.name, section.name);
+                        violations.push(
+                            Violation::error(
+                                &section.name,
+                                Some(&key),
+                                section_location.clone(),
+                                "required option is missing".to_string(),
+                            )
+                            .with_help(help),
+                        );
+                    }
+                    continue;
+                }
+                let location = ini
+                    .location(&section.name, Some(&key), true)
+                    .or_else(|| ini.location(&default_section, Some(&key), true));
+                let problems = match ini.try_get(&section.name, &key) {
+                    Ok(value) => option.check(ini, &value),
+                    Err(e) => vec![e.to_string()],
+                };
+                for problem in problems {
+                    let location = location.clone();
+                    violations.push(Violation::error(
+                        &section.name,
+                        Some(&key),
+                        location,
+                        problem,
+                    ));
+                }
+            }
+        }
+        self.report_unknown(ini, &mut violations);
+        violations.sort_by_key(|violation| violation.line.unwrap_or(usize::MAX));
+        ValidationReport {
+            path: ini.filepath(),
+            violations,
+        }
+    }
+    /**
+     * Add warnings for the sections and options which are not declared.
+     */
+    fn report_unknown(&self, ini: &IniFile, violations: &mut Vec<Violation>) {
+        let default_section = ini.default_section();
+        let mut sections = ini.sections();
+        sections.insert(0, default_section.clone());
+        for name in sections {
+            let section = match self.sections.iter().find(|section| section.name == name) {
+                Some(section) => section,
+                None if name == default_section => {
+                    // Default options are visible in every section: they may be declared anywhere.
+                    let known: Vec<String> = self
+                        .sections
+                        .iter()
+                        .flat_map(|section| section.options.iter())
+                        .map(|option| ini.optionxform(&option.name))
+                        .collect();
+                    for option in ini.own_options(&name) {
+                        if !known.contains(&option) {
+                            let location = ini.location(&name, Some(&option), false);
+                            let message = unknown("option", &option, known.iter());
+                            violations.push(Violation::warning(
+                                &name,
+                                Some(&option),
+                                location,
+                                message,
+                            ));
+                        }
+                    }
+                    continue;
+                }
+                None => {
+                    let location = ini.location(&name, None, false);
+                    let known = self.sections.iter().map(|section| &section.name);
+                    let message = unknown("section", &name, known);
+                    violations.push(Violation::warning(&name, None, location, message));
+                    continue;
+                }
+            };
+            if section.allow_unknown {
+                continue;
+            }
+            let known: Vec<String> = section
+                .options
+                .iter()
+                .map(|option| ini.optionxform(&option.name))
+                .collect();
+            for option in ini.own_options(&name) {
+                if !known.contains(&option) {
+                    let location = ini.location(&name, Some(&option), false);
+                    let message = unknown("option", &option, known.iter());
+                    violations.push(Violation::warning(&name, Some(&option), location, message));
+                }
+            }
+        }
+    }
+}
+
+/**
+ * How serious a violation is: errors make the file invalid, warnings do not.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/**
+ * A problem found by the validation.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub severity: Severity,
+    pub section: String,
+    /// None when the whole section is concerned.
+    pub option: Option<String>,
+    /// Line of the option, or of the section header; starts at 1.
+    pub line: Option<usize>,
+    pub message: String,
+    /// Value or key of the option, or section header, in the file defining it.
+    pub location: Option<Location>,
+    /// How the problem may be fixed.
+    pub help: Option<String>,
+}
+
+impl Violation {
+    /**
+     * The violation as a diagnostic, to render it.
+     */
+    pub fn diagnostic(&self) -> Diagnostic {
+        let message = match self.option {
+            Some(ref option) => format!("[{}] {}: {}", self.section, option, self.message),
+            None => format!("[{}]: {}", self.section, self.message),
+        };
+        Diagnostic {
+            severity: self.severity,
+            message,
+            location: self.location.clone(),
+            help: self.help.clone(),
+        }
+    }
+    fn error(
+        section: &str,
+        option: Option<&str>,
+        location: Option<Location>,
+        message: String,
+    ) -> Violation {
+        Violation {
+            severity: Severity::Error,
+            section: section.to_string(),
+            option: option.map(|option| option.to_string()),
+            line: location.as_ref().map(|location| location.line),
+            message,
+            location,
+            help: None,
+        }
+    }
+    fn warning(
+        section: &str,
+        option: Option<&str>,
+        location: Option<Location>,
+        message: String,
+    ) -> Violation {
+        Violation {
+            severity: Severity::Warning,
+            ..Violation::error(section, option, location, message)
+        }
+    }
+    fn with_help(mut self, help: String) -> Violation {
+        self.help = Some(help);
+        self
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        match self.option {
+            Some(ref option) => write!(f, "[{}] {}: {}", self.section, option, self.message),
+            None => write!(f, "[{}]: {}", self.section, self.message),
+        }
+    }
+}
+
+/**
+ * Every violation found by `Schema::validate()`, in line order.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    /// Path of the validated file, may be empty.
+    pub path: String,
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /**
+     * The violations as diagnostics, in line order, to render them.
+     */
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.violations.iter().map(Violation::diagnostic).collect()
+    }
+    /**
+     * Violations making the file invalid.
+     */
+    pub fn errors(&self) -> Vec<&Violation> {
+        self.violations
+            .iter()
+            .filter(|violation| violation.severity == Severity::Error)
+            .collect()
+    }
+    /**
+     * Indicates whether the file has no error; it may have warnings.
+     */
+    pub fn is_valid(&self) -> bool {
+        self.errors().is_empty()
+    }
+    /**
+     * Violations which do not make the file invalid.
+     */
+    pub fn warnings(&self) -> Vec<&Violation> {
+        self.violations
+            .iter()
+            .filter(|violation| violation.severity == Severity::Warning)
+            .collect()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for violation in self.violations.iter() {
+            if !self.path.is_empty() {
+                write!(f, "{}: ", self.path)?;
+            }
+            writeln!(f, "{}", violation)?;
+        }
+        write!(
+            f,
+            "{} error(s), {} warning(s)",
+            self.errors().len(),
+            self.warnings().len()
+        )
+    }
+}
+
+/**
+ * Option declared as `TYPE constraint...`, see `Schema::from_ini()`.
+ */
+fn parse_declaration(name: &str, declaration: &str) -> Result<OptionSchema, String> {
+    let mut words = declaration.split_whitespace();
+    let value_type = match words.next() {
+        Some(word) => parse_type(word)?,
+        None => return Err(format!("missing type of option {:?}", name)),
+    };
+    let mut option = OptionSchema::new(name, value_type);
+    for word in words {
+        if word == "required" {
+            option = option.required();
+            continue;
+        }
+        let (constraint, value) = match word.find('=') {
+            Some(index) => (&word[..index], &word[index + 1..]),
+            None => return Err(format!("unknown constraint {:?}", word)),
+        };
+        let number = || {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("{} expects a number, found {:?}", constraint, value))
+        };
+        let length = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("{} expects a length, found {:?}", constraint, value))
+        };
+        option = match constraint {
+            "default" => option.default(value),
+            "min" => option.minimum(number()?),
+            "max" => option.maximum(number()?),
+            "min_length" => option.min_length(length()?),
+            "max_length" => option.max_length(length()?),
+            "starts_with" => option.starts_with(value),
+            "ends_with" => option.ends_with(value),
+            _ => return Err(format!("unknown constraint {:?}", word)),
+        };
+    }
+    Ok(option)
+}
+
+/**
+ * Type declared as `bool`, `int`, `float`, `string`, `enum:a|b|c` or `list:TYPE`.
+ */
+fn parse_type(word: &str) -> Result<ValueType, String> {
+    match word {
+        "bool" => Ok(ValueType::Bool),
+        "int" => Ok(ValueType::Int),
+        "float" => Ok(ValueType::Float),
+        "string" => Ok(ValueType::String),
+        _ if word.starts_with("enum:") => Ok(ValueType::Enum(
+            word["enum:".len()..]
+                .split('|')
+                .map(|w| w.to_string())
+                .collect(),
+        )),
+        _ if word.starts_with("list:") => Ok(ValueType::List(Box::new(parse_type(
+            &word["list:".len()..],
+        )?))),
+        _ => Err(format!("unknown type {:?}", word)),
+    }
+}
+
+/**
+ * Message for an unknown section or option, suggesting the closest known name.
+ */
+fn unknown<'a, I: Iterator<Item = &'a String>>(kind: &str, name: &str, known: I) -> String {
+    match suggest(name, known) {
+        Some(closest) => format!("unknown {} (did you mean {:?}?)", kind, closest),
+        None => format!("unknown {}", kind),
+    }
+}
+
+/**
+ * Closest candidate, if it is at most a third of the name away (one edit for short names).
+ */
+fn suggest<'a, I: Iterator<Item = &'a String>>(name: &str, candidates: I) -> Option<&'a String> {
+    let threshold = ::std::cmp::max(1, name.chars().count() / 3);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= threshold)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/**
+ * Edit distance between two strings, in characters: insertions, deletions, substitutions and
+ * transpositions of adjacent characters, which are common typos, count as one edit.
+ */
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j]: distance between the first i characters of a and the first j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, OptionSchema, Schema, SectionSchema, Severity, ValueType};
+    use IniFile;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .section(
+                SectionSchema::new("server")
+                    .option(
+                        OptionSchema::new("host", ValueType::String)
+                            .required()
+                            .min_length(1),
+                    )
+                    .option(
+                        OptionSchema::new("port", ValueType::Int)
+                            .default("80")
+                            .range(1.0, 65535.0),
+                    )
+                    .option(OptionSchema::new("debug", ValueType::Bool))
+                    .option(OptionSchema::new(
+                        "mode",
+                        ValueType::Enum(vec!["fast".to_string(), "safe".to_string()]),
+                    )),
+            )
+            .section(
+                SectionSchema::new("paths")
+                    .option(
+                        OptionSchema::new("dirs", ValueType::List(Box::new(ValueType::String)))
+                            .starts_with("/"),
+                    )
+                    .option(OptionSchema::new("log", ValueType::String).required()),
+            )
+    }
+
+    #[test]
+    fn valid() {
+        let ini: IniFile = "[server]\nhost = example.org\nport = 8080\ndebug = Yes\nmode = safe\n\n[paths]\ndirs = /a, /b\nlog = /var/log\n"
+            .parse()
+            .unwrap();
+        let report = schema().validate(&ini);
+        assert!(report.is_valid(), "{}", report);
+        assert!(report.violations.is_empty(), "{}", report);
+    }
+    #[test]
+    fn typed_as_getters() {
+        let mut ini: IniFile =
+            "[server]\nhost = a\nport = 0x1F90\n\n[paths]\ndirs = /a; /b,c\nlog = l\n"
+                .parse()
+                .unwrap();
+        ini.set_list_delimiter(';');
+        let report = schema().validate(&ini);
+        assert!(report.is_valid(), "{}", report);
+        ini.set_list_delimiter(',');
+        let report = schema().validate(&ini);
+        assert_eq!(1, report.violations.len(), "{}", report);
+    }
+    #[test]
+    fn every_violation() {
+        let ini: IniFile = "[server]\nhost =\nport = 70000\ndebug = maybe\nmode = fats\nprot = 1\n\n[paths]\ndirs = /a, b\n\n[extra]\n"
+            .parse()
+            .unwrap();
+        let report = schema().validate(&ini);
+        let found: Vec<(Severity, Option<usize>, String)> = report
+            .violations
+            .iter()
+            .map(|v| (v.severity, v.line, v.option.clone().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Severity::Error, Some(2), "host".to_string()),
+                (Severity::Error, Some(3), "port".to_string()),
+                (Severity::Error, Some(4), "debug".to_string()),
+                (Severity::Error, Some(5), "mode".to_string()),
+                (Severity::Warning, Some(6), "prot".to_string()),
+                (Severity::Error, Some(8), "log".to_string()),
+                (Severity::Error, Some(9), "dirs".to_string()),
+                (Severity::Warning, Some(11), "".to_string()),
+            ],
+            found
+        );
+        assert!(!report.is_valid());
+        assert_eq!(6, report.errors().len());
+        let text = report.to_string();
+        assert!(
+            text.contains("line 5: error: [server] mode: \"fats\" is not one of fast, safe (did you mean \"fast\"?)"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains(
+                "line 6: warning: [server] prot: unknown option (did you mean \"port\"?)"
+            ),
+            "{}",
+            text
+        );
+        assert!(text.ends_with("6 error(s), 2 warning(s)"), "{}", text);
+        let diagnostics = report.diagnostics();
+        assert_eq!(8, diagnostics.len());
+        let port = diagnostics[1].render();
+        assert!(port.starts_with("error: [server] port: "), "{}", port);
+        assert!(
+            port.contains(" --> <string>:3:8\n  |\n3 | port = 70000\n  |        ^^^^^\n"),
+            "{}",
+            port
+        );
+        assert_eq!(Some(8), diagnostics[5].line());
+        assert_eq!(
+            Some("define `log` in section [paths]"),
+            diagnostics[5].help.as_deref()
+        );
+    }
+    #[test]
+    fn default_section() {
+        let ini: IniFile = "log = /var/log\n[server]\nhost = a\n[paths]\n"
+            .parse()
+            .unwrap();
+        let report = schema().validate(&ini);
+        assert!(report.violations.is_empty(), "{}", report);
+    }
+    #[test]
+    fn missing_section() {
+        let ini: IniFile = "[server]\nhost = a\n".parse().unwrap();
+        let report = schema().validate(&ini);
+        assert_eq!(1, report.violations.len(), "{}", report);
+        assert_eq!(None, report.violations[0].line);
+        assert_eq!("paths", report.violations[0].section);
+    }
+    #[test]
+    fn apply_defaults() {
+        let mut ini: IniFile = "[server]\nhost = a\n".parse().unwrap();
+        schema().apply_defaults(&mut ini);
+        assert_eq!("80", ini.get("server", "port"));
+        assert!(!ini.has_section("paths"));
+    }
+    #[test]
+    fn try_validate() {
+        let ini: IniFile = "[server]\nhost = a\nport = x\n[paths]\nlog = l\n"
+            .parse()
+            .unwrap();
+        match ini.try_validate(&schema()) {
+            Err(::IniError::Validation(report)) => assert_eq!(1, report.errors().len()),
+            other => panic!("IniError::Validation expected, not {:?}", other),
+        }
+        let ini: IniFile = "[server]\nhost = a\nunknown = x\n[paths]\nlog = l\n"
+            .parse()
+            .unwrap();
+        let report = ini.try_validate(&schema()).unwrap();
+        assert_eq!(1, report.warnings().len());
+    }
+    #[test]
+    fn distance() {
+        assert_eq!(0, edit_distance("port", "port"));
+        assert_eq!(1, edit_distance("prot", "port"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+        assert_eq!(4, edit_distance("", "port"));
+    }
+    #[test]
+    fn from_ini() {
+        let declaration: IniFile = "[server]\nhost = string required min_length=1\nport = int default=80 min=1 max=65535\ndebug = bool\nmode = enum:fast|safe\n\n[paths]\n* = allow\ndirs = list:string starts_with=/\nlog = string required\n"
+            .parse()
+            .unwrap();
+        let schema = Schema::from_ini(&declaration).unwrap();
+        let ini: IniFile =
+            "[server]\nhost = a\nport = 0\nmode = slow\n[paths]\nlog = l\nother = 1\n"
+                .parse()
+                .unwrap();
+        let report = schema.validate(&ini);
+        assert_eq!(2, report.errors().len(), "{}", report);
+        assert_eq!(0, report.warnings().len(), "{}", report);
+        let mut ini: IniFile = "[server]\nhost = a\n[paths]\nlog = l\n".parse().unwrap();
+        schema.apply_defaults(&mut ini);
+        assert_eq!("80", ini.get("server", "port"));
+        for declaration in ["[s]\nx = integer\n", "[s]\nx =\n", "[s]\n\nx = int min=a\n"].iter() {
+            let ini: IniFile = declaration.parse().unwrap();
+            match Schema::from_ini(&ini) {
+                Err(::IniError::Syntax(diagnostic)) => {
+                    assert!(diagnostic.line() >= Some(2), "{:?}", diagnostic)
+                }
+                other => panic!("IniError::Syntax expected, not {:?}", other),
+            }
+        }
+    }
+}
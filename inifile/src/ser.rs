@@ -0,0 +1,455 @@
+//! Serialize a typed structure into an `IniFile`, with serde.
+//!
+//! The structure is written as the reverse of `de`: nested structures and maps become sections,
+//! other fields become options of the default section.
+//! `None` options are omitted, lists are delimited by `, ` and unit enum variants are written by name.
+//! List items which would not be read back as they are, empty, with surrounding spaces, or holding `,` or
+//! a new line, are errors.
+//!
+//! @see https://serde.rs/impl-serializer.html
+
+use serde::ser::{self, Serialize};
+
+use error::{IniError, IniResult};
+use IniFile;
+
+/**
+ * Serialize a structure into a new INI file.
+ */
+pub fn to_ini<T: Serialize + ?Sized>(value: &T) -> IniResult<IniFile> {
+    let entries = match value.serialize(NodeSerializer { depth: 0 })? {
+        Node::Map(entries) => entries,
+        Node::Value(_) => {
+            return Err(ser::Error::custom(
+                "only structures and maps can be written as an INI file",
+            ))
+        }
+    };
+    let mut ini = IniFile::new();
+    let default_section = ini.default_section();
+    for (name, node) in entries {
+        match node {
+            Node::Value(Some(value)) => ini.try_set(default_section.clone(), name, value)?,
+            Node::Value(None) => (),
+            Node::Map(options) => {
+                ini.try_add_section(&name)?;
+                for (option, node) in options {
+                    if let Node::Value(Some(value)) = node {
+                        ini.try_set(name.clone(), option, value)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(ini)
+}
+
+/**
+ * Serialize a structure into the text of an INI file.
+ */
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> IniResult<String> {
+    Ok(to_ini(value)?.to_string())
+}
+
+impl ser::Error for IniError {
+    fn custom<T: ::std::fmt::Display>(msg: T) -> IniError {
+        IniError::Serde(msg.to_string())
+    }
+}
+
+/**
+ * Serialized form: an option value (`None` to omit it), or the entries of a section or of the file.
+ */
+enum Node {
+    Value(Option<String>),
+    Map(Vec<(String, Node)>),
+}
+
+/**
+ * Serializer of the file (depth 0), of a section (depth 1) or of a value (depth 2).
+ */
+struct NodeSerializer {
+    depth: usize,
+}
+
+impl NodeSerializer {
+    fn value<T: ToString>(value: T) -> IniResult<Node> {
+        Ok(Node::Value(Some(value.to_string())))
+    }
+
+    fn map(self) -> IniResult<MapSerializer> {
+        if self.depth >= 2 {
+            return Err(ser::Error::custom(
+                "sections can not be nested, options can not contain structures",
+            ));
+        }
+        Ok(MapSerializer {
+            depth: self.depth,
+            entries: Vec::new(),
+            key: None,
+        })
+    }
+}
+
+/**
+ * Serialize a value as a string, for keys and list items.
+ */
+fn to_value<T: Serialize + ?Sized>(value: &T) -> IniResult<Option<String>> {
+    match value.serialize(NodeSerializer { depth: 2 })? {
+        Node::Value(value) => Ok(value),
+        Node::Map(_) => Err(ser::Error::custom("a value was expected")),
+    }
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = IniError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ser::Impossible<Node, IniError>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = ser::Impossible<Node, IniError>;
+
+    fn serialize_bool(self, v: bool) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_i8(self, v: i8) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_i16(self, v: i16) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_i32(self, v: i32) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_i64(self, v: i64) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_u8(self, v: u8) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_u16(self, v: u16) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_u32(self, v: u32) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_u64(self, v: u64) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_f32(self, v: f32) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_f64(self, v: f64) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_char(self, v: char) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_str(self, v: &str) -> IniResult<Node> {
+        NodeSerializer::value(v)
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> IniResult<Node> {
+        Err(ser::Error::custom(
+            "bytes can not be written in an INI file",
+        ))
+    }
+    fn serialize_none(self) -> IniResult<Node> {
+        Ok(Node::Value(None))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> IniResult<Node> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> IniResult<Node> {
+        NodeSerializer::value("")
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> IniResult<Node> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> IniResult<Node> {
+        NodeSerializer::value(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> IniResult<Node> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> IniResult<Node> {
+        Err(ser::Error::custom(
+            "only unit enum variants can be written in an INI file",
+        ))
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> IniResult<ListSerializer> {
+        Ok(ListSerializer { items: Vec::new() })
+    }
+    fn serialize_tuple(self, len: usize) -> IniResult<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> IniResult<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> IniResult<Self::SerializeTupleVariant> {
+        Err(ser::Error::custom(
+            "only unit enum variants can be written in an INI file",
+        ))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> IniResult<MapSerializer> {
+        self.map()
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> IniResult<MapSerializer> {
+        self.map()
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> IniResult<Self::SerializeStructVariant> {
+        Err(ser::Error::custom(
+            "only unit enum variants can be written in an INI file",
+        ))
+    }
+}
+
+/**
+ * Items of a list, written delimited by `, `.
+ */
+struct ListSerializer {
+    items: Vec<String>,
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Node;
+    type Error = IniError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> IniResult<()> {
+        if let Some(item) = to_value(value)? {
+            if item.is_empty() || item.trim() != item || item.contains([',', '\n']) {
+                return Err(ser::Error::custom(format!(
+                    "the list item {:?} would be read back split or trimmed",
+                    item
+                )));
+            }
+            self.items.push(item);
+        }
+        Ok(())
+    }
+    fn end(self) -> IniResult<Node> {
+        Ok(Node::Value(Some(self.items.join(", "))))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Node;
+    type Error = IniError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> IniResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> IniResult<Node> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Node;
+    type Error = IniError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> IniResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> IniResult<Node> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/**
+ * Entries of the file or of a section.
+ */
+struct MapSerializer {
+    depth: usize,
+    entries: Vec<(String, Node)>,
+    key: Option<String>,
+}
+
+impl MapSerializer {
+    fn entry<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> IniResult<()> {
+        let node = value.serialize(NodeSerializer {
+            depth: self.depth + 1,
+        })?;
+        self.entries.push((key, node));
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = IniError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> IniResult<()> {
+        match to_value(key)? {
+            Some(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            None => Err(ser::Error::custom("keys can not be None")),
+        }
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> IniResult<()> {
+        match self.key.take() {
+            Some(key) => self.entry(key, value),
+            None => Err(ser::Error::custom("value serialized before key")),
+        }
+    }
+    fn end(self) -> IniResult<Node> {
+        Ok(Node::Map(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Node;
+    type Error = IniError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> IniResult<()> {
+        self.entry(key.to_string(), value)
+    }
+    fn end(self) -> IniResult<Node> {
+        Ok(Node::Map(self.entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_ini, to_string};
+    use de::from_str;
+    use std::collections::BTreeMap;
+    use IniError;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Mode {
+        Active,
+        Passive,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Ftp {
+        host: String,
+        port: u16,
+        mode: Mode,
+        user: Option<String>,
+        mirrors: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Config {
+        verbose: bool,
+        ftp: Ftp,
+    }
+
+    fn config() -> Config {
+        Config {
+            verbose: false,
+            ftp: Ftp {
+                host: "ftp.example.com".to_string(),
+                port: 21,
+                mode: Mode::Passive,
+                user: None,
+                mirrors: vec!["a.example.com".to_string(), "b.example.com".to_string()],
+            },
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let expected = "[DEFAULT]
+verbose=false
+
+[ftp]
+host=ftp.example.com
+port=21
+mode=Passive
+mirrors=a.example.com, b.example.com
+";
+        assert_eq!(expected, to_string(&config()).unwrap());
+    }
+    #[test]
+    fn serialize_map() {
+        let mut env = BTreeMap::new();
+        env.insert("HOME", "/root");
+        env.insert("LANG", "C");
+        let mut sections = BTreeMap::new();
+        sections.insert("env", env);
+        sections.insert("empty", BTreeMap::new());
+        let ini = to_ini(&sections).unwrap();
+        assert_eq!(vec!["empty".to_string(), "env".to_string()], ini.sections());
+        assert_eq!("[empty]\n\n[env]\nHOME=/root\nLANG=C\n", ini.to_string());
+    }
+    #[test]
+    fn serialize_round_trip() {
+        let text = to_string(&config()).unwrap();
+        let found: Config = from_str(&text).unwrap();
+        assert_eq!(config(), found);
+    }
+    #[test]
+    fn serialize_list_delimiter() {
+        let mut ftp = config().ftp;
+        ftp.mirrors = vec!["a, b".to_string(), "c".to_string()];
+        let text = to_string(&ftp);
+        ftp.mirrors = vec!["a".to_string(), " b".to_string()];
+        let spaces = to_string(&ftp);
+        ftp.mirrors = vec!["a".to_string(), "b c".to_string()];
+        let found: Ftp = from_str(&to_string(&ftp).unwrap()).unwrap();
+        for result in [text, spaces] {
+            match result {
+                Err(IniError::Serde(ref message)) => assert!(message.contains("read back")),
+                other => panic!("IniError::Serde expected, not {:?}", other),
+            }
+        }
+        assert_eq!(ftp, found);
+    }
+    #[test]
+    fn serialize_nested_section() {
+        let mut sections = BTreeMap::new();
+        sections.insert("section", config());
+        match to_ini(&sections) {
+            Err(IniError::Serde(_)) => (),
+            other => panic!(
+                "IniError::Serde expected, not {:?}",
+                other.map(|ini| ini.to_string())
+            ),
+        }
+        match to_ini(&42) {
+            Err(IniError::Serde(_)) => (),
+            other => panic!(
+                "IniError::Serde expected, not {:?}",
+                other.map(|ini| ini.to_string())
+            ),
+        }
+    }
+}
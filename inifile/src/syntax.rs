@@ -0,0 +1,192 @@
+//! Options of the parser, on top of the dialect, see `IniFile::set_syntax()`.
+//!
+//! ```ignore
+//! let mut ini = IniFile::new();
+//! ini.set_syntax(Syntax::python().allow_no_value().inline_comment_prefixes(&[";"]));
+//! ```
+//!
+//! The default syntax reads `key = value` lines, trimmed, and lets duplicates through: a section defined
+//! twice is merged, an option defined twice keeps the value chosen by the dialect. `Syntax::python()`
+//! matches the defaults of Python's `ConfigParser`.
+
+use std::ops::Range;
+
+/**
+ * How the lines of a file are split into keys and values, and whether duplicates are errors.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Syntax {
+    allow_no_value: bool,
+    delimiters: Vec<char>,
+    inline_comment_prefixes: Vec<String>,
+    strict: bool,
+    trim: bool,
+}
+
+impl Syntax {
+    /**
+     * A key alone on its line is an option with an empty value, as Python's `allow_no_value`;
+     * git-config keys without value are `true` whatever the syntax.
+     */
+    pub fn allow_no_value(mut self) -> Syntax {
+        self.allow_no_value = true;
+        self
+    }
+    /**
+     * Characters separating a key from its value, the first one found in the line ending the key.
+     * New options are written with the first delimiter. If `delimiters` is empty, panic!()
+     */
+    pub fn delimiters(mut self, delimiters: &[char]) -> Syntax {
+        assert!(!delimiters.is_empty(), "a syntax needs a delimiter");
+        self.delimiters = delimiters.to_vec();
+        self
+    }
+    /**
+     * Prefixes of the comments which may follow a value or a section header, as `key = value ; comment`;
+     * they must be preceded by a whitespace. None by default: `;` and `#` only start comment lines.
+     */
+    pub fn inline_comment_prefixes(mut self, prefixes: &[&str]) -> Syntax {
+        self.inline_comment_prefixes = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+        self
+    }
+    /**
+     * Keys and values are read as written, spaces around the delimiter included; only the indentation
+     * of the line is removed.
+     */
+    pub fn keep_whitespace(mut self) -> Syntax {
+        self.trim = false;
+        self
+    }
+    /**
+     * Syntax of the files read before syntax options existed: `=` delimiter, trimmed keys and values,
+     * duplicates allowed.
+     */
+    pub fn new() -> Syntax {
+        Syntax {
+            allow_no_value: false,
+            delimiters: vec!['='],
+            inline_comment_prefixes: Vec::new(),
+            strict: false,
+            trim: true,
+        }
+    }
+    /**
+     * Defaults of Python's `ConfigParser`: `=` and `:` delimiters, strict.
+     */
+    pub fn python() -> Syntax {
+        Syntax::new().delimiters(&['=', ':']).strict()
+    }
+    /**
+     * A section or option defined twice in a file is a syntax error, instead of being merged or the
+     * last value winning.
+     */
+    pub fn strict(mut self) -> Syntax {
+        self.strict = true;
+        self
+    }
+    /**
+     * Offset of the inline comment of a line, if any.
+     */
+    pub(crate) fn comment_start(&self, line: &str) -> Option<usize> {
+        if self.inline_comment_prefixes.is_empty() {
+            return None;
+        }
+        let mut previous = None;
+        for (index, c) in line.char_indices() {
+            if previous.is_some_and(char::is_whitespace)
+                && self
+                    .inline_comment_prefixes
+                    .iter()
+                    .any(|prefix| line[index..].starts_with(prefix.as_str()))
+            {
+                return Some(index);
+            }
+            previous = Some(c);
+        }
+        None
+    }
+    /**
+     * Prefixes of the inline comments.
+     */
+    pub(crate) fn comment_prefixes(&self) -> &[String] {
+        &self.inline_comment_prefixes
+    }
+    /**
+     * Delimiter written in new options.
+     */
+    pub(crate) fn delimiter(&self) -> char {
+        self.delimiters[0]
+    }
+    /**
+     * Span of the delimiter ending the key of a line, if any.
+     */
+    pub(crate) fn find_delimiter(&self, line: &str) -> Option<Range<usize>> {
+        line.char_indices()
+            .find(|&(_, c)| self.delimiters.contains(&c))
+            .map(|(index, c)| index..index + c.len_utf8())
+    }
+    /**
+     * Whether a key alone on its line is an option.
+     */
+    pub(crate) fn has_no_value(&self) -> bool {
+        self.allow_no_value
+    }
+    /**
+     * Whether duplicate sections and options are errors.
+     */
+    pub(crate) fn is_strict(&self) -> bool {
+        self.strict
+    }
+    /**
+     * Span of a key or value in the text around the delimiter: trimmed, unless whitespace is kept.
+     */
+    pub(crate) fn trimmed(&self, text: &str) -> Range<usize> {
+        if !self.trim {
+            return 0..text.len();
+        }
+        let start = text.len() - text.trim_start().len();
+        start..text.trim_end().len().max(start)
+    }
+}
+
+impl Default for Syntax {
+    fn default() -> Syntax {
+        Syntax::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Syntax;
+
+    #[test]
+    fn comment_start() {
+        let syntax = Syntax::new().inline_comment_prefixes(&[";", "//"]);
+        assert_eq!(Some(6), syntax.comment_start("k = v ; c"));
+        assert_eq!(Some(6), syntax.comment_start("k = v\t// c ; d"));
+        assert_eq!(None, syntax.comment_start("k = a;b//c"));
+        assert_eq!(None, syntax.comment_start("; c"));
+        assert_eq!(None, Syntax::new().comment_start("k = v ; c"));
+    }
+    #[test]
+    fn delimiters() {
+        let syntax = Syntax::python();
+        assert_eq!(Some(3..4), syntax.find_delimiter("key: a=b"));
+        assert_eq!(Some(1..2), syntax.find_delimiter("k=a:b"));
+        assert_eq!(None, Syntax::new().find_delimiter("key: value"));
+        let arrow = Syntax::new().delimiters(&['\u{2192}', '=']);
+        assert_eq!(Some(2..5), arrow.find_delimiter("k \u{2192} v"));
+        assert_eq!('\u{2192}', arrow.delimiter());
+    }
+    #[test]
+    fn trimmed() {
+        assert_eq!(1..4, Syntax::new().trimmed(" key  "));
+        assert_eq!(3..3, Syntax::new().trimmed("   "));
+        assert_eq!(0..6, Syntax::new().keep_whitespace().trimmed(" key  "));
+    }
+    #[test]
+    #[should_panic(expected = "a syntax needs a delimiter")]
+    fn no_delimiter() {
+        Syntax::new().delimiters(&[]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Parsing and formatting of typed values: integers of any width and radix, durations, byte sizes.
+//!
+//! Integers are written in decimal, or in hexadecimal, octal or binary with a `0x`, `0o` or `0b`
+//! prefix; `_` may separate digits. Durations are sums of numbers followed by a unit, such as `30s`,
+//! `5m` or `1h30m`. Sizes are numbers followed by a decimal or binary unit, such as `10MB` or `4KiB`.
+
+use std::convert::TryFrom;
+use std::num::ParseIntError;
+use std::time::Duration;
+
+/**
+ * Integer type returned by `IniFile::get_int_as()`.
+ */
+pub trait Integer: Sized {
+    /**
+     * Parse digits in the given radix, preceded by an optional sign, as `i32::from_str_radix()`.
+     */
+    fn from_str_radix(digits: &str, radix: u32) -> Result<Self, ParseIntError>;
+}
+
+macro_rules! integer {
+    ($($t:ty)*) => ($(
+        impl Integer for $t {
+            fn from_str_radix(digits: &str, radix: u32) -> Result<$t, ParseIntError> {
+                <$t>::from_str_radix(digits, radix)
+            }
+        }
+    )*)
+}
+
+integer! { i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize }
+
+/**
+ * Parse an integer: decimal, or hexadecimal, octal or binary with a `0x`, `0o` or `0b` prefix.
+ * The sign precedes the prefix, as in `-0x10`; `_` may separate digits.
+ */
+pub fn parse_int<T: Integer>(value: &str) -> Option<T> {
+    let value = value.trim();
+    let (sign, unsigned) = match value.chars().next() {
+        Some(sign) if sign == '-' || sign == '+' => (&value[..1], &value[1..]),
+        _ => ("", value),
+    };
+    let prefix = unsigned.get(..2).map(str::to_lowercase);
+    let (radix, digits) = match prefix.as_deref() {
+        Some("0x") => (16, &unsigned[2..]),
+        Some("0o") => (8, &unsigned[2..]),
+        Some("0b") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+    if digits.is_empty()
+        || digits.starts_with('_')
+        || digits.ends_with('_')
+        || digits.contains("__")
+        || digits.starts_with('+')
+        || digits.starts_with('-')
+    {
+        return None;
+    }
+    let digits = format!("{}{}", sign, digits.replace('_', ""));
+    T::from_str_radix(&digits, radix).ok()
+}
+
+/**
+ * Split a value into numbers and units, as `1h30m` into `("1", "h")` and `("30", "m")`.
+ * A number without unit has an empty unit.
+ */
+fn quantities(value: &str) -> Option<Vec<(&str, &str)>> {
+    let mut quantities = Vec::new();
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number = &rest[..number_end];
+        number.parse::<f64>().ok()?;
+        rest = rest[number_end..].trim_start();
+        let unit_end = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        quantities.push((number, &rest[..unit_end]));
+        rest = rest[unit_end..].trim_start();
+    }
+    Some(quantities)
+}
+
+/**
+ * Parse a duration: numbers followed by `ns`, `us`, `ms`, `s`, `m`, `h`, `d` or `w`, as `1h30m` or
+ * `1.5s`. A single number without unit is a number of seconds.
+ */
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let quantities = quantities(value)?;
+    let mut nanos = 0u128;
+    for &(number, unit) in quantities.iter() {
+        let number: f64 = number.parse().ok()?;
+        let factor: u64 = match unit {
+            "" if quantities.len() == 1 => 1_000_000_000,
+            "ns" => 1,
+            "us" | "µs" => 1000,
+            "ms" => 1_000_000,
+            "s" | "sec" => 1_000_000_000,
+            "m" | "min" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            "d" => 86_400_000_000_000,
+            "w" => 604_800_000_000_000,
+            _ => return None,
+        };
+        let quantity = (number * factor as f64).round();
+        if quantity >= u128::MAX as f64 {
+            return None;
+        }
+        nanos = nanos.checked_add(quantity as u128)?;
+    }
+    let seconds = nanos / 1_000_000_000;
+    if seconds > u128::from(u64::MAX) {
+        return None;
+    }
+    Some(Duration::new(
+        seconds as u64,
+        (nanos % 1_000_000_000) as u32,
+    ))
+}
+
+/**
+ * Canonical text of a duration, as parse_duration() reads it: `1h30m`, `1m0.5s`, `250ms`, `0s`.
+ */
+pub fn format_duration(duration: Duration) -> String {
+    let mut text = String::new();
+    let mut seconds = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+    for &(unit, length) in [("w", 604800), ("d", 86400), ("h", 3600), ("m", 60)].iter() {
+        if seconds >= length {
+            text.push_str(&format!("{}{}", seconds / length, unit));
+            seconds %= length;
+        }
+    }
+    if nanos == 0 {
+        if seconds > 0 || text.is_empty() {
+            text.push_str(&format!("{}s", seconds));
+        }
+    } else if seconds > 0 || !text.is_empty() {
+        let fraction = format!("{:09}", nanos);
+        text.push_str(&format!("{}.{}s", seconds, fraction.trim_end_matches('0')));
+    } else if nanos.is_multiple_of(1_000_000) {
+        text.push_str(&format!("{}ms", nanos / 1_000_000));
+    } else if nanos.is_multiple_of(1000) {
+        text.push_str(&format!("{}us", nanos / 1000));
+    } else {
+        text.push_str(&format!("{}ns", nanos));
+    }
+    text
+}
+
+/// Units of sizes, from the largest one.
+const SIZE_UNITS: [(&str, u64); 10] = [
+    ("PiB", 1 << 50),
+    ("PB", 1_000_000_000_000_000),
+    ("TiB", 1 << 40),
+    ("TB", 1_000_000_000_000),
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("kB", 1000),
+];
+
+/**
+ * Parse a size in bytes: a number followed by an optional unit, `B`, `kB` (1000 bytes), `KiB`
+ * (1024 bytes), `MB`, `MiB`, up to `PB` and `PiB`. Units are case-insensitive, `K`, `M`, `G`, `T`
+ * and `P` being decimal units.
+ */
+pub fn parse_size(value: &str) -> Option<u64> {
+    let quantities = quantities(value)?;
+    if quantities.len() != 1 {
+        return None;
+    }
+    let (number, unit) = quantities[0];
+    let unit = unit.to_lowercase();
+    let factor = match unit.as_str() {
+        "" | "b" => 1,
+        _ => {
+            let unit = if unit.ends_with('b') {
+                unit
+            } else {
+                format!("{}b", unit)
+            };
+            SIZE_UNITS
+                .iter()
+                .find(|&&(name, _)| name.to_lowercase() == unit)
+                .map(|&(_, factor)| factor)?
+        }
+    };
+    // Computed exactly on the digits, as 1.5 * 1000 = 15 * 1000 / 10.
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits: u128 = format!("{}{}", whole, fraction).parse().ok()?;
+    let scale = 10u128.checked_pow(fraction.len() as u32)?;
+    let bytes = digits.checked_mul(u128::from(factor))?;
+    // A fraction of byte is an error.
+    if bytes % scale != 0 {
+        return None;
+    }
+    u64::try_from(bytes / scale).ok()
+}
+
+/**
+ * Canonical text of a size, as parse_size() reads it: the largest unit dividing it, as `4KiB`,
+ * `10MB` or `1023B`.
+ */
+pub fn format_size(bytes: u64) -> String {
+    for &(unit, factor) in SIZE_UNITS.iter() {
+        if bytes >= factor && bytes.is_multiple_of(factor) {
+            return format!("{}{}", bytes / factor, unit);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, format_size, parse_duration, parse_int, parse_size};
+    use std::time::Duration;
+
+    #[test]
+    fn integers() {
+        assert_eq!(Some(255u8), parse_int("0xff"));
+        assert_eq!(Some(-16i64), parse_int(" -0x10 "));
+        assert_eq!(Some(8i32), parse_int("0o10"));
+        assert_eq!(Some(5u16), parse_int("0B101"));
+        assert_eq!(Some(1_000_000u32), parse_int("1_000_000"));
+        assert_eq!(Some(3isize), parse_int("03"));
+        assert_eq!(Some(u128::MAX), parse_int(&u128::MAX.to_string()));
+        assert_eq!(None, parse_int::<u8>("256"));
+        assert_eq!(None, parse_int::<u8>("-1"));
+        for value in ["", "0x", "_1", "1_", "1__0", "0x-1", "--1", "1.0", "a"].iter() {
+            assert_eq!(
+                None,
+                parse_int::<i32>(value),
+                "{:?} is not an integer",
+                value
+            );
+        }
+    }
+    #[test]
+    fn durations() {
+        assert_eq!(Some(Duration::from_secs(30)), parse_duration("30s"));
+        assert_eq!(Some(Duration::from_secs(300)), parse_duration("5m"));
+        assert_eq!(Some(Duration::from_secs(5400)), parse_duration("1h30m"));
+        assert_eq!(Some(Duration::from_secs(5400)), parse_duration("1h 30 min"));
+        assert_eq!(Some(Duration::from_millis(1500)), parse_duration("1.5s"));
+        assert_eq!(Some(Duration::from_millis(250)), parse_duration("250ms"));
+        assert_eq!(Some(Duration::from_secs(10)), parse_duration("10"));
+        for value in ["", "s", "10 20", "5 parsecs", "-1s", "1h30"].iter() {
+            assert_eq!(None, parse_duration(value), "{:?} is not a duration", value);
+        }
+        for &(text, duration) in [
+            ("1h30m", Duration::from_secs(5400)),
+            ("0s", Duration::from_secs(0)),
+            ("1d1s", Duration::from_secs(86401)),
+            ("1m0.5s", Duration::from_millis(60500)),
+            ("250ms", Duration::from_millis(250)),
+            ("5us", Duration::from_micros(5)),
+            ("7ns", Duration::from_nanos(7)),
+        ]
+        .iter()
+        {
+            assert_eq!(text, format_duration(duration));
+            assert_eq!(Some(duration), parse_duration(text));
+        }
+    }
+    #[test]
+    fn sizes() {
+        assert_eq!(Some(10_000_000), parse_size("10MB"));
+        assert_eq!(Some(4096), parse_size("4KiB"));
+        assert_eq!(Some(4096), parse_size("4 kib"));
+        assert_eq!(Some(1500), parse_size("1.5k"));
+        assert_eq!(Some(512), parse_size("512"));
+        assert_eq!(Some(512), parse_size("512B"));
+        assert_eq!(Some(1 << 30), parse_size("1GiB"));
+        assert_eq!(Some(1_100_000_000), parse_size("1.1GB"));
+        assert_eq!(Some(512), parse_size("0.5KiB"));
+        assert_eq!(Some((1 << 53) + 1), parse_size("9007199254740993"));
+        assert_eq!(Some(u64::MAX), parse_size("18446744073709551615B"));
+        assert_eq!(Some(15 << 59), parse_size("7680PiB"));
+        assert_eq!(
+            Some(1_000_000_000_000_001),
+            parse_size("1000000000000.001kB")
+        );
+        assert_eq!(None, parse_size("18446744073709551616"));
+        assert_eq!(None, parse_size("16384PiB"));
+        for value in ["", "MB", "1.5B", "10 XB", "10bb", "1MB 1kB", "-1B"].iter() {
+            assert_eq!(None, parse_size(value), "{:?} is not a size", value);
+        }
+        assert_eq!("4KiB", format_size(4096));
+        assert_eq!("10MB", format_size(10_000_000));
+        assert_eq!("1000KiB", format_size(1_024_000));
+        assert_eq!("1023B", format_size(1023));
+        assert_eq!("0B", format_size(0));
+        assert_eq!(Some(u64::MAX), parse_size(&format_size(u64::MAX)));
+    }
+}
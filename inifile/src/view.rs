@@ -0,0 +1,291 @@
+//! Borrowing access to the sections and options of an `IniFile`, without cloning names or values.
+//!
+//! ```ignore
+//! for section in &ini {
+//!     for (key, value) in section {
+//!         println!("[{}] {} = {}", section.name(), key, value);
+//!     }
+//! }
+//! let port = &ini.section("server").unwrap()["port"];
+//! ```
+//!
+//! Values are raw, as `get_raw()` returns them: overrides and the default section are taken into account,
+//! interpolation is not.
+
+use std::fmt;
+use std::ops::Index;
+use std::vec;
+
+use IniFile;
+
+/**
+ * View on a section of an `IniFile`: its own options, then the options of the default section it does
+ * not override.
+ */
+#[derive(Clone, Copy)]
+pub struct Section<'a> {
+    ini: &'a IniFile,
+    name: &'a str,
+}
+
+impl<'a> Section<'a> {
+    /**
+     * Indicates whether the option is visible in the section.
+     */
+    pub fn contains_key(&self, option: &str) -> bool {
+        self.get(option).is_some()
+    }
+    /**
+     * Raw value of the option, or `None` if it is not visible in the section.
+     */
+    pub fn get(&self, option: &str) -> Option<&'a str> {
+        self.ini
+            .lookup_value(self.name, &self.ini.optionxform(option))
+            .map(String::as_str)
+    }
+    /**
+     * Indicates whether no option is visible in the section.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.keys().is_empty()
+    }
+    /**
+     * Iterator over the `(option, raw value)` pairs of the section, in file order.
+     */
+    pub fn iter(&self) -> Options<'a> {
+        Options {
+            keys: self.keys().into_iter(),
+            section: *self,
+        }
+    }
+    /**
+     * Normalized names of the options visible in the section, as `IniFile::options()` lists them.
+     */
+    fn keys(&self) -> Vec<&'a str> {
+        let ini = self.ini;
+        let mut keys = own_keys(ini, self.name);
+        if self.name != ini.default_section && ini.dialect.default_fallback() {
+            for key in own_keys(ini, &ini.default_section) {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+    /**
+     * Number of options visible in the section.
+     */
+    pub fn len(&self) -> usize {
+        self.keys().len()
+    }
+    /**
+     * Name of the section.
+     */
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+}
+
+impl<'a> fmt::Debug for Section<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] ", self.name)?;
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/**
+ * Raw value of an option of the section; panic!() if it is not visible in the section.
+ */
+impl<'a, 'b> Index<&'b str> for Section<'a> {
+    type Output = str;
+
+    fn index(&self, option: &'b str) -> &str {
+        match self.get(option) {
+            Some(value) => value,
+            None => panic!("no option {:?} in section [{}]", option, self.name),
+        }
+    }
+}
+
+impl<'a> IntoIterator for Section<'a> {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Options<'a>;
+
+    fn into_iter(self) -> Options<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &Section<'a> {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Options<'a>;
+
+    fn into_iter(self) -> Options<'a> {
+        self.iter()
+    }
+}
+
+/**
+ * Option names of a section, without those of the default section; overridden options missing from the
+ * file come last. Borrowing counterpart of `IniFile::own_options()`.
+ */
+fn own_keys<'a>(ini: &'a IniFile, section: &str) -> Vec<&'a str> {
+    let mut keys: Vec<&'a str> = match ini.sections.get(section) {
+        Some(options) => options.keys().collect(),
+        None => vec![],
+    };
+    for o in ini.overrides.iter() {
+        if o.section == section && !keys.contains(&o.option.as_str()) {
+            keys.push(&o.option);
+        }
+    }
+    keys
+}
+
+/**
+ * Iterator over the `(option, raw value)` pairs of a section, see `Section::iter()`.
+ */
+#[derive(Clone)]
+pub struct Options<'a> {
+    keys: vec::IntoIter<&'a str>,
+    section: Section<'a>,
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let key = self.keys.next()?;
+        let section = self.section;
+        // Every key is visible in the section, and already normalized.
+        let value = section.ini.lookup_value(section.name, key)?;
+        Some((key, value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Options<'a> {}
+
+/**
+ * Iterator over the sections of an `IniFile`, the default section excluded, see `IniFile::iter()`.
+ */
+#[derive(Clone)]
+pub struct Sections<'a> {
+    ini: &'a IniFile,
+    names: vec::IntoIter<&'a str>,
+}
+
+impl<'a> Sections<'a> {
+    pub(crate) fn new(ini: &'a IniFile) -> Sections<'a> {
+        let mut names: Vec<&'a str> = ini
+            .sections
+            .keys()
+            .filter(|section| *section != ini.default_section)
+            .collect();
+        for o in ini.overrides.iter() {
+            if o.section != ini.default_section && !names.contains(&o.section.as_str()) {
+                names.push(&o.section);
+            }
+        }
+        Sections {
+            ini,
+            names: names.into_iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = Section<'a>;
+
+    fn next(&mut self) -> Option<Section<'a>> {
+        let name = self.names.next()?;
+        Some(Section {
+            ini: self.ini,
+            name,
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Sections<'a> {}
+
+/**
+ * View on a section of the configuration, or `None` if the section does not exist; see `IniFile::section()`.
+ */
+pub(crate) fn section<'a>(ini: &'a IniFile, name: &str) -> Option<Section<'a>> {
+    if name == ini.default_section {
+        return Some(Section {
+            ini,
+            name: &ini.default_section,
+        });
+    }
+    let stored = ini.sections.get_key_value(name).map(|(name, _)| name);
+    let overridden = ini
+        .overrides
+        .iter()
+        .find(|o| o.section == name)
+        .map(|o| o.section.as_str());
+    stored.or(overridden).map(|name| Section { ini, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use IniFile;
+
+    fn ini() -> IniFile {
+        let mut ini: IniFile =
+            "[DEFAULT]\ntimeout = 5\n[server]\nHost = example.org\nport = 80\n[empty]\n"
+                .parse()
+                .unwrap();
+        ini.set_override("server", "port", "8080", "test");
+        ini.set_override("cache", "size", "1", "test");
+        ini
+    }
+
+    #[test]
+    fn iterate() {
+        let ini = ini();
+        let names: Vec<&str> = ini.iter().map(|section| section.name()).collect();
+        assert_eq!(vec!["server", "empty", "cache"], names);
+        let server = ini.section("server").unwrap();
+        let pairs: Vec<(&str, &str)> = server.iter().collect();
+        assert_eq!(
+            vec![("host", "example.org"), ("port", "8080"), ("timeout", "5")],
+            pairs
+        );
+        assert_eq!(3, server.len());
+        let mut count = 0;
+        for section in &ini {
+            for (key, value) in section {
+                assert_eq!(ini.get_raw(section.name(), key), value);
+                count += 1;
+            }
+        }
+        assert_eq!(3 + 1 + 2, count);
+        let default = ini.section("DEFAULT").unwrap();
+        assert_eq!(vec![("timeout", "5")], default.iter().collect::<Vec<_>>());
+        assert!(ini.section("missing").is_none());
+    }
+    #[test]
+    fn index() {
+        let ini = ini();
+        let server = ini.section("server").unwrap();
+        assert_eq!("example.org", &server["HOST"]);
+        assert_eq!("8080", &server["port"]);
+        assert_eq!("5", &ini.section("empty").unwrap()["timeout"]);
+        assert_eq!(Some("1"), ini.section("cache").unwrap().get("size"));
+        assert!(!server.contains_key("missing"));
+        assert!(!server.is_empty());
+    }
+    #[test]
+    #[should_panic(expected = "no option \"missing\" in section [server]")]
+    fn index_missing() {
+        let ini = ini();
+        let _ = &ini.section("server").unwrap()["missing"];
+    }
+}
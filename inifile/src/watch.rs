@@ -0,0 +1,306 @@
+//! Hot reload of an INI file, by polling: no inotify or other platform dependency.
+//!
+//! The file is read again when its modification time or its size, or those of a file it includes, change,
+//! and parsed again only when the hash of the content of one of these files changes, or an included file
+//! disappears. Callbacks receive the changed options, but the overridden ones, whose value stays the
+//! override. If the new content can not be parsed, the previous configuration is kept.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use diff::{changes, Change};
+use error::IniResult;
+use IniFile;
+
+/// Callback receiving the changes and the new configuration.
+type Callback = Box<dyn FnMut(&[Change], &IniFile)>;
+
+/// Modification time and size of a file.
+type Stamp = (SystemTime, u64);
+
+/// Path, and stamp and hash of the content of a file when it was last read, `None` if it was missing.
+type Snapshot = (PathBuf, Option<(Stamp, u64)>);
+
+/**
+ * Configuration read from a file, reloaded by `poll()` when the file changes.
+ */
+pub struct Watcher {
+    callbacks: Vec<Callback>,
+    /// The file then its included files, as last read, parsed or not
+    files: Vec<Snapshot>,
+    /// Current configuration
+    ini: IniFile,
+}
+
+impl Watcher {
+    /**
+     * Getter on the current configuration.
+     */
+    pub fn config(&self) -> &IniFile {
+        &self.ini
+    }
+    /**
+     * Watch the configuration read from its file path: its settings (default section, interpolation,
+     * option names normalization, boolean words) and its overrides are kept on reload.
+     */
+    pub fn from_ini(ini: IniFile) -> IniResult<Watcher> {
+        Ok(Watcher {
+            callbacks: Vec::new(),
+            files: snapshot(&ini)?,
+            ini,
+        })
+    }
+    /**
+     * Read the file and watch it.
+     */
+    pub fn new<P: AsRef<Path>>(path: P) -> IniResult<Watcher> {
+        Watcher::from_ini(IniFile::load(path)?)
+    }
+    /**
+     * Register a callback, called by `poll()` with the changed options and the new configuration.
+     */
+    pub fn on_change<F: FnMut(&[Change], &IniFile) + 'static>(&mut self, callback: F) {
+        self.callbacks.push(Box::new(callback));
+    }
+    /**
+     * Reload the file if it or one of its included files changed, call the callbacks and return the changes.
+     * If the file can not be read, or the configuration can not be read or parsed, return the error and keep
+     * the previous configuration; the same faulty content is not reported twice.
+     */
+    pub fn poll(&mut self) -> IniResult<Vec<Change>> {
+        let unchanged = self
+            .files
+            .iter()
+            .all(|&(ref path, state)| stamp(path) == state.map(|(stamp, _)| stamp));
+        if unchanged {
+            return Ok(vec![]);
+        }
+        // The file itself, its included files as last read, which it may not include any more
+        let path = self.files[0].0.clone();
+        let (stamp, content) = read(&path)?;
+        let mut files = vec![(path, Some((stamp, hash(&content))))];
+        files.extend(
+            self.files[1..]
+                .iter()
+                .map(|(path, _)| (path.clone(), state(path))),
+        );
+        let same = files
+            .iter()
+            .zip(self.files.iter())
+            .all(|(new, old)| new.1.map(|(_, hash)| hash) == old.1.map(|(_, hash)| hash));
+        self.files = files;
+        if same {
+            return Ok(vec![]);
+        }
+        let mut ini = self.ini.with_settings();
+        ini.try_read_from(&content[..])?;
+        // The file may include other files now.
+        self.files = snapshot(&ini)?;
+        let path = PathBuf::from(ini.filepath());
+        let changes: Vec<Change> = changes(&self.ini, &ini)
+            .into_iter()
+            .filter(|change| !overridden(&ini, change))
+            .collect();
+        self.ini = ini;
+        if !changes.is_empty() {
+            debug!("{} reloaded, {} changes", path.display(), changes.len());
+            for callback in self.callbacks.iter_mut() {
+                callback(&changes, &self.ini);
+            }
+        }
+        Ok(changes)
+    }
+    /**
+     * Poll the file every `interval` while `running` is true; errors are logged.
+     */
+    pub fn run(&mut self, interval: Duration, running: &AtomicBool) {
+        while running.load(Ordering::SeqCst) {
+            if let Err(e) = self.poll() {
+                warn!("failed to reload {:?}: {}", self.ini.filepath(), e);
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+/**
+ * Modification time, size and content of the file.
+ */
+fn read(path: &Path) -> IniResult<(Stamp, Vec<u8>)> {
+    let metadata = fs::metadata(path)?;
+    let content = fs::read(path)?;
+    Ok(((metadata.modified()?, metadata.len()), content))
+}
+
+/**
+ * Modification time and size of the file, `None` if it can not be read.
+ */
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/**
+ * Stamp and hash of the content of the file, `None` if it can not be read.
+ */
+fn state(path: &Path) -> Option<(Stamp, u64)> {
+    read(path)
+        .ok()
+        .map(|(stamp, content)| (stamp, hash(&content)))
+}
+
+/**
+ * Whether the change is of an option whose value is overridden, and does not change.
+ */
+fn overridden(ini: &IniFile, change: &Change) -> bool {
+    match *change {
+        Change::Added {
+            ref section,
+            ref option,
+            ..
+        }
+        | Change::Removed {
+            ref section,
+            ref option,
+            ..
+        }
+        | Change::Changed {
+            ref section,
+            ref option,
+            ..
+        } => ini.is_overridden(section, option),
+        _ => false,
+    }
+}
+
+/**
+ * Snapshots of the file of the configuration, which must be readable, then of its included files.
+ */
+fn snapshot(ini: &IniFile) -> IniResult<Vec<Snapshot>> {
+    let path = PathBuf::from(ini.filepath());
+    let (stamp, content) = read(&path)?;
+    let file = (path, Some((stamp, hash(&content))));
+    Ok(iter::once(file)
+        .chain(
+            ini.includes()
+                .into_iter()
+                .map(|path| (path.to_path_buf(), state(path))),
+        )
+        .collect())
+}
+
+fn hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watcher;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use test_directory;
+    use IniError;
+    use IniFile;
+
+    #[test]
+    fn reload() {
+        let filepath = "src/data/watch_test.ini";
+        fs::write(filepath, "[server]\nport = 80\nhost = a\n").unwrap();
+        let mut ini = IniFile::load(filepath).unwrap();
+        ini.set_override("server", "host", "overridden", "test");
+        let mut watcher = Watcher::from_ini(ini).unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen_by_callback = seen.clone();
+        watcher.on_change(move |changes, ini| {
+            let port = ini.get("server", "port");
+            seen_by_callback.borrow_mut().push((changes.len(), port));
+        });
+        assert!(watcher.poll().unwrap().is_empty());
+
+        fs::write(filepath, "[server]\nport = 8080\nhost = b\n").unwrap();
+        let changes = watcher.poll().unwrap();
+        assert_eq!(1, changes.len(), "{:?}", changes);
+        assert_eq!("overridden", watcher.config().get("server", "host"));
+
+        fs::write(filepath, "[server\n").unwrap();
+        let result = watcher.poll();
+        match result {
+            Err(IniError::Syntax(ref diagnostic)) if diagnostic.line() == Some(1) => (),
+            other => panic!("IniError::Syntax expected, not {:?}", other),
+        }
+        assert_eq!("8080", watcher.config().get("server", "port"));
+        assert!(watcher.poll().unwrap().is_empty());
+
+        fs::write(filepath, "[server]\nport = 8080\nhost = b\n\n").unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        fs::remove_file(filepath).unwrap();
+        assert_eq!(vec![(1, "8080".to_string())], *seen.borrow());
+        assert!(watcher.poll().is_err());
+    }
+    #[test]
+    fn reload_included() {
+        let directory = &test_directory("watch-include");
+        let common = directory.join("common.ini");
+        fs::write(
+            directory.join("main.ini"),
+            "[server]\n!include common.ini\n",
+        )
+        .unwrap();
+        fs::write(&common, "[server]\nport = 80\n").unwrap();
+        let mut watcher = Watcher::new(directory.join("main.ini")).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        fs::write(&common, "[server]\nport = 8080\n").unwrap();
+        let changes = watcher.poll();
+        fs::write(&common, "[server]\nport = 8080\n\n").unwrap();
+        let unchanged = watcher.poll();
+        fs::remove_dir_all(directory).unwrap();
+        assert_eq!(1, changes.unwrap().len());
+        assert_eq!("8080", watcher.config().get("server", "port"));
+        assert!(unchanged.unwrap().is_empty());
+    }
+    #[test]
+    fn reload_removed_include() {
+        let directory = test_directory("watch-removed-include");
+        let main = directory.join("main.ini");
+        let common = directory.join("common.ini");
+        fs::write(&main, "[server]\n!include common.ini\nhost = a\n").unwrap();
+        fs::write(&common, "[server]\nport = 80\n").unwrap();
+        let mut watcher = Watcher::new(&main).unwrap();
+        // The included file disappears first: an error, reported once.
+        fs::remove_file(&common).unwrap();
+        let missing = watcher.poll();
+        let reported = watcher.poll();
+        // Then the file does not include it any more.
+        fs::write(&main, "[server]\nhost = b\n").unwrap();
+        let changes = watcher.poll();
+        fs::write(&common, "[server]\nport = 8080\n").unwrap();
+        fs::write(&main, "[server]\n!include common.ini\nhost = b\n").unwrap();
+        let included = watcher.poll();
+        // The file stops including it, then it disappears.
+        fs::write(&main, "[server]\nhost = c\n").unwrap();
+        fs::remove_file(&common).unwrap();
+        let removed = watcher.poll();
+        let unchanged = watcher.poll();
+        fs::remove_dir_all(&directory).unwrap();
+        match missing {
+            Err(IniError::Include { ref path, .. }) => assert_eq!(common, *path),
+            other => panic!("IniError::Include expected, not {:?}", other),
+        }
+        assert!(reported.unwrap().is_empty());
+        assert_eq!(2, changes.unwrap().len());
+        assert_eq!(1, included.unwrap().len());
+        assert_eq!(2, removed.unwrap().len());
+        assert!(unchanged.unwrap().is_empty());
+        assert_eq!("c", watcher.config().get("server", "host"));
+        assert!(!watcher.config().has_option("server", "port"));
+    }
+}